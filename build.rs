@@ -45,6 +45,56 @@ macro_rules! base_app {
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(6..=20))
+                        .help(gettext("Length of the audio recognized at each request, in seconds (12 by default)"))
+                )
+                .arg(
+                    Arg::new("voting-windows")
+                        .long("voting-windows")
+                        .value_parser(clap::value_parser!(u64).range(1..=3))
+                        .help(gettext("Number of staggered windows recognized at each request, only reporting the songs most of them agree on"))
+                )
+                .arg(
+                    Arg::new("capture-archive")
+                        .long("capture-archive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Keep the recorded audio of the recognition requests, see the \"captures\" subcommand"))
+                )
+                .arg(
+                    Arg::new("no-audio-gating")
+                        .long("no-audio-gating")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Also make recognition requests when only silence or speech is heard"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -60,12 +110,145 @@ macro_rules! base_app {
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("no-history")
+                        .long("no-history")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't add the recognized songs to the song history"))
+                )
+                .arg(
+                    Arg::new("no-scrobble")
+                        .long("no-scrobble")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't submit the recognized songs to ListenBrainz, even when a token is set in the preferences"))
+                )
+                .arg(
+                    Arg::new("listenbrainz-url")
+                        .long("listenbrainz-url")
+                        .conflicts_with("no-scrobble")
+                        .help(gettext("The root URL of the ListenBrainz-compatible server to submit the recognized songs to"))
+                )
+                .arg(
+                    Arg::new("listenbrainz-token")
+                        .long("listenbrainz-token")
+                        .conflicts_with("no-scrobble")
+                        .help(gettext("The user token used to submit the recognized songs, instead of the one set in the preferences"))
+                )
+                .arg(
+                    Arg::new("no-webhooks")
+                        .long("no-webhooks")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't notify the webhooks set in the preferences"))
+                )
                 .arg(
                     Arg::new("disable-mpris")
                         .long("disable-mpris")
                         .action(ArgAction::SetTrue)
                         .help(gettext("Disable MPRIS support"))
                 )
+                .arg(
+                    Arg::new("disable-dbus")
+                        .long("disable-dbus")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Disable the D-Bus control interface"))
+                )
+                .arg(
+                    Arg::new("http-server")
+                        .long("http-server")
+                        .value_name("ADDRESS")
+                        .help(gettext("Serve a now playing page and JSON endpoints on this address, such as 127.0.0.1:8080"))
+                )
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about(gettext("Run in the background, recognizing songs from an audio device and answering JSON-RPC requests from local clients over a Unix socket"))
+                .arg(
+                    Arg::new("socket")
+                        .long("socket")
+                        .help(gettext("The path of the Unix socket to listen on"))
+                )
+                .arg(
+                    Arg::new("audio-device")
+                        .short('d')
+                        .long("audio-device")
+                        .help(gettext("Specify the audio device to use"))
+                )
+                .arg(
+                    Arg::new("idle")
+                        .long("idle")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't start recording before a client requests it"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(6..=20))
+                        .help(gettext("Length of the audio recognized at each request, in seconds (12 by default)"))
+                )
+                .arg(
+                    Arg::new("voting-windows")
+                        .long("voting-windows")
+                        .value_parser(clap::value_parser!(u64).range(1..=3))
+                        .help(gettext("Number of staggered windows recognized at each request, only reporting the songs most of them agree on"))
+                )
+                .arg(
+                    Arg::new("capture-archive")
+                        .long("capture-archive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Keep the recorded audio of the recognition requests, see the \"captures\" subcommand"))
+                )
+                .arg(
+                    Arg::new("no-audio-gating")
+                        .long("no-audio-gating")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Also make recognition requests when only silence or speech is heard"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("no-history")
+                        .long("no-history")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't add the recognized songs to the song history"))
+                )
+                .arg(
+                    Arg::new("http-server")
+                        .long("http-server")
+                        .value_name("ADDRESS")
+                        .help(gettext("Serve a now playing page and JSON endpoints on this address, such as 127.0.0.1:8080"))
+                )
         )
         .subcommand(
             Command::new("recognize")
@@ -92,6 +275,44 @@ macro_rules! base_app {
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(6..=20))
+                        .help(gettext("Length of the audio recognized at each request, in seconds (12 by default)"))
+                )
+                .arg(
+                    Arg::new("voting-windows")
+                        .long("voting-windows")
+                        .value_parser(clap::value_parser!(u64).range(1..=3))
+                        .help(gettext("Number of staggered windows recognized at each request, only reporting the songs most of them agree on"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -107,6 +328,12 @@ macro_rules! base_app {
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("no-history")
+                        .long("no-history")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't add the recognized songs to the song history"))
+                )
                 .arg(
                     Arg::new("input_file")
                         .required(false)
@@ -146,6 +373,31 @@ macro_rules! base_app {
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
         )
         .subcommand(
             Command::new("audio-file-to-fingerprint")
@@ -165,6 +417,508 @@ macro_rules! base_app {
                         .help(gettext("The data-URI Shazam fingerprint to recognize."))
                 )
         )
+        .subcommand(
+            Command::new("scan")
+                .about(gettext("Recognize every song within a long recording (DJ set, radio show...) and print a timestamped tracklist."))
+                .arg(
+                    Arg::new("window")
+                        .short('w')
+                        .long("window")
+                        .default_value("12")
+                        .value_parser(clap::value_parser!(u64).range(3..=20))
+                        .help(gettext("Length of the recognized windows, in seconds"))
+                )
+                .arg(
+                    Arg::new("step")
+                        .short('s')
+                        .long("step")
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help(gettext("Interval between the start of two windows, in seconds"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Pause between two requests in seconds (increase if you are rate-limited, ignored with the local database)"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .conflicts_with("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in JSON"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in the CSV format"))
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .value_parser(["cue", "ffmetadata", "webvtt", "audacity"])
+                        .conflicts_with_all(["json", "csv"])
+                        .help(gettext("Print the tracklist as a CUE sheet, FFmpeg chapters (;FFMETADATA1), WebVTT subtitles or an Audacity label track"))
+                )
+                .arg(
+                    Arg::new("input_file")
+                        .required(true)
+                        .help(gettext("The audio file to scan."))
+                )
+        )
+        .subcommand(
+            Command::new("batch")
+                .about(gettext("Recognize many audio files or directories, printing one result per file in the JSON Lines or CSV format."))
+                .arg(
+                    Arg::new("recursive")
+                        .short('r')
+                        .long("recursive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Walk directories recursively"))
+                )
+                .arg(
+                    Arg::new("include")
+                        .long("include")
+                        .action(ArgAction::Append)
+                        .help(gettext("Only process the files whose name matches this pattern (such as \"*.flac\"), may be repeated"))
+                )
+                .arg(
+                    Arg::new("exclude")
+                        .long("exclude")
+                        .action(ArgAction::Append)
+                        .help(gettext("Skip the files whose name matches this pattern, may be repeated"))
+                )
+                .arg(
+                    Arg::new("workers")
+                        .short('w')
+                        .long("workers")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help(gettext("Number of files decoded in parallel (defaults to the number of CPUs)"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Pause between two requests in seconds (increase if you are rate-limited, ignored with the local database)"))
+                )
+                .arg(
+                    Arg::new("state-file")
+                        .long("state-file")
+                        .help(gettext("File recording the processed files, used to resume an interrupted batch"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the results in the CSV format instead of JSON Lines"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("paths")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The audio files and directories to recognize."))
+                )
+        )
+        .subcommand(
+            Command::new("organize")
+                .about(gettext("Recognize audio files and rename or move them according to a template, such as \"{artist}/{album}/{title}.{ext}\"."))
+                .arg(
+                    Arg::new("template")
+                        .short('t')
+                        .long("template")
+                        .default_value("{artist}/{album}/{title}.{ext}")
+                        .help(gettext("The path of the organized files, relative to the destination directory. Available fields: {artist}, {title}, {album}, {year}, {genre}, {isrc}, {ext}"))
+                )
+                .arg(
+                    Arg::new("destination")
+                        .short('o')
+                        .long("destination")
+                        .default_value(".")
+                        .help(gettext("The directory to move the files into"))
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .short('n')
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print the moves which would be made"))
+                )
+                .arg(
+                    Arg::new("journal")
+                        .long("journal")
+                        .help(gettext("The file recording the moves, in order to undo them (a new file in the SongRec data directory by default)"))
+                )
+                .arg(
+                    Arg::new("undo")
+                        .long("undo")
+                        .conflicts_with("paths")
+                        .help(gettext("Move back the files listed in the given journal file"))
+                )
+                .arg(
+                    Arg::new("recursive")
+                        .short('r')
+                        .long("recursive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Walk directories recursively"))
+                )
+                .arg(
+                    Arg::new("include")
+                        .long("include")
+                        .action(ArgAction::Append)
+                        .help(gettext("Only process the files whose name matches this pattern (such as \"*.flac\"), may be repeated"))
+                )
+                .arg(
+                    Arg::new("exclude")
+                        .long("exclude")
+                        .action(ArgAction::Append)
+                        .help(gettext("Skip the files whose name matches this pattern, may be repeated"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Pause between two requests in seconds (increase if you are rate-limited, ignored with the local database)"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("paths")
+                        .required_unless_present("undo")
+                        .num_args(1..)
+                        .help(gettext("The audio files and directories to organize."))
+                )
+        )
+        .subcommand(
+            Command::new("tag")
+                .about(gettext("Recognize audio files and write the obtained information (title, artist, album, year, genre, ISRC, cover art) into their tags."))
+                .arg(
+                    Arg::new("dry-run")
+                        .short('n')
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print the changes which would be made to the tags"))
+                )
+                .arg(
+                    Arg::new("only-fill-empty")
+                        .short('e')
+                        .long("only-fill-empty")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't overwrite the tags which already have a value"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("input_files")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The audio files to recognize and tag."))
+                )
+        )
+        .subcommand(
+            Command::new("index")
+                .about(gettext("Fingerprint the audio files of a directory into the local database, for offline recognition with \"--local-db\"."))
+                .arg(
+                    Arg::new("directory")
+                        .required(true)
+                        .help(gettext("The directory to index recursively."))
+                )
+                .arg(
+                    Arg::new("database")
+                        .long("database")
+                        .help(gettext("The local fingerprint database file to write"))
+                )
+                .arg(
+                    Arg::new("rebuild")
+                        .long("rebuild")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Discard the existing database contents instead of only adding new files"))
+                )
+        )
+        .subcommand(
+            Command::new("history")
+                .about(gettext("Print the song history, as recorded by the GUI and the \"listen\" and \"recognize\" commands."))
+                .arg(
+                    Arg::new("list")
+                        .long("list")
+                        .value_parser(["history", "favorites"])
+                        .default_value("history")
+                        .help(gettext("Whether to read the song history or the favorites"))
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .help(gettext("Only print the songs recognized from this date (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .help(gettext("Only print the songs recognized until this date, included (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("artist")
+                        .long("artist")
+                        .help(gettext("Only print the songs whose artist name contains this text"))
+                )
+                .arg(
+                    Arg::new("genre")
+                        .long("genre")
+                        .help(gettext("Only print the songs of this genre"))
+                )
+                .arg(
+                    Arg::new("favorite")
+                        .long("favorite")
+                        .value_parser(["yes", "no"])
+                        .help(gettext("Only print the songs which are (or are not) in the favorites"))
+                )
+                .arg(
+                    Arg::new("sort")
+                        .long("sort")
+                        .value_parser(["date", "artist", "song", "genre"])
+                        .default_value("date")
+                        .help(gettext("The field to sort the songs by"))
+                )
+                .arg(
+                    Arg::new("reverse")
+                        .short('r')
+                        .long("reverse")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Sort in descending order (most recent songs first when sorting by date)"))
+                )
+                .arg(
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Print at most this number of songs"))
+                )
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Skip this number of songs first"))
+                )
+                .arg(
+                    Arg::new("dedupe")
+                        .long("dedupe")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print the most recent recognition of each song"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .conflicts_with("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print one JSON object per song, with all the stored information"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the songs in the CSV format"))
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["m3u8", "xspf", "jspf", "listenbrainz"])
+                        .conflicts_with_all(["json", "csv"])
+                        .help(gettext("Print the songs as a playlist in this format (\"listenbrainz\" being the JSON format of ListenBrainz listens)"))
+                )
+        )
+        .subcommand(
+            Command::new("statistics")
+                .about(gettext("Print statistics about the song history: top artists, genres and tracks, listening activity and new songs."))
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .help(gettext("Only count the songs recognized from this date (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .help(gettext("Only count the songs recognized until this date, included (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("top")
+                        .long("top")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("The number of entries of the rankings"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the statistics in JSON"))
+                )
+        )
+        .subcommand(
+            Command::new("import")
+                .about(gettext("Merge a history exported from the Shazam app, or the CSV history of another SongRec install, into the song history."))
+                .arg(
+                    Arg::new("favorites")
+                        .long("favorites")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Import into the favorites instead of the song history"))
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print what would be imported"))
+                )
+                .arg(
+                    Arg::new("input_files")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The CSV files to import."))
+                )
+        )
+        .subcommand(
+            Command::new("favorites")
+                .about(gettext("Manage the favorites shared with the GUI."))
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about(gettext("Add a song of the history to the favorites."))
+                        .arg(
+                            Arg::new("song")
+                                .required(true)
+                                .help(gettext("The Shazam track key or the name (\"Artist - Title\") of the song"))
+                        )
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about(gettext("Remove a song from the favorites."))
+                        .arg(
+                            Arg::new("song")
+                                .required(true)
+                                .help(gettext("The Shazam track key or the name (\"Artist - Title\") of the song"))
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("captures")
+                .about(gettext("List the audio kept for the recognition requests, from the most recent, along with the songs recognized from it. It can be recognized again with the \"recognize\" subcommand."))
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the captures in JSON Lines"))
+                )
+        )
     };
 }
 
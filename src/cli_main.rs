@@ -1,5 +1,13 @@
 use log::{error, info, warn};
+#[cfg(unix)]
+use serde_json::json;
+#[cfg(unix)]
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use chrono::Local;
@@ -8,17 +16,59 @@ use gettextrs::gettext;
 #[cfg(all(target_os = "linux", feature = "mpris"))]
 use mpris_server::PlaybackStatus;
 
+use crate::core::batch_recognition::{BatchParameters, BatchStatus, recognize_batch};
+use crate::core::capture_archive::list_captures;
+#[cfg(unix)]
+use crate::core::capture_window::CaptureSettings;
+#[cfg(unix)]
+use crate::core::daemon::{DaemonCall, DaemonRequest, device_to_json, notification, serve};
+use crate::core::file_scanner::{ScanParameters, format_timestamp, scan_file};
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
 use crate::core::http_task::http_task;
 use crate::core::microphone_thread::microphone_thread;
+use crate::core::now_playing_server::NowPlayingServer;
 use crate::core::processing_thread::processing_thread;
+#[cfg(unix)]
+use crate::core::recognition_backends::backend::recognize_by_vote;
+use crate::core::recognition_backends::backend::{get_backend, recognize_with_retries};
+use crate::core::request_scheduler::ScheduleState;
+#[cfg(unix)]
+use crate::core::request_scheduler::ScheduleStatus;
+use crate::core::scrobbling::{listen_from_song, scrobbling_task};
+#[cfg(unix)]
+use crate::core::thread_messages::DeviceListItem;
 use crate::core::thread_messages::{
-    GUIMessage, MicrophoneMessage, ProcessingMessage, spawn_big_thread,
+    GUIMessage, MicrophoneMessage, ProcessingMessage, ScrobblingMessage, SongRecognizedMessage,
+    WebhookMessage, spawn_big_thread,
 };
+use crate::core::webhooks::{WebhookEvent, webhook_task};
 
 use crate::core::preferences::{Preferences, PreferencesInterface};
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+use crate::core::thread_messages::ControlRequest;
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+use crate::plugins::dbus_control::ControlService;
 #[cfg(all(target_os = "linux", feature = "mpris"))]
 use crate::plugins::mpris_player::{get_player, update_song};
+use crate::utils::audio_tagging::{TaggingOptions, tag_file};
 use crate::utils::csv_song_history::SongHistoryRecord;
+use crate::utils::file_organizer::{
+    JournalEntry, append_to_journal, move_file, render_template, resolve_collision, undo_journal,
+};
+#[cfg(unix)]
+use crate::utils::filesystem_operations::obtain_daemon_socket_path;
+use crate::utils::filesystem_operations::{
+    obtain_history_database_path, obtain_organize_journal_path,
+};
+use crate::utils::history_database::{
+    HistoryDatabase, HistoryQuery, RecognitionDetails, SongList, store_cover_art,
+};
+use crate::utils::history_import::import_history_file;
+use crate::utils::listening_statistics::{compute_statistics, format_report};
+use crate::utils::playlist_formats::{PlaylistFormat, write_playlist};
+use crate::utils::tracklist_formats::{
+    TracklistFormat, write_audacity_labels, write_cue_sheet, write_ffmetadata, write_webvtt,
+};
 
 pub enum CLIOutputType {
     SongName,
@@ -34,10 +84,40 @@ pub struct CLIParameters {
     pub request_interval: u64,
     pub input_file: Option<String>,
     pub output_type: CLIOutputType,
+    pub recognition_backend: Option<String>,
+    pub recognition_backend_url: Option<String>,
+    pub local_database_path: Option<String>,
+    /// Append the recognized songs to the history shared with the GUI
+    pub record_history: bool,
+    /// Submit the recognized songs to ListenBrainz when a token is set,
+    /// either here or in the preferences of the GUI
+    pub enable_scrobbling: bool,
+    pub listenbrainz_url: Option<String>,
+    pub listenbrainz_token: Option<String>,
+    /// Notify the webhooks set in the preferences of the GUI
+    pub enable_webhooks: bool,
+    /// Expose the D-Bus control interface
+    pub enable_dbus: bool,
+    /// Serve the now playing page on this address
+    pub http_server_address: Option<String>,
+    /// Override the capture window settings, see capture_window.rs
+    pub capture_window_secs: Option<u64>,
+    pub voting_windows: Option<u64>,
+    /// Keep the recorded audio of the recognition requests, see capture_archive.rs
+    pub capture_archive: bool,
+    /// Skip the recognition requests for silence and speech, see audio_activity.rs
+    pub audio_gating: bool,
 }
 
-pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
-    let (gui_tx, gui_rx) = async_channel::unbounded();
+/// Start the microphone and processing threads, and the HTTP task, which
+/// report to `gui_tx`. Return the channels used to drive them.
+fn start_recognition_threads(
+    gui_tx: async_channel::Sender<GUIMessage>,
+    preferences_interface: Arc<Mutex<PreferencesInterface>>,
+) -> (
+    async_channel::Sender<MicrophoneMessage>,
+    async_channel::Sender<ProcessingMessage>,
+) {
     let (microphone_tx, microphone_rx) = async_channel::unbounded();
     let (processing_tx, processing_rx) = async_channel::unbounded();
     let (http_tx, http_rx) = async_channel::unbounded();
@@ -47,11 +127,8 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
     let processing_tx_2 = processing_tx.clone();
     let microphone_tx_2 = microphone_tx.clone();
     let microphone_tx_3 = microphone_tx.clone();
-
-    let preferences_interface = Arc::new(Mutex::new(PreferencesInterface {
-        preferences_file_path: None,
-        preferences: Preferences::with_interval(parameters.request_interval),
-    }));
+    let preferences_interface_2 = preferences_interface.clone();
+    let preferences_interface_3 = preferences_interface.clone();
 
     spawn_big_thread(move || {
         microphone_thread(
@@ -65,10 +142,107 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
     });
 
     spawn_big_thread(move || {
-        processing_thread(processing_rx, http_tx, gui_tx_3);
+        processing_thread(processing_rx, http_tx, gui_tx_3, preferences_interface_3);
     });
 
-    glib::spawn_future_local(http_task(http_rx, gui_tx, microphone_tx_3));
+    glib::spawn_future_local(http_task(
+        http_rx,
+        gui_tx,
+        microphone_tx_3,
+        preferences_interface_2,
+    ));
+
+    (microphone_tx, processing_tx)
+}
+
+fn add_to_history(
+    history_database: &mut HistoryDatabase,
+    record: &SongHistoryRecord,
+    message: &SongRecognizedMessage,
+    device_name: Option<String>,
+) {
+    let details = RecognitionDetails {
+        artist_name: Some(message.artist_name.clone()),
+        device_name,
+        shazam_json: Some(message.shazam_json.clone()),
+        cover_path: message
+            .cover_image
+            .as_ref()
+            .and_then(|cover_image| store_cover_art(&message.track_key, cover_image).ok()),
+        capture_path: message
+            .capture_path
+            .as_ref()
+            .map(|capture_path| capture_path.to_string_lossy().into_owned()),
+    };
+    if let Err(error_info) = history_database.add(SongList::History, record, &details) {
+        error!(
+            "{} {}",
+            gettext("Error when saving the song history:"),
+            error_info
+        );
+    }
+}
+
+pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
+    let (gui_tx, gui_rx) = async_channel::unbounded();
+    let (scrobbling_tx, scrobbling_rx) = async_channel::unbounded();
+    let (webhook_tx, webhook_rx) = async_channel::unbounded();
+
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    let gui_tx_2 = gui_tx.clone();
+
+    let mut preferences = Preferences::with_interval(parameters.request_interval);
+    preferences.recognition_backend = parameters.recognition_backend.clone();
+    preferences.recognition_backend_url = parameters.recognition_backend_url.clone();
+    preferences.local_database_path = parameters.local_database_path.clone();
+    preferences.capture_window_secs = parameters.capture_window_secs;
+    preferences.voting_windows = parameters.voting_windows;
+    preferences.capture_archive = Some(parameters.capture_archive);
+    preferences.skip_silence_and_speech = Some(parameters.audio_gating);
+
+    if parameters.enable_scrobbling || parameters.enable_webhooks || parameters.capture_archive {
+        let saved_preferences = PreferencesInterface::new().preferences;
+        if parameters.enable_scrobbling {
+            preferences.listenbrainz_url = parameters
+                .listenbrainz_url
+                .clone()
+                .or(saved_preferences.listenbrainz_url);
+            preferences.listenbrainz_token = parameters
+                .listenbrainz_token
+                .clone()
+                .or(saved_preferences.listenbrainz_token);
+        }
+        if parameters.enable_webhooks {
+            preferences.webhooks = saved_preferences.webhooks;
+        }
+        if parameters.capture_archive {
+            preferences.capture_archive_max_files = saved_preferences.capture_archive_max_files;
+            preferences.capture_archive_max_megabytes =
+                saved_preferences.capture_archive_max_megabytes;
+        }
+    }
+
+    let preferences_interface = Arc::new(Mutex::new(PreferencesInterface {
+        preferences_file_path: None,
+        preferences,
+    }));
+
+    if parameters.enable_scrobbling {
+        glib::spawn_future_local(scrobbling_task(
+            scrobbling_rx,
+            preferences_interface.clone(),
+        ));
+    }
+    if parameters.enable_webhooks {
+        glib::spawn_future_local(webhook_task(webhook_rx, preferences_interface.clone()));
+    }
+
+    let now_playing_server = match parameters.http_server_address {
+        Some(ref address) => Some(NowPlayingServer::start(address)?),
+        None => None,
+    };
+
+    let (microphone_tx, processing_tx) = start_recognition_threads(gui_tx, preferences_interface);
 
     let main_loop = glib::MainLoop::new(None, false);
     let loop_inner = main_loop.clone();
@@ -92,6 +266,14 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
         #[cfg(all(target_os = "linux", feature = "mpris"))]
         let mut last_cover_path = None;
 
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        let control_service = match parameters.enable_dbus && !do_recognize_once {
+            true => ControlService::start(gui_tx_2).await,
+            false => None,
+        };
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        let mut is_recording = false;
+
         let mut last_track: Option<String> = None;
 
         let audio_dev_name = parameters.audio_device.as_ref().map(|dev| dev.to_string());
@@ -105,6 +287,26 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
 
         let mut csv_writer = csv::Writer::from_writer(std::io::stdout());
 
+        // Also used to tell the favorites apart for the webhooks
+        let mut history_database = match parameters.record_history || parameters.enable_webhooks {
+            true => match obtain_history_database_path()
+                .and_then(|database_path| HistoryDatabase::open(&database_path))
+            {
+                Ok(history_database) => Some(history_database),
+                Err(error_info) => {
+                    error!(
+                        "{} {}",
+                        gettext("Error when reading the song history on the disk:"),
+                        error_info
+                    );
+                    None
+                }
+            },
+            false => None,
+        };
+        let mut current_device_name: Option<String> = None;
+        let mut last_schedule_state: Option<ScheduleState> = None;
+
         while let Ok(gui_message) = gui_rx.recv().await {
             match gui_message {
                 GUIMessage::DevicesList(device_names) => {
@@ -112,6 +314,10 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                     if input_file_name.is_some() {
                         continue;
                     }
+                    #[cfg(all(target_os = "linux", feature = "dbus"))]
+                    if let Some(ref control_service) = control_service {
+                        control_service.set_devices(&device_names);
+                    }
                     for device in device_names.iter() {
                         info!(
                             "{} {} ({})",
@@ -145,11 +351,16 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                         &device_names[0].inner_name
                     };
                     info!("{}", gettext("Using device %s").replace("%s", dev_name));
+                    current_device_name = Some(dev_name.to_owned());
                     microphone_tx
                         .try_send(MicrophoneMessage::MicrophoneRecordStart(
                             dev_name.to_owned(),
                         ))
                         .unwrap();
+                    #[cfg(all(target_os = "linux", feature = "dbus"))]
+                    {
+                        is_recording = true;
+                    }
                 }
                 GUIMessage::NetworkStatus(reachable) => {
                     #[cfg(all(target_os = "linux", feature = "mpris"))]
@@ -186,6 +397,12 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                 GUIMessage::MicrophoneRecording if !do_recognize_once => {
                     info!("{}", gettext("Recording started!"));
                 }
+                GUIMessage::RequestSchedule(status) if !do_recognize_once => {
+                    if last_schedule_state != Some(status.state) {
+                        info!("{} {}", gettext("Request schedule:"), status.describe());
+                        last_schedule_state = Some(status.state);
+                    }
+                }
                 GUIMessage::SongRecognized(message) => {
                     let track_key = Some(message.track_key.clone());
                     let song_name = format!("{} - {}", message.artist_name, message.song_name);
@@ -197,24 +414,71 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                             update_song(player, &message, &mut last_cover_path).await;
                         }
 
+                        #[cfg(all(target_os = "linux", feature = "dbus"))]
+                        if let Some(ref control_service) = control_service {
+                            control_service.song_recognized(&message).await;
+                        }
+
+                        if let Some(ref now_playing_server) = now_playing_server {
+                            now_playing_server.song_recognized(&message);
+                        }
+
                         last_track = track_key;
+
+                        if parameters.enable_scrobbling {
+                            scrobbling_tx
+                                .try_send(ScrobblingMessage::SubmitListen(listen_from_song(
+                                    &message,
+                                    Local::now(),
+                                )))
+                                .unwrap();
+                        }
+
+                        let record = SongHistoryRecord {
+                            song_name: song_name.clone(),
+                            album: Some(message.album_name.clone().unwrap_or_default()),
+                            track_key: Some(message.track_key.clone()),
+                            release_year: Some(message.release_year.clone().unwrap_or_default()),
+                            genre: Some(message.genre.clone().unwrap_or_default()),
+                            recognition_date: Local::now().format("%c").to_string(),
+                            isrc: message.isrc.clone(),
+                            label: message.label.clone(),
+                        };
+
+                        if parameters.enable_webhooks {
+                            webhook_tx
+                                .try_send(WebhookMessage::SongRecognized {
+                                    event: WebhookEvent::new(
+                                        &message,
+                                        Local::now(),
+                                        current_device_name.as_deref(),
+                                    ),
+                                    is_favorite: history_database.as_ref().is_some_and(
+                                        |history_database| {
+                                            history_database.is_favorite(&record).unwrap_or(false)
+                                        },
+                                    ),
+                                })
+                                .unwrap();
+                        }
+
+                        if parameters.record_history
+                            && let Some(ref mut history_database) = history_database
+                        {
+                            add_to_history(
+                                history_database,
+                                &record,
+                                &message,
+                                current_device_name.clone(),
+                            );
+                        }
+
                         match parameters.output_type {
                             CLIOutputType::JSON => {
                                 println!("{}", message.shazam_json);
                             }
                             CLIOutputType::CSV => {
-                                csv_writer
-                                    .serialize(SongHistoryRecord {
-                                        song_name,
-                                        album: Some(message.album_name.unwrap_or_default()),
-                                        track_key: Some(message.track_key),
-                                        release_year: Some(
-                                            message.release_year.unwrap_or_default(),
-                                        ),
-                                        genre: Some(message.genre.unwrap_or_default()),
-                                        recognition_date: Local::now().format("%c").to_string(),
-                                    })
-                                    .unwrap();
+                                csv_writer.serialize(record).unwrap();
                                 csv_writer.flush().unwrap();
                             }
                             CLIOutputType::SongName => {
@@ -226,6 +490,52 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
                         break;
                     }
                 }
+                #[cfg(all(target_os = "linux", feature = "dbus"))]
+                GUIMessage::RemoteControl(request) => match request {
+                    ControlRequest::StartRecognition(device_name) => {
+                        if device_name.is_some() {
+                            current_device_name = device_name;
+                        }
+                        if let Some(ref device_name) = current_device_name {
+                            info!("{}", gettext("Using device %s").replace("%s", device_name));
+                            microphone_tx
+                                .try_send(MicrophoneMessage::MicrophoneRecordStop)
+                                .unwrap();
+                            microphone_tx
+                                .try_send(MicrophoneMessage::MicrophoneRecordStart(
+                                    device_name.to_owned(),
+                                ))
+                                .unwrap();
+                            is_recording = true;
+                        }
+                    }
+                    ControlRequest::StopRecognition => {
+                        info!("{}", gettext("Recording stopped"));
+                        microphone_tx
+                            .try_send(MicrophoneMessage::MicrophoneRecordStop)
+                            .unwrap();
+                        is_recording = false;
+                    }
+                    ControlRequest::RecognizeFile(file_path) => {
+                        processing_tx
+                            .try_send(ProcessingMessage::ProcessAudioFile(file_path))
+                            .unwrap();
+                    }
+                    ControlRequest::SelectDevice(device_name) => {
+                        info!("{}", gettext("Using device %s").replace("%s", &device_name));
+                        if is_recording {
+                            microphone_tx
+                                .try_send(MicrophoneMessage::MicrophoneRecordStop)
+                                .unwrap();
+                            microphone_tx
+                                .try_send(MicrophoneMessage::MicrophoneRecordStart(
+                                    device_name.clone(),
+                                ))
+                                .unwrap();
+                        }
+                        current_device_name = Some(device_name);
+                    }
+                },
                 _ => {}
             }
         }
@@ -238,3 +548,791 @@ pub fn cli_main(parameters: CLIParameters) -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[cfg(unix)]
+pub struct CLIDaemonParameters {
+    pub socket_path: Option<String>,
+    pub audio_device: Option<String>,
+    /// Wait for a "start" request before recording
+    pub start_idle: bool,
+    pub request_interval: u64,
+    pub recognition_backend: Option<String>,
+    pub recognition_backend_url: Option<String>,
+    pub local_database_path: Option<String>,
+    pub record_history: bool,
+    /// Serve the now playing page on this address
+    pub http_server_address: Option<String>,
+    /// Override the capture window settings, see capture_window.rs
+    pub capture_window_secs: Option<u64>,
+    pub voting_windows: Option<u64>,
+    /// Keep the recorded audio of the recognition requests, see capture_archive.rs
+    pub capture_archive: bool,
+    /// Skip the recognition requests for silence and speech, see audio_activity.rs
+    pub audio_gating: bool,
+}
+
+#[cfg(unix)]
+#[derive(Default)]
+struct DaemonState {
+    devices: Vec<DeviceListItem>,
+    current_device_name: Option<String>,
+    is_recording: bool,
+    schedule: Option<ScheduleStatus>,
+    subscribers: Vec<async_channel::Sender<serde_json::Value>>,
+}
+
+#[cfg(unix)]
+impl DaemonState {
+    /// The internal name of an audio device, given its internal or display
+    /// name.
+    fn find_device(&self, device_name: &str) -> Result<String, String> {
+        self.devices
+            .iter()
+            .find(|device| device.inner_name == device_name || device.display_name == device_name)
+            .map(|device| device.inner_name.clone())
+            .ok_or_else(|| gettext("Audio device not found: %s").replace("%s", device_name))
+    }
+
+    fn start_recording(
+        &mut self,
+        microphone_tx: &async_channel::Sender<MicrophoneMessage>,
+    ) -> Result<(), String> {
+        let device_name = match self.current_device_name {
+            Some(ref device_name) => self.find_device(device_name)?,
+            None => match self.devices.first() {
+                Some(device) => device.inner_name.clone(),
+                None => return Err(gettext("No audio devices found!")),
+            },
+        };
+        info!("{}", gettext("Using device %s").replace("%s", &device_name));
+
+        microphone_tx
+            .try_send(MicrophoneMessage::MicrophoneRecordStop)
+            .unwrap();
+        microphone_tx
+            .try_send(MicrophoneMessage::MicrophoneRecordStart(
+                device_name.clone(),
+            ))
+            .unwrap();
+        self.current_device_name = Some(device_name);
+        self.is_recording = true;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub fn daemon_main(parameters: CLIDaemonParameters) -> Result<(), Box<dyn Error>> {
+    let (gui_tx, gui_rx) = async_channel::unbounded();
+    let (daemon_tx, daemon_rx) = async_channel::unbounded();
+    let (start_idle, record_history) = (parameters.start_idle, parameters.record_history);
+
+    let mut preferences = Preferences::with_interval(parameters.request_interval);
+    preferences.recognition_backend = parameters.recognition_backend;
+    preferences.recognition_backend_url = parameters.recognition_backend_url;
+    preferences.local_database_path = parameters.local_database_path;
+    preferences.capture_window_secs = parameters.capture_window_secs;
+    preferences.voting_windows = parameters.voting_windows;
+    preferences.capture_archive = Some(parameters.capture_archive);
+    preferences.skip_silence_and_speech = Some(parameters.audio_gating);
+    if parameters.capture_archive {
+        let saved_preferences = PreferencesInterface::new().preferences;
+        preferences.capture_archive_max_files = saved_preferences.capture_archive_max_files;
+        preferences.capture_archive_max_megabytes = saved_preferences.capture_archive_max_megabytes;
+    }
+
+    let preferences_interface = Arc::new(Mutex::new(PreferencesInterface {
+        preferences_file_path: None,
+        preferences,
+    }));
+    let preferences_interface_2 = preferences_interface.clone();
+
+    let socket_path = match parameters.socket_path {
+        Some(socket_path) => PathBuf::from(socket_path),
+        None => obtain_daemon_socket_path()?,
+    };
+    let capture_settings =
+        CaptureSettings::from_preferences(&preferences_interface.lock().unwrap().preferences);
+    serve(&socket_path, daemon_tx, capture_settings)?;
+
+    let now_playing_server = match parameters.http_server_address {
+        Some(ref address) => Some(NowPlayingServer::start(address)?),
+        None => None,
+    };
+
+    let gui_tx_2 = gui_tx.clone();
+    let (microphone_tx, _processing_tx) = start_recognition_threads(gui_tx, preferences_interface);
+    let microphone_tx_2 = microphone_tx.clone();
+
+    let state = Rc::new(RefCell::new(DaemonState {
+        current_device_name: parameters.audio_device,
+        ..Default::default()
+    }));
+    let state_2 = state.clone();
+
+    let main_loop = glib::MainLoop::new(None, false);
+    let loop_inner = main_loop.clone();
+
+    // Answer the requests of the clients
+
+    glib::spawn_future_local(async move {
+        while let Ok(DaemonCall { request, reply_tx }) = daemon_rx.recv().await {
+            let mut state = state_2.borrow_mut();
+            let reply = match request {
+                DaemonRequest::Start(device_name) => {
+                    let selected = match device_name {
+                        Some(device_name) => state
+                            .find_device(&device_name)
+                            .map(|device_name| state.current_device_name = Some(device_name)),
+                        None => Ok(()),
+                    };
+                    selected
+                        .and_then(|_| state.start_recording(&microphone_tx_2))
+                        .map(|_| json!(true))
+                }
+                DaemonRequest::Stop => {
+                    info!("{}", gettext("Recording stopped"));
+                    microphone_tx_2
+                        .try_send(MicrophoneMessage::MicrophoneRecordStop)
+                        .unwrap();
+                    state.is_recording = false;
+                    Ok(json!(true))
+                }
+                DaemonRequest::SetDevice(device_name) => {
+                    state.find_device(&device_name).and_then(|device_name| {
+                        state.current_device_name = Some(device_name);
+                        if state.is_recording {
+                            state.start_recording(&microphone_tx_2)?;
+                        }
+                        Ok(json!(true))
+                    })
+                }
+                DaemonRequest::ListDevices => {
+                    Ok(state.devices.iter().map(device_to_json).collect())
+                }
+                DaemonRequest::Status => Ok(json!({
+                    "recording": state.is_recording,
+                    "device": state.current_device_name,
+                    "schedule": state.schedule.filter(|_| state.is_recording).map(|schedule| json!({
+                        "state": schedule.state.key(),
+                        "next_request_secs": schedule.next_request_secs,
+                    })),
+                })),
+                DaemonRequest::RecognizeSignatures(signatures) => {
+                    let backend = get_backend(&preferences_interface_2.lock().unwrap().preferences);
+                    let gui_tx = gui_tx_2.clone();
+                    glib::spawn_future_local(async move {
+                        let result = match recognize_by_vote(backend.as_ref(), &signatures).await {
+                            Ok(message) => {
+                                // Follow the same path as the songs recognized
+                                // from the audio device: history, subscribers
+                                // and now playing page
+                                let song = message.to_json();
+                                gui_tx
+                                    .send(GUIMessage::SongRecognized(Box::new(message)))
+                                    .await
+                                    .ok();
+                                Ok(song)
+                            }
+                            Err(error) => Err(error.to_string()),
+                        };
+                        reply_tx.send(result).await.ok();
+                    });
+                    continue;
+                }
+                DaemonRequest::Subscribe(event_tx) => {
+                    state.subscribers.push(event_tx);
+                    Ok(json!(true))
+                }
+            };
+            drop(state);
+            reply_tx.send(reply).await.ok();
+        }
+    });
+
+    // Follow the recording
+
+    glib::spawn_future_local(async move {
+        let mut history_database = match record_history {
+            true => match obtain_history_database_path()
+                .and_then(|database_path| HistoryDatabase::open(&database_path))
+            {
+                Ok(history_database) => Some(history_database),
+                Err(error_info) => {
+                    error!(
+                        "{} {}",
+                        gettext("Error when reading the song history on the disk:"),
+                        error_info
+                    );
+                    None
+                }
+            },
+            false => None,
+        };
+        let mut last_track: Option<String> = None;
+        let mut pending_start = !start_idle;
+
+        while let Ok(gui_message) = gui_rx.recv().await {
+            match gui_message {
+                GUIMessage::DevicesList(devices) => {
+                    for device in devices.iter() {
+                        info!(
+                            "{} {} ({})",
+                            gettext("Available device:"),
+                            device.inner_name,
+                            device.display_name
+                        );
+                    }
+                    let mut state = state.borrow_mut();
+                    state.devices = devices;
+
+                    // The device list is sent again after each refresh,
+                    // only start recording upon the first one
+                    if pending_start {
+                        pending_start = false;
+                        if let Err(error) = state.start_recording(&microphone_tx) {
+                            error!("{} {}", gettext("Error:"), error);
+                        }
+                    }
+                }
+                GUIMessage::NetworkStatus(false) => {
+                    warn!("{}", gettext("Warning: Network unreachable"));
+                }
+                GUIMessage::ErrorMessage(string) => {
+                    if string != gettext("No match for this song") {
+                        error!("{} {}", gettext("Error:"), string);
+                    }
+                }
+                GUIMessage::MicrophoneRecording => {
+                    info!("{}", gettext("Recording started!"));
+                }
+                GUIMessage::RequestSchedule(status) => {
+                    let mut state = state.borrow_mut();
+                    if state.schedule.map(|schedule| schedule.state) != Some(status.state) {
+                        info!("{} {}", gettext("Request schedule:"), status.describe());
+                    }
+                    state.schedule = Some(status);
+                }
+                GUIMessage::SongRecognized(message) => {
+                    let track_key = Some(message.track_key.clone());
+                    if last_track == track_key {
+                        continue;
+                    }
+                    last_track = track_key;
+
+                    let song_name = format!("{} - {}", message.artist_name, message.song_name);
+                    info!("{} {}", gettext("Song recognized:"), song_name);
+
+                    if let Some(ref now_playing_server) = now_playing_server {
+                        now_playing_server.song_recognized(&message);
+                    }
+
+                    let event = notification("song_recognized", message.to_json());
+                    let mut state = state.borrow_mut();
+                    state
+                        .subscribers
+                        .retain(|event_tx| event_tx.try_send(event.clone()).is_ok());
+
+                    if let Some(ref mut history_database) = history_database {
+                        let record = SongHistoryRecord {
+                            song_name,
+                            album: Some(message.album_name.clone().unwrap_or_default()),
+                            track_key: Some(message.track_key.clone()),
+                            release_year: Some(message.release_year.clone().unwrap_or_default()),
+                            genre: Some(message.genre.clone().unwrap_or_default()),
+                            recognition_date: Local::now().format("%c").to_string(),
+                            isrc: message.isrc.clone(),
+                            label: message.label.clone(),
+                        };
+                        add_to_history(
+                            history_database,
+                            &record,
+                            &message,
+                            state.current_device_name.clone(),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        loop_inner.quit();
+    });
+
+    main_loop.run();
+
+    Ok(())
+}
+
+pub struct CLIScanParameters {
+    pub input_file: String,
+    pub scan_parameters: ScanParameters,
+    pub output_type: CLIOutputType,
+    /// Takes precedence over the output type when set
+    pub tracklist_format: Option<TracklistFormat>,
+    pub recognition_backend: Option<String>,
+    pub recognition_backend_url: Option<String>,
+    pub local_database_path: Option<String>,
+}
+
+pub fn scan_main(parameters: CLIScanParameters) -> Result<(), Box<dyn Error>> {
+    let mut preferences = Preferences::new();
+    preferences.recognition_backend = parameters.recognition_backend;
+    preferences.recognition_backend_url = parameters.recognition_backend_url;
+    preferences.local_database_path = parameters.local_database_path;
+
+    let backend = get_backend(&preferences);
+
+    glib::MainContext::default().block_on(async {
+        let segments = scan_file(
+            backend.as_ref(),
+            &parameters.input_file,
+            &parameters.scan_parameters,
+        )
+        .await?;
+
+        if let Some(tracklist_format) = parameters.tracklist_format {
+            match tracklist_format {
+                TracklistFormat::CueSheet => {
+                    print!("{}", write_cue_sheet(&segments, &parameters.input_file));
+                }
+                TracklistFormat::FFMetadata => {
+                    print!("{}", write_ffmetadata(&segments));
+                }
+                TracklistFormat::WebVTT => {
+                    print!("{}", write_webvtt(&segments));
+                }
+                TracklistFormat::AudacityLabels => {
+                    print!("{}", write_audacity_labels(&segments));
+                }
+            }
+            return Ok(());
+        }
+
+        match parameters.output_type {
+            CLIOutputType::JSON => {
+                println!("{}", serde_json::to_string_pretty(&segments)?);
+            }
+            CLIOutputType::CSV => {
+                let mut csv_writer = csv::Writer::from_writer(std::io::stdout());
+                for segment in segments {
+                    csv_writer.serialize(segment)?;
+                }
+                csv_writer.flush()?;
+            }
+            CLIOutputType::SongName => {
+                for segment in segments {
+                    println!(
+                        "{} - {}  {} - {}",
+                        format_timestamp(segment.start_secs),
+                        format_timestamp(segment.end_secs),
+                        segment.artist_name,
+                        segment.song_name
+                    );
+                }
+            }
+        }
+
+        Ok::<(), Box<dyn Error>>(())
+    })
+}
+
+pub struct CLITagParameters {
+    pub input_files: Vec<String>,
+    pub tagging_options: TaggingOptions,
+    pub recognition_backend: Option<String>,
+    pub recognition_backend_url: Option<String>,
+    pub local_database_path: Option<String>,
+}
+
+pub fn tag_main(parameters: CLITagParameters) -> Result<(), Box<dyn Error>> {
+    let mut preferences = Preferences::new();
+    preferences.recognition_backend = parameters.recognition_backend;
+    preferences.recognition_backend_url = parameters.recognition_backend_url;
+    preferences.local_database_path = parameters.local_database_path;
+
+    let backend = get_backend(&preferences);
+
+    glib::MainContext::default().block_on(async {
+        for input_file in &parameters.input_files {
+            let signature = match SignatureGenerator::make_signature_from_file(input_file) {
+                Ok(signature) => signature,
+                Err(error) => {
+                    error!("{} {}: {}", gettext("Could not decode:"), input_file, error);
+                    continue;
+                }
+            };
+
+            let message = match recognize_with_retries(backend.as_ref(), &signature).await {
+                Ok(message) => message,
+                Err(error) => {
+                    error!("{}: {}", input_file, error);
+                    continue;
+                }
+            };
+
+            println!(
+                "{}: {} - {}",
+                input_file, message.artist_name, message.song_name
+            );
+
+            match tag_file(input_file, &message, &parameters.tagging_options) {
+                Ok(changes) if changes.is_empty() => {
+                    println!("  {}", gettext("Nothing to change"));
+                }
+                Ok(changes) => {
+                    for change in changes {
+                        println!(
+                            "  {}: {} -> {:?}",
+                            change.field,
+                            change
+                                .old_value
+                                .map(|value| format!("{:?}", value))
+                                .unwrap_or_else(|| gettext("(empty)")),
+                            change.new_value
+                        );
+                    }
+                }
+                Err(error) => {
+                    error!(
+                        "{} {}: {}",
+                        gettext("Could not write the tags of:"),
+                        input_file,
+                        error
+                    );
+                }
+            }
+        }
+    });
+
+    if parameters.tagging_options.dry_run {
+        println!("{}", gettext("Dry run: no file was modified."));
+    }
+
+    Ok(())
+}
+
+pub struct CLIBatchParameters {
+    pub batch_parameters: BatchParameters,
+    pub output_type: CLIOutputType,
+    pub recognition_backend: Option<String>,
+    pub recognition_backend_url: Option<String>,
+    pub local_database_path: Option<String>,
+}
+
+pub fn batch_main(parameters: CLIBatchParameters) -> Result<(), Box<dyn Error>> {
+    let mut preferences = Preferences::new();
+    preferences.recognition_backend = parameters.recognition_backend;
+    preferences.recognition_backend_url = parameters.recognition_backend_url;
+    preferences.local_database_path = parameters.local_database_path;
+
+    let backend = get_backend(&preferences);
+
+    // When resuming, the CSV header was already printed by the previous run
+    let is_resuming = parameters
+        .batch_parameters
+        .state_file
+        .as_ref()
+        .is_some_and(|path| Path::new(path).exists());
+
+    let mut csv_writer = csv::WriterBuilder::new()
+        .has_headers(!is_resuming)
+        .from_writer(std::io::stdout());
+
+    glib::MainContext::default().block_on(recognize_batch(
+        backend.as_ref(),
+        &parameters.batch_parameters,
+        |record| {
+            match parameters.output_type {
+                CLIOutputType::CSV => {
+                    csv_writer.serialize(record)?;
+                    csv_writer.flush()?;
+                }
+                // JSON Lines
+                CLIOutputType::JSON | CLIOutputType::SongName => {
+                    println!("{}", serde_json::to_string(&record)?);
+                }
+            }
+            Ok(())
+        },
+    ))
+}
+
+pub struct CLIOrganizeParameters {
+    pub batch_parameters: BatchParameters,
+    pub template: String,
+    pub destination: String,
+    pub dry_run: bool,
+    pub journal_path: Option<String>,
+    pub undo_journal_path: Option<String>,
+    pub recognition_backend: Option<String>,
+    pub recognition_backend_url: Option<String>,
+    pub local_database_path: Option<String>,
+}
+
+pub fn organize_main(parameters: CLIOrganizeParameters) -> Result<(), Box<dyn Error>> {
+    if let Some(ref undo_journal_path) = parameters.undo_journal_path {
+        let report = undo_journal(Path::new(undo_journal_path), parameters.dry_run)?;
+        for entry in report.undone {
+            println!("{} -> {}", entry.to, entry.from);
+        }
+        for (entry, reason) in report.skipped {
+            warn!(
+                "{} {} -> {}: {}",
+                gettext("Not undone:"),
+                entry.to,
+                entry.from,
+                reason
+            );
+        }
+        if parameters.dry_run {
+            println!("{}", gettext("Dry run: no file was moved."));
+        }
+        return Ok(());
+    }
+
+    let mut preferences = Preferences::new();
+    preferences.recognition_backend = parameters.recognition_backend;
+    preferences.recognition_backend_url = parameters.recognition_backend_url;
+    preferences.local_database_path = parameters.local_database_path;
+
+    let backend = get_backend(&preferences);
+
+    let journal_path = match parameters.journal_path {
+        Some(ref path) => PathBuf::from(path),
+        None => obtain_organize_journal_path()?,
+    };
+    let mut planned_targets: HashSet<PathBuf> = HashSet::new();
+    let mut number_moved = 0;
+    let mut failed_moves: Vec<String> = vec![];
+
+    glib::MainContext::default().block_on(recognize_batch(
+        backend.as_ref(),
+        &parameters.batch_parameters,
+        |record| {
+            if record.status != BatchStatus::Recognized {
+                warn!(
+                    "{} {}",
+                    gettext("Not recognized, left in place:"),
+                    record.file
+                );
+                return Ok(());
+            }
+
+            let target = Path::new(&parameters.destination)
+                .join(render_template(&parameters.template, &record));
+
+            if target == Path::new(&record.file) {
+                return Ok(());
+            }
+
+            let target = resolve_collision(target, &planned_targets);
+            planned_targets.insert(target.clone());
+
+            println!("{} -> {}", record.file, target.display());
+
+            if !parameters.dry_run {
+                // Keep going with the other files, this one is left in place
+                if let Err(error) = move_file(Path::new(&record.file), &target) {
+                    error!("{} {}: {}", gettext("Could not move:"), record.file, error);
+                    failed_moves.push(record.file);
+                    return Ok(());
+                }
+                append_to_journal(
+                    &journal_path,
+                    &JournalEntry {
+                        from: record.file,
+                        to: target.to_string_lossy().into_owned(),
+                    },
+                )?;
+                number_moved += 1;
+            }
+            Ok(())
+        },
+    ))?;
+
+    if !failed_moves.is_empty() {
+        warn!(
+            "{} {}",
+            gettext("Number of files which could not be moved:"),
+            failed_moves.len()
+        );
+        for file in &failed_moves {
+            warn!("  {}", file);
+        }
+    }
+
+    if parameters.dry_run {
+        println!("{}", gettext("Dry run: no file was moved."));
+    } else if number_moved > 0 {
+        println!(
+            "{} songrec organize --undo {:?}",
+            gettext("To undo these moves, run:"),
+            journal_path
+        );
+    }
+
+    Ok(())
+}
+
+pub struct CLIHistoryParameters {
+    pub query: HistoryQuery,
+    pub output_type: CLIOutputType,
+    /// Takes precedence over the output type when set
+    pub playlist_format: Option<PlaylistFormat>,
+}
+
+pub fn history_main(parameters: CLIHistoryParameters) -> Result<(), Box<dyn Error>> {
+    let database = HistoryDatabase::open(&obtain_history_database_path()?)?;
+
+    let rows = database.query(&parameters.query)?;
+
+    if let Some(playlist_format) = parameters.playlist_format {
+        let title = match parameters.query.list {
+            SongList::History => gettext("SongRec history"),
+            SongList::Favorites => gettext("SongRec favorites"),
+        };
+        print!("{}", write_playlist(playlist_format, &rows, &title)?);
+        return Ok(());
+    }
+
+    let mut csv_writer = csv::Writer::from_writer(std::io::stdout());
+
+    for row in rows {
+        match parameters.output_type {
+            // JSON Lines
+            CLIOutputType::JSON => {
+                println!("{}", serde_json::to_string(&row)?);
+            }
+            CLIOutputType::CSV => {
+                csv_writer.serialize(row.to_song_history_record())?;
+            }
+            CLIOutputType::SongName => {
+                println!("{}", row.song_name);
+            }
+        }
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+/// Add or remove a song, designated by its track key or its name, from the
+/// favorites.
+pub fn favorites_main(song: &str, add: bool) -> Result<(), Box<dyn Error>> {
+    let mut database = HistoryDatabase::open(&obtain_history_database_path()?)?;
+
+    if add {
+        match database.add_favorite(song)? {
+            Some(song_name) => println!("{} {}", gettext("Added to the favorites:"), song_name),
+            None => return Err(gettext("No such song in the history").into()),
+        }
+    } else {
+        match database.remove_favorite(song)? {
+            0 => return Err(gettext("No such song in the favorites").into()),
+            _ => println!("{} {}", gettext("Removed from the favorites:"), song),
+        }
+    }
+
+    Ok(())
+}
+
+/// List the archived captures, from the most recent, with the songs
+/// recognized from them.
+pub fn captures_main(enable_json: bool) -> Result<(), Box<dyn Error>> {
+    let database = HistoryDatabase::open(&obtain_history_database_path()?)?;
+
+    for capture in list_captures()?.iter().rev() {
+        let capture_path = capture.path.to_string_lossy();
+        let song_name = database.song_for_capture(&capture_path)?;
+
+        if enable_json {
+            println!(
+                "{}",
+                json!({
+                    "capture_path": capture_path,
+                    "size": capture.size,
+                    "song_name": song_name,
+                })
+            );
+        } else {
+            println!(
+                "{}\t{}",
+                capture_path,
+                song_name.unwrap_or_else(|| "-".to_string())
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub struct CLIStatisticsParameters {
+    pub query: HistoryQuery,
+    pub top_count: usize,
+    pub output_type: CLIOutputType,
+}
+
+pub fn statistics_main(parameters: CLIStatisticsParameters) -> Result<(), Box<dyn Error>> {
+    let database = HistoryDatabase::open(&obtain_history_database_path()?)?;
+
+    let now = Local::now();
+    let statistics = compute_statistics(
+        &database.query(&parameters.query)?,
+        now,
+        parameters.top_count,
+    );
+
+    match parameters.output_type {
+        CLIOutputType::JSON => {
+            println!("{}", serde_json::to_string_pretty(&statistics)?);
+        }
+        CLIOutputType::SongName | CLIOutputType::CSV => {
+            print!("{}", format_report(&statistics, now));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn import_main(
+    input_files: Vec<String>,
+    list: SongList,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut database = HistoryDatabase::open(&obtain_history_database_path()?)?;
+
+    for input_file in input_files {
+        let report = import_history_file(&mut database, list, Path::new(&input_file), dry_run)?;
+
+        for entry in &report.added {
+            println!("+ {}", entry);
+        }
+        for entry in &report.skipped_duplicates {
+            println!("= {} {}", entry, gettext("(already present, skipped)"));
+        }
+        for entry in &report.skipped_invalid_dates {
+            println!("! {} {}", entry, gettext("(invalid date, skipped)"));
+        }
+        for error in &report.errors {
+            error!("{}: {}", input_file, error);
+        }
+        println!(
+            "{}: {} {}, {} {}, {} {}, {} {}",
+            input_file,
+            report.added.len(),
+            gettext("added"),
+            report.skipped_duplicates.len(),
+            gettext("skipped"),
+            report.skipped_invalid_dates.len(),
+            gettext("with an invalid date"),
+            report.errors.len(),
+            gettext("errors")
+        );
+    }
+
+    if dry_run {
+        println!("{}", gettext("Dry run: the history was not modified."));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,306 @@
+//! Tells apart silence, speech and music in the audio recorded from the
+//! microphone, so that recognition requests are only made for music, and
+//! notices when the audio changes markedly.
+//!
+//! The audio is cut into frames of 64 ms, of which are measured:
+//! - the loudness (RMS level), below which the audio is silence
+//! - the spectral flatness, close to 1 for noise such as the hiss of a quiet
+//!   room, and close to 0 for tonal sounds
+//! - the share of frames much quieter than the average, which is high for
+//!   speech, paused between syllables and words, and low for music
+
+use gettextrs::gettext;
+use realfft::{RealFftPlanner, RealToComplex};
+use rustfft::num_complex::Complex;
+use std::sync::Arc;
+
+use crate::core::preferences::Preferences;
+
+/// The length of the most recent audio to classify, in seconds.
+pub const ANALYSIS_SECS: usize = 4;
+
+const FRAME_SIZE: usize = 1024; // 64 ms at 16 KHz
+
+// Frames quieter than half of the average energy count as pauses
+const PAUSE_ENERGY_RATIO: f32 = 0.5;
+
+pub const DEFAULT_SILENCE_THRESHOLD: f32 = 0.003; // About -50 dBFS
+pub const DEFAULT_NOISE_FLATNESS_THRESHOLD: f32 = 0.4;
+pub const DEFAULT_SPEECH_PAUSE_THRESHOLD: f32 = 0.3;
+
+// The average difference of level over the frequency bands from which the
+// audio content is considered to change
+const NUMBER_BANDS: usize = 16;
+const CONTENT_CHANGE_DB: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioActivity {
+    Silence,
+    Speech,
+    Music,
+}
+
+impl AudioActivity {
+    pub fn label(&self) -> String {
+        match self {
+            AudioActivity::Silence => gettext("Silence"),
+            AudioActivity::Speech => gettext("Speech"),
+            AudioActivity::Music => gettext("Music"),
+        }
+    }
+}
+
+pub struct ActivityThresholds {
+    /// The RMS level below which the audio is silence
+    pub silence: f32,
+    /// The spectral flatness above which the audio is noise
+    pub noise_flatness: f32,
+    /// The share of pauses above which the audio is speech
+    pub speech_pause: f32,
+}
+
+impl ActivityThresholds {
+    pub fn from_preferences(preferences: &Preferences) -> Self {
+        ActivityThresholds {
+            silence: preferences
+                .silence_threshold
+                .unwrap_or(DEFAULT_SILENCE_THRESHOLD),
+            noise_flatness: preferences
+                .noise_flatness_threshold
+                .unwrap_or(DEFAULT_NOISE_FLATNESS_THRESHOLD),
+            speech_pause: preferences
+                .speech_pause_threshold
+                .unwrap_or(DEFAULT_SPEECH_PAUSE_THRESHOLD),
+        }
+    }
+}
+
+/// Computes the power spectra of frames of audio, with a Hann window. The
+/// microphone thread keeps one per stream, as planning the FFT allocates.
+pub struct SpectrumAnalyzer {
+    fft: Arc<dyn RealToComplex<f32>>,
+    hann_window: Vec<f32>,
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex<f32>>,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new() -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FRAME_SIZE);
+        SpectrumAnalyzer {
+            hann_window: (0..FRAME_SIZE)
+                .map(|index| {
+                    0.5 - 0.5
+                        * (2.0 * std::f32::consts::PI * index as f32 / (FRAME_SIZE - 1) as f32)
+                            .cos()
+                })
+                .collect(),
+            fft_input: fft.make_input_vec(),
+            fft_output: fft.make_output_vec(),
+            fft,
+        }
+    }
+
+    /// The power of each frequency bin, without the DC component.
+    fn power_spectrum(&mut self, frame: &[f32]) -> Vec<f32> {
+        for ((input, sample), multiplier) in self
+            .fft_input
+            .iter_mut()
+            .zip(frame.iter())
+            .zip(self.hann_window.iter())
+        {
+            *input = sample * multiplier;
+        }
+        self.fft
+            .process(&mut self.fft_input, &mut self.fft_output)
+            .unwrap();
+
+        self.fft_output[1..]
+            .iter()
+            .map(|bin| bin.norm_sqr() + 1e-12)
+            .collect()
+    }
+}
+
+impl Default for SpectrumAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The ratio of the geometric mean to the arithmetic mean of a power
+/// spectrum.
+fn spectral_flatness(powers: &[f32]) -> f32 {
+    let log_mean = powers.iter().map(|power| power.ln()).sum::<f32>() / powers.len() as f32;
+    let mean = powers.iter().sum::<f32>() / powers.len() as f32;
+
+    log_mean.exp() / mean
+}
+
+/// Classify 16 KHz mono audio, as stored by the microphone thread.
+pub fn classify_audio(
+    analyzer: &mut SpectrumAnalyzer,
+    samples: &[f32],
+    thresholds: &ActivityThresholds,
+) -> AudioActivity {
+    let frames: Vec<&[f32]> = samples.chunks_exact(FRAME_SIZE).collect();
+    if frames.is_empty() {
+        return AudioActivity::Silence;
+    }
+
+    let energies: Vec<f32> = frames
+        .iter()
+        .map(|frame| frame.iter().map(|sample| sample * sample).sum::<f32>() / FRAME_SIZE as f32)
+        .collect();
+    let mean_energy = energies.iter().sum::<f32>() / energies.len() as f32;
+
+    if mean_energy.sqrt() < thresholds.silence {
+        return AudioActivity::Silence;
+    }
+
+    // Steady noise is spectrally flat over most of the frames which are
+    // not pauses
+
+    let mut flatnesses: Vec<f32> = frames
+        .iter()
+        .zip(energies.iter())
+        .filter(|(_, energy)| **energy >= mean_energy * PAUSE_ENERGY_RATIO)
+        .map(|(frame, _)| spectral_flatness(&analyzer.power_spectrum(frame)))
+        .collect();
+    flatnesses.sort_by(f32::total_cmp);
+
+    if flatnesses[flatnesses.len() / 2] > thresholds.noise_flatness {
+        return AudioActivity::Silence;
+    }
+
+    // Speech is made of short bursts, music is more continuous
+
+    let number_pauses = energies
+        .iter()
+        .filter(|energy| **energy < mean_energy * PAUSE_ENERGY_RATIO)
+        .count();
+
+    if number_pauses as f32 / energies.len() as f32 > thresholds.speech_pause {
+        AudioActivity::Speech
+    } else {
+        AudioActivity::Music
+    }
+}
+
+/// The average level of audio in frequency bands of growing widths, in
+/// decibels.
+fn band_levels(analyzer: &mut SpectrumAnalyzer, samples: &[f32]) -> [f32; NUMBER_BANDS] {
+    let mut band_powers = [0.0f32; NUMBER_BANDS];
+    let mut number_frames = 0;
+
+    for frame in samples.chunks_exact(FRAME_SIZE) {
+        let powers = analyzer.power_spectrum(frame);
+
+        let mut band_start = 0;
+        for (band_index, band_power) in band_powers.iter_mut().enumerate() {
+            let band_end = ((powers.len() as f32)
+                .powf((band_index + 1) as f32 / NUMBER_BANDS as f32)
+                as usize)
+                .max(band_start + 1)
+                .min(powers.len());
+            *band_power +=
+                powers[band_start..band_end].iter().sum::<f32>() / (band_end - band_start) as f32;
+            band_start = band_end;
+        }
+        number_frames += 1;
+    }
+
+    band_powers.map(|band_power| 10.0 * (band_power / number_frames.max(1) as f32 + 1e-10).log10())
+}
+
+/// Whether the latest audio sounds markedly different from the audio
+/// before it, such as when another song starts.
+pub fn audio_content_changed(
+    analyzer: &mut SpectrumAnalyzer,
+    previous_samples: &[f32],
+    latest_samples: &[f32],
+) -> bool {
+    let previous_levels = band_levels(analyzer, previous_samples);
+    let latest_levels = band_levels(analyzer, latest_samples);
+
+    let mean_difference = previous_levels
+        .iter()
+        .zip(latest_levels.iter())
+        .map(|(previous_level, latest_level)| (previous_level - latest_level).abs())
+        .sum::<f32>()
+        / NUMBER_BANDS as f32;
+
+    mean_difference > CONTENT_CHANGE_DB
+}
+
+#[test]
+fn test_classify_audio() {
+    let thresholds = ActivityThresholds {
+        silence: DEFAULT_SILENCE_THRESHOLD,
+        noise_flatness: DEFAULT_NOISE_FLATNESS_THRESHOLD,
+        speech_pause: DEFAULT_SPEECH_PAUSE_THRESHOLD,
+    };
+    let mut analyzer = SpectrumAnalyzer::new();
+    let chord = |index: usize| {
+        [220.0, 277.2, 329.6]
+            .iter()
+            .map(|frequency| {
+                0.1 * (2.0 * std::f32::consts::PI * frequency * index as f32 / 16000.0).sin()
+            })
+            .sum::<f32>()
+    };
+
+    let silence = vec![0.0f32; 16000 * ANALYSIS_SECS];
+    assert_eq!(
+        classify_audio(&mut analyzer, &silence, &thresholds),
+        AudioActivity::Silence
+    );
+
+    // Hiss, from a linear congruential generator
+    let mut seed: u32 = 1;
+    let hiss: Vec<f32> = (0..16000 * ANALYSIS_SECS)
+        .map(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1 << 24) as f32 * 0.2 - 0.1
+        })
+        .collect();
+    assert_eq!(
+        classify_audio(&mut analyzer, &hiss, &thresholds),
+        AudioActivity::Silence
+    );
+
+    let music: Vec<f32> = (0..16000 * ANALYSIS_SECS).map(chord).collect();
+    assert_eq!(
+        classify_audio(&mut analyzer, &music, &thresholds),
+        AudioActivity::Music
+    );
+
+    // Bursts of 200 ms separated by 300 ms pauses
+    let speech: Vec<f32> = (0..16000 * ANALYSIS_SECS)
+        .map(|index| match index % 8000 < 3200 {
+            true => chord(index),
+            false => 0.0,
+        })
+        .collect();
+    assert_eq!(
+        classify_audio(&mut analyzer, &speech, &thresholds),
+        AudioActivity::Speech
+    );
+
+    let higher_music: Vec<f32> = (0..16000).map(|index| chord(index * 3)).collect();
+    assert!(!audio_content_changed(
+        &mut analyzer,
+        &music[16000..],
+        &music[..16000]
+    ));
+    assert!(audio_content_changed(
+        &mut analyzer,
+        &music[16000..],
+        &higher_music
+    ));
+    assert!(audio_content_changed(
+        &mut analyzer,
+        &silence[16000..],
+        &music[..16000]
+    ));
+}
@@ -0,0 +1,330 @@
+//! Recognition of whole directories of audio files. Files are decoded and
+//! fingerprinted in parallel by a pool of worker threads, while recognition
+//! requests are sent one at a time from the main loop, so that they can be
+//! rate-limited.
+
+use gettextrs::gettext;
+use log::{error, info};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::recognition_backends::backend::{
+    RecognitionBackend, RecognitionError, recognize_with_retries,
+};
+use crate::utils::filesystem_operations::is_audio_file;
+
+pub struct BatchParameters {
+    pub paths: Vec<String>,
+    pub recursive: bool,
+    /// Wildcard patterns ("*" and "?") matched against file names. When
+    /// empty, all audio files are included
+    pub include_patterns: Vec<String>,
+    pub exclude_patterns: Vec<String>,
+    pub number_workers: usize,
+    /// Pause between two recognition requests, to avoid being rate-limited
+    pub request_interval_secs: u64,
+    /// File listing the already processed files, one per line, so that an
+    /// interrupted batch can be resumed
+    pub state_file: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Recognized,
+    #[default]
+    NoMatch,
+    Error,
+}
+
+/// The result of the recognition of one file (flat, so that it can be
+/// serialized to CSV)
+#[derive(Serialize, Default)]
+pub struct BatchRecord {
+    pub file: String,
+    pub status: BatchStatus,
+    pub artist_name: Option<String>,
+    pub song_name: Option<String>,
+    pub album_name: Option<String>,
+    pub release_year: Option<String>,
+    pub genre: Option<String>,
+    pub isrc: Option<String>,
+    pub track_key: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Match a file name against a pattern where "*" stands for any sequence of
+/// characters and "?" for any single character.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    // Classic backtracking over the last "*" encountered
+    let (mut pattern_index, mut name_index) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+
+    while name_index < name.len() {
+        if pattern_index < pattern.len()
+            && (pattern[pattern_index] == '?' || pattern[pattern_index] == name[name_index])
+        {
+            pattern_index += 1;
+            name_index += 1;
+        } else if pattern_index < pattern.len() && pattern[pattern_index] == '*' {
+            last_star = Some((pattern_index, name_index));
+            pattern_index += 1;
+        } else if let Some((star_pattern_index, star_name_index)) = last_star {
+            pattern_index = star_pattern_index + 1;
+            name_index = star_name_index + 1;
+            last_star = Some((star_pattern_index, star_name_index + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[pattern_index..]
+        .iter()
+        .all(|character| *character == '*')
+}
+
+fn collect_files(
+    path: &Path,
+    parameters: &BatchParameters,
+    is_top_level: bool,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    if path.is_dir() {
+        // Don't follow symbolic links to directories below the top level,
+        // which may loop
+        let is_symlink = path
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+
+        if is_top_level || (parameters.recursive && !is_symlink) {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            entries.sort();
+
+            for entry in entries {
+                collect_files(&entry, parameters, false, files)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let is_included = match parameters.include_patterns.is_empty() {
+        // Files given explicitly on the command line are always included
+        true => is_top_level || is_audio_file(path),
+        false => parameters
+            .include_patterns
+            .iter()
+            .any(|pattern| wildcard_match(pattern, &file_name)),
+    };
+    let is_excluded = parameters
+        .exclude_patterns
+        .iter()
+        .any(|pattern| wildcard_match(pattern, &file_name));
+
+    if is_included && !is_excluded {
+        files.push(path.to_path_buf());
+    }
+
+    Ok(())
+}
+
+fn load_state_file(state_file: &Option<String>) -> HashSet<String> {
+    match state_file
+        .as_ref()
+        .and_then(|path| std::fs::File::open(path).ok())
+    {
+        Some(file) => BufReader::new(file).lines().map_while(Result::ok).collect(),
+        None => HashSet::new(),
+    }
+}
+
+/// Recognize every file matching the parameters, calling `on_record` for
+/// each result as soon as it is available (in an unspecified order).
+pub async fn recognize_batch(
+    backend: &dyn RecognitionBackend,
+    parameters: &BatchParameters,
+    mut on_record: impl FnMut(BatchRecord) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut files = vec![];
+    for path in &parameters.paths {
+        collect_files(Path::new(path), parameters, true, &mut files)?;
+    }
+
+    let already_done = load_state_file(&parameters.state_file);
+    let total_files = files.len();
+    files.retain(|file| !already_done.contains(&*file.to_string_lossy()));
+
+    if !already_done.is_empty() {
+        info!(
+            "{} {}",
+            gettext("Files skipped as already processed:"),
+            total_files - files.len()
+        );
+    }
+
+    let mut state_file = match parameters.state_file {
+        Some(ref path) => Some(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ),
+        None => None,
+    };
+
+    // Decode and fingerprint the files on the worker pool
+
+    let number_files = files.len();
+    let queue = Arc::new(Mutex::new(files.into_iter()));
+    let (signature_tx, signature_rx) = async_channel::bounded::<(
+        PathBuf,
+        Result<DecodedSignature, String>,
+    )>(parameters.number_workers.max(1) * 2);
+
+    for _ in 0..parameters.number_workers.max(1) {
+        let queue = queue.clone();
+        let signature_tx = signature_tx.clone();
+
+        std::thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(move || {
+                loop {
+                    let Some(path) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let signature =
+                        SignatureGenerator::make_signature_from_file(&path.to_string_lossy())
+                            .map_err(|error| error.to_string());
+
+                    if signature_tx.send_blocking((path, signature)).is_err() {
+                        break;
+                    }
+                }
+            })?;
+    }
+    drop(signature_tx);
+
+    // Recognize the fingerprints as they come
+
+    let mut number_done = 0;
+    let mut is_first_request = true;
+
+    while let Ok((path, signature)) = signature_rx.recv().await {
+        number_done += 1;
+        let file = path.to_string_lossy().into_owned();
+
+        info!("[{}/{}] {}", number_done, number_files, file);
+
+        // Recognition errors (network issues...) are not saved to the state
+        // file, so that the concerned files are retried when resuming
+        let mut is_final = true;
+
+        let record = match signature {
+            Ok(signature) => {
+                if !is_first_request && parameters.request_interval_secs > 0 {
+                    glib::timeout_future(Duration::from_secs(parameters.request_interval_secs))
+                        .await;
+                }
+                is_first_request = false;
+
+                match recognize_with_retries(backend, &signature).await {
+                    Ok(message) => BatchRecord {
+                        file: file.clone(),
+                        status: BatchStatus::Recognized,
+                        artist_name: Some(message.artist_name),
+                        song_name: Some(message.song_name),
+                        album_name: message.album_name,
+                        release_year: message.release_year,
+                        genre: message.genre,
+                        isrc: message.isrc,
+                        track_key: Some(message.track_key),
+                        error: None,
+                    },
+                    Err(RecognitionError::NoMatch) => BatchRecord {
+                        file: file.clone(),
+                        status: BatchStatus::NoMatch,
+                        ..Default::default()
+                    },
+                    Err(error) => {
+                        is_final = false;
+                        BatchRecord {
+                            file: file.clone(),
+                            status: BatchStatus::Error,
+                            error: Some(error.to_string()),
+                            ..Default::default()
+                        }
+                    }
+                }
+            }
+            Err(error) => BatchRecord {
+                file: file.clone(),
+                status: BatchStatus::Error,
+                error: Some(error),
+                ..Default::default()
+            },
+        };
+
+        on_record(record)?;
+
+        if let Some(ref mut state_file) = state_file
+            && is_final
+        {
+            writeln!(state_file, "{}", file)?;
+        }
+    }
+
+    if number_done < number_files {
+        error!("{}", gettext("Some files could not be processed"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match("*.mp3", "Song.MP3"));
+    assert!(wildcard_match("*live*", "artist - song (live).flac"));
+    assert!(wildcard_match("track??.wav", "track01.wav"));
+    assert!(!wildcard_match("track??.wav", "track1.wav"));
+    assert!(!wildcard_match("*.mp3", "song.mp3.part"));
+    assert!(wildcard_match("*", ""));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_collect_files_skips_symlink_loops() {
+    let directory = tempfile::tempdir().unwrap();
+    let album = directory.path().join("album");
+    std::fs::create_dir(&album).unwrap();
+    std::fs::write(album.join("song.mp3"), b"").unwrap();
+    std::os::unix::fs::symlink("..", album.join("loop")).unwrap();
+
+    let parameters = BatchParameters {
+        paths: vec![],
+        recursive: true,
+        include_patterns: vec![],
+        exclude_patterns: vec![],
+        number_workers: 1,
+        request_interval_secs: 0,
+        state_file: None,
+    };
+    let mut files = vec![];
+    collect_files(directory.path(), &parameters, true, &mut files).unwrap();
+    assert_eq!(files, vec![album.join("song.mp3")]);
+}
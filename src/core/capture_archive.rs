@@ -0,0 +1,116 @@
+//! Keeps the audio recorded from the microphone for each recognition
+//! request, so that a wrong or missing recognition can be checked by
+//! listening to what SongRec actually heard, or submitted again.
+//!
+//! The captures are saved as 16 KHz mono WAV files in the data directory,
+//! named after the time of the request, and the oldest ones are removed
+//! beyond a number of files or a total size. The path of a capture is stored
+//! in the history entry of the song recognized from it.
+
+use chrono::Local;
+use log::debug;
+use rodio::buffer::SamplesBuffer;
+use rodio::nz;
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::core::preferences::Preferences;
+use crate::utils::filesystem_operations::obtain_capture_archive_directory;
+
+pub const DEFAULT_MAX_FILES: u64 = 100;
+pub const DEFAULT_MAX_MEGABYTES: u64 = 200;
+
+pub struct Capture {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// The archived captures, from the oldest.
+pub fn list_captures() -> Result<Vec<Capture>, Box<dyn Error>> {
+    let mut captures = vec![];
+
+    for entry in std::fs::read_dir(obtain_capture_archive_directory()?)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_some_and(|extension| extension == "wav") {
+            captures.push(Capture {
+                size: entry.metadata()?.len(),
+                path,
+            });
+        }
+    }
+
+    // The file names start with the date of the capture
+    captures.sort_by(|capture, other_capture| capture.path.cmp(&other_capture.path));
+
+    Ok(captures)
+}
+
+/// The number of oldest captures to remove so that the others fit within
+/// the limits.
+fn number_captures_to_remove(sizes: &[u64], max_files: u64, max_bytes: u64) -> usize {
+    let mut total_size: u64 = sizes.iter().sum();
+    let mut number_removed = 0;
+
+    for size in sizes {
+        if (sizes.len() - number_removed) as u64 <= max_files && total_size <= max_bytes {
+            break;
+        }
+        total_size -= size;
+        number_removed += 1;
+    }
+
+    number_removed
+}
+
+/// Save the audio of a recognition request, and remove the oldest captures
+/// beyond the limits set in the preferences. Returns the path of the new
+/// capture, which is always kept.
+pub fn save_capture(
+    samples: Vec<f32>,
+    preferences: &Preferences,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let mut capture_path = obtain_capture_archive_directory()?;
+    capture_path.push(format!(
+        "{}.wav",
+        Local::now().format("%Y-%m-%d_%H-%M-%S%.3f")
+    ));
+
+    rodio::wav_to_file(
+        SamplesBuffer::new(nz!(1), nz!(16000), samples),
+        &capture_path,
+    )?;
+
+    let captures = list_captures()?;
+    let sizes: Vec<u64> = captures.iter().map(|capture| capture.size).collect();
+
+    let number_removed = number_captures_to_remove(
+        &sizes,
+        preferences
+            .capture_archive_max_files
+            .unwrap_or(DEFAULT_MAX_FILES),
+        preferences
+            .capture_archive_max_megabytes
+            .unwrap_or(DEFAULT_MAX_MEGABYTES)
+            * 1024
+            * 1024,
+    )
+    .min(captures.len().saturating_sub(1));
+
+    for capture in &captures[..number_removed] {
+        debug!("Removing the capture {}", capture.path.display());
+        std::fs::remove_file(&capture.path)?;
+    }
+
+    Ok(capture_path)
+}
+
+#[test]
+fn test_number_captures_to_remove() {
+    assert_eq!(number_captures_to_remove(&[], 2, 100), 0);
+    assert_eq!(number_captures_to_remove(&[10, 10, 10], 3, 100), 0);
+    assert_eq!(number_captures_to_remove(&[10, 10, 10], 2, 100), 1);
+    assert_eq!(number_captures_to_remove(&[50, 30, 30], 3, 70), 1);
+    assert_eq!(number_captures_to_remove(&[50, 30, 30], 3, 50), 2);
+    assert_eq!(number_captures_to_remove(&[50, 30, 30], 0, 100), 3);
+}
@@ -0,0 +1,140 @@
+//! Sets the windows of audio fingerprinted for each recognition request.
+//!
+//! Longer windows hold more frequency peaks, which helps with quiet or noisy
+//! recordings, while shorter ones follow song changes more closely. The
+//! length is kept within what the signature format and recognition backends
+//! handle well, from 6 to 20 seconds.
+//!
+//! In voting mode, two or three windows, staggered by a few seconds, are
+//! recognized one after the other, and a song is only reported when most of
+//! them agree on it, which cuts false positives in noisy venues at the cost
+//! of more requests.
+
+use std::ops::Range;
+
+use crate::core::preferences::Preferences;
+
+pub const MIN_WINDOW_SECS: usize = 6;
+pub const MAX_WINDOW_SECS: usize = 20;
+pub const DEFAULT_WINDOW_SECS: usize = 12;
+
+pub const MAX_VOTING_WINDOWS: usize = 3;
+const VOTING_STAGGER_SECS: usize = 2;
+
+/// The length of microphone audio which has to be kept fingerprinted for
+/// the longest windows, in seconds.
+pub const MAX_HISTORY_SECS: usize =
+    MAX_WINDOW_SECS + (MAX_VOTING_WINDOWS - 1) * VOTING_STAGGER_SECS;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureSettings {
+    pub window_secs: usize,
+    pub number_windows: usize, // 1 when not voting
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        CaptureSettings {
+            window_secs: DEFAULT_WINDOW_SECS,
+            number_windows: 1,
+        }
+    }
+}
+
+impl CaptureSettings {
+    pub fn from_preferences(preferences: &Preferences) -> Self {
+        CaptureSettings {
+            window_secs: preferences
+                .capture_window_secs
+                .map_or(DEFAULT_WINDOW_SECS, |window_secs| {
+                    (window_secs as usize).clamp(MIN_WINDOW_SECS, MAX_WINDOW_SECS)
+                }),
+            number_windows: preferences.voting_windows.map_or(1, |number_windows| {
+                (number_windows as usize).clamp(1, MAX_VOTING_WINDOWS)
+            }),
+        }
+    }
+
+    /// The length of audio covered by all the windows, in seconds.
+    pub fn span_secs(&self) -> usize {
+        self.window_secs + (self.number_windows - 1) * VOTING_STAGGER_SECS
+    }
+
+    /// For the audio recorded from the microphone: the number of seconds
+    /// between the end of each window and the latest samples, from the most
+    /// recent window.
+    pub fn microphone_window_offsets(&self) -> impl Iterator<Item = usize> {
+        (0..self.number_windows).map(|index| index * VOTING_STAGGER_SECS)
+    }
+
+    /// For audio files: the ranges of 16 KHz samples of the windows, around
+    /// the middle of the file in order to increase recognition odds. Files
+    /// shorter than `span_secs` should be padded first.
+    pub fn file_windows(&self, number_samples: usize) -> Vec<Range<usize>> {
+        let span = self.span_secs() * 16000;
+        let first_start = (number_samples / 2)
+            .saturating_sub(span / 2)
+            .min(number_samples.saturating_sub(span));
+
+        (0..self.number_windows)
+            .map(|index| {
+                let start = first_start + index * VOTING_STAGGER_SECS * 16000;
+                start..(start + self.window_secs * 16000).min(number_samples)
+            })
+            .collect()
+    }
+}
+
+/// The index of the first window whose recognized track most of the
+/// windows agree on, if any. Windows which were not recognized (yet) are
+/// `None`.
+pub fn majority_vote(track_keys: &[Option<&str>], number_windows: usize) -> Option<usize> {
+    track_keys.iter().position(|track_key| {
+        track_key.is_some()
+            && track_keys
+                .iter()
+                .filter(|other_track_key| *other_track_key == track_key)
+                .count()
+                * 2
+                > number_windows
+    })
+}
+
+#[test]
+fn test_capture_window() {
+    let mut preferences = Preferences::new();
+    assert_eq!(
+        CaptureSettings::from_preferences(&preferences),
+        CaptureSettings::default()
+    );
+    preferences.capture_window_secs = Some(30);
+    preferences.voting_windows = Some(3);
+    let settings = CaptureSettings::from_preferences(&preferences);
+    assert_eq!(settings.window_secs, MAX_WINDOW_SECS);
+    assert_eq!(settings.span_secs(), MAX_HISTORY_SECS);
+    assert_eq!(
+        settings.microphone_window_offsets().collect::<Vec<_>>(),
+        vec![0, 2, 4]
+    );
+
+    // The middle 12 seconds of a 60 seconds file, as before
+    assert_eq!(
+        CaptureSettings::default().file_windows(16000 * 60),
+        vec![16000 * 24..16000 * 36]
+    );
+    let settings = CaptureSettings {
+        window_secs: 6,
+        number_windows: 2,
+    };
+    assert_eq!(
+        settings.file_windows(16000 * 8),
+        vec![0..16000 * 6, 16000 * 2..16000 * 8]
+    );
+
+    assert_eq!(majority_vote(&[Some("1")], 1), Some(0));
+    assert_eq!(majority_vote(&[None], 1), None);
+    assert_eq!(majority_vote(&[Some("1")], 2), None);
+    assert_eq!(majority_vote(&[Some("1"), Some("1")], 2), Some(0));
+    assert_eq!(majority_vote(&[Some("1"), Some("2")], 3), None);
+    assert_eq!(majority_vote(&[None, Some("2"), Some("2")], 3), Some(1));
+}
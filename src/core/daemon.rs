@@ -0,0 +1,341 @@
+//! The Unix socket API of the daemon mode ("songrec daemon"), which lets
+//! several local clients share a single capturing process. The clients
+//! exchange JSON-RPC 2.0 messages with the daemon, one per line; the
+//! methods are listed in the README.
+
+use gettextrs::gettext;
+use log::{debug, error, info};
+use serde_json::{Value, json};
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::core::capture_window::CaptureSettings;
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::thread_messages::{DeviceListItem, spawn_big_thread};
+use crate::utils::filesystem_operations::obtain_history_database_path;
+use crate::utils::history_database::{HistoryDatabase, HistoryQuery, SongList};
+
+// Error codes defined by the JSON-RPC specification
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+const DEFAULT_HISTORY_LIMIT: u64 = 10;
+
+/// The requests which need the state of the main loop of the daemon.
+pub enum DaemonRequest {
+    Start(Option<String>), // The audio device name, or the current one
+    Stop,
+    SetDevice(String),
+    ListDevices,
+    Status,
+    RecognizeSignatures(Vec<DecodedSignature>), // One per window, voted on when several
+    Subscribe(async_channel::Sender<Value>),    // Receives the notifications
+}
+
+pub struct DaemonCall {
+    pub request: DaemonRequest,
+    pub reply_tx: async_channel::Sender<Result<Value, String>>,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Display) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RpcRequest {
+    id: Option<Value>, // Notifications from the client have no identifier
+    method: String,
+    params: Value,
+}
+
+fn parse_request(line: &str) -> Result<RpcRequest, RpcError> {
+    let request: Value =
+        serde_json::from_str(line).map_err(|error| RpcError::new(PARSE_ERROR, error))?;
+
+    match request["method"].as_str() {
+        Some(method) => Ok(RpcRequest {
+            id: request.get("id").cloned(),
+            method: method.to_string(),
+            params: request.get("params").cloned().unwrap_or(Value::Null),
+        }),
+        None => Err(RpcError::new(INVALID_REQUEST, "Missing method")),
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+}
+
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+pub fn device_to_json(device: &DeviceListItem) -> Value {
+    json!({
+        "name": device.inner_name,
+        "display_name": device.display_name,
+        "is_monitor": device.is_monitor,
+    })
+}
+
+fn string_param(params: &Value, name: &str) -> Result<Option<String>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("\"{}\" should be a string", name),
+        )),
+    }
+}
+
+fn required_string_param(params: &Value, name: &str) -> Result<String, RpcError> {
+    string_param(params, name)?
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Missing \"{}\"", name)))
+}
+
+fn query_history(params: &Value) -> Result<Value, RpcError> {
+    let list = match string_param(params, "list")?.as_deref() {
+        None | Some("history") => SongList::History,
+        Some("favorites") => SongList::Favorites,
+        Some(other) => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown list: {}", other),
+            ));
+        }
+    };
+    let limit = match params.get("limit") {
+        None | Some(Value::Null) => DEFAULT_HISTORY_LIMIT,
+        Some(limit) => limit.as_u64().ok_or_else(|| {
+            RpcError::new(INVALID_PARAMS, "\"limit\" should be a positive integer")
+        })?,
+    };
+
+    let server_error = |error: Box<dyn Error>| RpcError::new(SERVER_ERROR, error);
+    let database = HistoryDatabase::open(&obtain_history_database_path().map_err(server_error)?)
+        .map_err(server_error)?;
+    let rows = database
+        .query(&HistoryQuery {
+            list,
+            descending: true,
+            limit: Some(limit),
+            ..Default::default()
+        })
+        .map_err(server_error)?;
+
+    serde_json::to_value(rows).map_err(|error| RpcError::new(SERVER_ERROR, error))
+}
+
+struct Client {
+    writer: Arc<Mutex<UnixStream>>,
+    daemon_tx: async_channel::Sender<DaemonCall>,
+    capture_settings: CaptureSettings, // The windows recognized from a file
+}
+
+impl Client {
+    fn send(&self, message: &Value) -> std::io::Result<()> {
+        writeln!(self.writer.lock().unwrap(), "{}", message)
+    }
+
+    /// Hand a request to the main loop, and wait for its reply.
+    fn call(&self, request: DaemonRequest) -> Result<Value, RpcError> {
+        let (reply_tx, reply_rx) = async_channel::bounded(1);
+        self.daemon_tx
+            .send_blocking(DaemonCall { request, reply_tx })
+            .map_err(|error| RpcError::new(SERVER_ERROR, error))?;
+        reply_rx
+            .recv_blocking()
+            .map_err(|error| RpcError::new(SERVER_ERROR, error))?
+            .map_err(|message| RpcError::new(SERVER_ERROR, message))
+    }
+
+    fn handle(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "start" => self.call(DaemonRequest::Start(string_param(params, "device")?)),
+            "stop" => self.call(DaemonRequest::Stop),
+            "set_device" => self.call(DaemonRequest::SetDevice(required_string_param(
+                params, "device",
+            )?)),
+            "list_devices" => self.call(DaemonRequest::ListDevices),
+            "status" => self.call(DaemonRequest::Status),
+            "recognize_file" => {
+                // Decode the file in the thread of the client, so that the
+                // main loop stays responsive
+                let path = required_string_param(params, "path")?;
+                let signatures =
+                    SignatureGenerator::make_signatures_from_file(&path, &self.capture_settings)
+                        .map_err(|error| RpcError::new(SERVER_ERROR, error))?;
+                self.call(DaemonRequest::RecognizeSignatures(signatures))
+            }
+            "recognize_signature" => {
+                let uri = required_string_param(params, "signature")?;
+                let signature = DecodedSignature::decode_from_uri(&uri)
+                    .map_err(|error| RpcError::new(INVALID_PARAMS, error))?;
+                self.call(DaemonRequest::RecognizeSignatures(vec![signature]))
+            }
+            "subscribe" => {
+                let (event_tx, event_rx) = async_channel::unbounded::<Value>();
+                let result = self.call(DaemonRequest::Subscribe(event_tx))?;
+
+                let writer = self.writer.clone();
+                spawn_big_thread(move || {
+                    while let Ok(event) = event_rx.recv_blocking() {
+                        if writeln!(writer.lock().unwrap(), "{}", event).is_err() {
+                            break; // The client is gone, unsubscribe
+                        }
+                    }
+                });
+                Ok(result)
+            }
+            "history" => query_history(params),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            )),
+        }
+    }
+}
+
+fn handle_client(
+    stream: UnixStream,
+    daemon_tx: async_channel::Sender<DaemonCall>,
+    capture_settings: CaptureSettings,
+) {
+    let client = match stream.try_clone() {
+        Ok(writer) => Client {
+            writer: Arc::new(Mutex::new(writer)),
+            daemon_tx,
+            capture_settings,
+        },
+        Err(error) => {
+            error!("Could not serve the daemon client: {}", error);
+            return;
+        }
+    };
+    debug!("Daemon client connected");
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match parse_request(&line) {
+            Ok(request) => {
+                let result = client.handle(&request.method, &request.params);
+                match request.id {
+                    Some(id) => response(id, result),
+                    None => continue,
+                }
+            }
+            Err(error) => response(Value::Null, Err(error)),
+        };
+        if client.send(&response).is_err() {
+            break;
+        }
+    }
+
+    debug!("Daemon client disconnected");
+}
+
+/// Listen on the Unix socket, each client being served from its own thread.
+pub fn serve(
+    socket_path: &Path,
+    daemon_tx: async_channel::Sender<DaemonCall>,
+    capture_settings: CaptureSettings,
+) -> Result<(), Box<dyn Error>> {
+    // Remove the socket left by a previous daemon which did not exit
+    // cleanly, unless it is still running
+    if socket_path.exists() && UnixStream::connect(socket_path).is_err() {
+        std::fs::remove_file(socket_path)?;
+    }
+    let listener = UnixListener::bind(socket_path)?;
+
+    info!(
+        "{} {}",
+        gettext("Waiting for clients on:"),
+        socket_path.display()
+    );
+
+    spawn_big_thread(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let daemon_tx = daemon_tx.clone();
+                    spawn_big_thread(move || handle_client(stream, daemon_tx, capture_settings));
+                }
+                Err(error) => error!("Could not accept a daemon client: {}", error),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[test]
+fn test_daemon_protocol() {
+    let request = parse_request(
+        r#"{"jsonrpc": "2.0", "id": 3, "method": "history", "params": {"limit": 5}}"#,
+    )
+    .unwrap();
+    assert_eq!(request.id, Some(json!(3)));
+    assert_eq!(request.method, "history");
+    assert_eq!(request.params["limit"], 5);
+
+    let request = parse_request(r#"{"jsonrpc": "2.0", "method": "stop"}"#).unwrap();
+    assert!(request.id.is_none());
+    assert!(string_param(&request.params, "device").unwrap().is_none());
+
+    assert_eq!(parse_request("{").unwrap_err().code, PARSE_ERROR);
+    assert_eq!(parse_request("[1]").unwrap_err().code, INVALID_REQUEST);
+
+    let params = json!({ "device": 42 });
+    assert_eq!(
+        string_param(&params, "device").unwrap_err().code,
+        INVALID_PARAMS
+    );
+    assert_eq!(
+        required_string_param(&json!({}), "path").unwrap_err().code,
+        INVALID_PARAMS
+    );
+
+    assert_eq!(
+        response(json!(1), Ok(json!(true))).to_string(),
+        r#"{"id":1,"jsonrpc":"2.0","result":true}"#
+    );
+    assert_eq!(
+        response(
+            Value::Null,
+            Err(RpcError::new(METHOD_NOT_FOUND, "Unknown method: foo"))
+        )["error"]["code"],
+        METHOD_NOT_FOUND
+    );
+}
@@ -0,0 +1,193 @@
+//! Recognition of every track within a long recording (DJ set, radio show...),
+//! by sliding a recognition window across the whole file.
+
+use gettextrs::gettext;
+use log::{info, warn};
+use serde::Serialize;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::recognition_backends::backend::{
+    RecognitionBackend, RecognitionError, recognize_with_retries,
+};
+
+const SAMPLE_RATE: usize = 16000;
+
+/// The recognition result for a single window
+pub struct WindowMatch {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub song: Option<SegmentSong>,
+}
+
+#[derive(Clone)]
+pub struct SegmentSong {
+    pub artist_name: String,
+    pub song_name: String,
+    pub album_name: Option<String>,
+    pub track_key: String,
+}
+
+/// A span of the recording during which the same song was recognized (flat,
+/// so that it can be serialized to CSV)
+#[derive(Serialize)]
+pub struct ScanSegment {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub artist_name: String,
+    pub song_name: String,
+    pub album_name: Option<String>,
+    pub track_key: String,
+}
+
+pub struct ScanParameters {
+    pub window_secs: usize,
+    pub step_secs: usize,
+    /// Pause between two recognition requests, to avoid being rate-limited
+    pub request_interval_secs: u64,
+}
+
+/// Format a position in seconds as "HH:MM:SS".
+pub fn format_timestamp(secs: f32) -> String {
+    let secs = secs as u64;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Merge the windows recognized as the same song into segments. Windows
+/// without a match between two windows of the same song don't split it, and
+/// overlapping windows are attributed to the song which comes next.
+pub fn merge_window_matches(window_matches: &[WindowMatch]) -> Vec<ScanSegment> {
+    let mut segments: Vec<ScanSegment> = vec![];
+
+    for window_match in window_matches {
+        if let Some(ref song) = window_match.song {
+            match segments.last_mut() {
+                Some(segment) if segment.track_key == song.track_key => {
+                    segment.end_secs = window_match.end_secs;
+                }
+                previous_segment => {
+                    if let Some(previous_segment) = previous_segment {
+                        previous_segment.end_secs =
+                            previous_segment.end_secs.min(window_match.start_secs);
+                    }
+                    segments.push(ScanSegment {
+                        start_secs: window_match.start_secs,
+                        end_secs: window_match.end_secs,
+                        artist_name: song.artist_name.clone(),
+                        song_name: song.song_name.clone(),
+                        album_name: song.album_name.clone(),
+                        track_key: song.track_key.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    segments
+}
+
+pub async fn scan_file(
+    backend: &dyn RecognitionBackend,
+    file_path: &str,
+    parameters: &ScanParameters,
+) -> Result<Vec<ScanSegment>, Box<dyn Error>> {
+    let samples = SignatureGenerator::decode_file(file_path)?;
+
+    let window_len = parameters.window_secs * SAMPLE_RATE;
+    let step_len = parameters.step_secs.max(1) * SAMPLE_RATE;
+    let total_secs = samples.len() as f32 / SAMPLE_RATE as f32;
+
+    let mut window_matches = vec![];
+    let mut start = 0;
+
+    loop {
+        let end = (start + window_len).min(samples.len());
+
+        // Don't bother with a trailing window shorter than half the normal
+        // length, unless the whole file is that short
+        if start > 0 && end - start < window_len / 2 {
+            break;
+        }
+
+        let start_secs = start as f32 / SAMPLE_RATE as f32;
+
+        info!(
+            "{} {} / {}",
+            gettext("Scanning:"),
+            format_timestamp(start_secs),
+            format_timestamp(total_secs)
+        );
+
+        let signature = SignatureGenerator::make_signature_from_buffer(&samples[start..end]);
+
+        let song = match recognize_with_retries(backend, &signature).await {
+            Ok(message) => Some(SegmentSong {
+                artist_name: message.artist_name,
+                song_name: message.song_name,
+                album_name: message.album_name,
+                track_key: message.track_key,
+            }),
+            Err(RecognitionError::NoMatch) => None,
+            Err(error) => {
+                warn!(
+                    "{} {}: {}",
+                    gettext("Could not recognize the window at"),
+                    format_timestamp(start_secs),
+                    error
+                );
+                None
+            }
+        };
+
+        window_matches.push(WindowMatch {
+            start_secs,
+            end_secs: end as f32 / SAMPLE_RATE as f32,
+            song,
+        });
+
+        if end == samples.len() {
+            break;
+        }
+        start += step_len;
+
+        if parameters.request_interval_secs > 0 {
+            glib::timeout_future(Duration::from_secs(parameters.request_interval_secs)).await;
+        }
+    }
+
+    Ok(merge_window_matches(&window_matches))
+}
+
+#[test]
+fn test_merge_window_matches() {
+    let song = |track_key: &str| {
+        Some(SegmentSong {
+            artist_name: String::new(),
+            song_name: track_key.to_string(),
+            album_name: None,
+            track_key: track_key.to_string(),
+        })
+    };
+    let window = |start_secs: f32, song: Option<SegmentSong>| WindowMatch {
+        start_secs,
+        end_secs: start_secs + 12.0,
+        song,
+    };
+
+    let segments = merge_window_matches(&[
+        window(0.0, None),
+        window(10.0, song("a")),
+        window(20.0, song("a")),
+        window(30.0, None),
+        window(40.0, song("a")),
+        window(50.0, song("b")),
+        window(60.0, song("b")),
+        window(70.0, None),
+    ]);
+
+    assert_eq!(segments.len(), 2);
+    assert_eq!((segments[0].start_secs, segments[0].end_secs), (10.0, 50.0));
+    assert_eq!(segments[1].track_key, "b");
+    assert_eq!((segments[1].start_secs, segments[1].end_secs), (50.0, 72.0));
+}
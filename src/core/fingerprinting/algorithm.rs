@@ -6,6 +6,7 @@ use rustfft::num_traits::Zero;
 use std::error::Error;
 use std::io::BufReader;
 
+use crate::core::capture_window::CaptureSettings;
 use crate::core::fingerprinting::hanning::HANNING_WINDOW_2048_MULTIPLIERS;
 use crate::core::fingerprinting::signature_format::{
     DecodedSignature, FrequencyBand, FrequencyPeak,
@@ -40,11 +41,61 @@ pub struct SignatureGenerator {
 
     num_spread_ffts_done: u32,
 
+    // Used when streaming input:
+    /// Samples received since the last complete 128-sample chunk.
+    pending_samples: Vec<i16>,
+
+    /// Total count of 16 KHz samples turned into FFT passes so far.
+    number_samples_processed: u64,
+
+    /// When set, frequency peaks older than this many seconds are dropped
+    /// as new samples come in, so that memory use stays bounded.
+    max_history_secs: Option<usize>,
+
     signature: DecodedSignature,
 }
 
+impl Default for SignatureGenerator {
+    fn default() -> Self {
+        SignatureGenerator::new()
+    }
+}
+
 impl SignatureGenerator {
     pub fn make_signature_from_file(file_path: &str) -> Result<DecodedSignature, Box<dyn Error>> {
+        Ok(
+            SignatureGenerator::make_signatures_from_file(file_path, &CaptureSettings::default())?
+                .remove(0),
+        )
+    }
+
+    /// Fingerprint the windows of an audio file set in the capture
+    /// settings, one signature per window.
+    pub fn make_signatures_from_file(
+        file_path: &str,
+        capture_settings: &CaptureSettings,
+    ) -> Result<Vec<DecodedSignature>, Box<dyn Error>> {
+        let mut raw_pcm_samples = SignatureGenerator::decode_file(file_path)?;
+
+        // Pad the input to at least the length of the windows in order to
+        // avoid missing data at the end of the input
+
+        let span_samples = capture_settings.span_secs() * 16000;
+
+        if raw_pcm_samples.len() < span_samples {
+            raw_pcm_samples.resize(span_samples, 0.0);
+        }
+
+        Ok(capture_settings
+            .file_windows(raw_pcm_samples.len())
+            .into_iter()
+            .map(|window| SignatureGenerator::make_signature_from_buffer(&raw_pcm_samples[window]))
+            .collect())
+    }
+
+    /// Decode a .WAV, .MP3, .OGG or .FLAC file (or any other format supported
+    /// by FFMpeg, if available) into 16 KHz mono samples.
+    pub fn decode_file(file_path: &str) -> Result<Vec<f32>, Box<dyn Error>> {
         // Decode the .WAV, .MP3, .OGG or .FLAC file
 
         #[cfg(not(feature = "ffmpeg"))]
@@ -72,36 +123,23 @@ impl SignatureGenerator {
         let converted_file =
             rodio::source::UniformSourceIterator::new(decoder?, nz!(1), nz!(16000));
 
-        let mut raw_pcm_samples: Vec<f32> = converted_file.collect();
-
-        // Pad the input to at least 12 seconds in order to avoid missing data
-        // at the end of the input
-
-        if raw_pcm_samples.len() < 12 * 16000 {
-            raw_pcm_samples.resize(12 * 16000, 0.0);
-        }
-
-        // Skip to the middle of the file in order to increase recognition
-        // odds. Take 12 seconds of sample.
-
-        let mut raw_pcm_samples_slice: &[f32] = &raw_pcm_samples;
-
-        let slice_len = raw_pcm_samples_slice.len().min(12 * 16000);
+        Ok(converted_file.collect())
+    }
 
-        if raw_pcm_samples_slice.len() > 12 * 16000 {
-            let middle = raw_pcm_samples.len() / 2;
+    pub fn make_signature_from_buffer(f32_mono_16khz_buffer: &[f32]) -> DecodedSignature {
+        let mut this = SignatureGenerator::new();
 
-            raw_pcm_samples_slice =
-                &raw_pcm_samples_slice[middle - (6 * 16000)..middle + (6 * 16000)];
-        }
+        this.add_samples(f32_mono_16khz_buffer);
 
-        Ok(SignatureGenerator::make_signature_from_buffer(
-            &raw_pcm_samples_slice[..slice_len],
-        ))
+        let mut signature = this.signature;
+        signature.number_samples = f32_mono_16khz_buffer.len() as u32;
+        signature
     }
 
-    pub fn make_signature_from_buffer(f32_mono_16khz_buffer: &[f32]) -> DecodedSignature {
-        let mut this = SignatureGenerator {
+    /// Create a generator that keeps every frequency peak found since it was
+    /// created.
+    pub fn new() -> Self {
+        SignatureGenerator {
             ring_buffer_of_samples: Box::new([0i16; 2048]),
             ring_buffer_of_samples_index: 0,
 
@@ -118,29 +156,125 @@ impl SignatureGenerator {
 
             num_spread_ffts_done: 0,
 
+            pending_samples: Vec::with_capacity(128),
+            number_samples_processed: 0,
+            max_history_secs: None,
+
             signature: DecodedSignature {
                 sample_rate_hz: 16000,
-                number_samples: f32_mono_16khz_buffer.len() as u32,
+                number_samples: 0,
                 frequency_band_to_sound_peaks: Default::default(),
             },
-        };
+        }
+    }
+
+    /// Create a generator meant to be fed with a live stream, that only
+    /// keeps the frequency peaks found over the last `max_history_secs`
+    /// seconds.
+    pub fn streaming(max_history_secs: usize) -> Self {
+        let mut this = SignatureGenerator::new();
+        this.max_history_secs = Some(max_history_secs);
+        this
+    }
+
+    /// Feed an arbitrary number of 16 KHz mono samples to the generator.
+    /// Samples that do not fill a complete 128-sample FFT pass are kept
+    /// until the next call.
+    pub fn add_samples(&mut self, f32_mono_16khz_samples: &[f32]) {
+        self.pending_samples
+            .extend(SampleTypeConverter::<_, i16>::new(
+                f32_mono_16khz_samples.iter().copied(),
+            ));
 
-        let s16_buffer: Vec<i16> =
-            SampleTypeConverter::<_, i16>::new(f32_mono_16khz_buffer.iter().copied()).collect();
+        let number_complete_samples = self.pending_samples.len() - self.pending_samples.len() % 128;
 
-        for chunk in s16_buffer.as_chunks::<128>().0 {
-            this.do_fft(chunk);
+        let complete_samples: Vec<i16> = self
+            .pending_samples
+            .drain(..number_complete_samples)
+            .collect();
 
-            this.do_peak_spreading();
+        for chunk in complete_samples.as_chunks::<128>().0 {
+            self.do_fft(chunk);
 
-            this.num_spread_ffts_done += 1;
+            self.do_peak_spreading();
 
-            if this.num_spread_ffts_done >= 46 {
-                this.do_peak_recognition();
+            self.num_spread_ffts_done += 1;
+
+            if self.num_spread_ffts_done >= 46 {
+                self.do_peak_recognition();
             }
         }
 
-        this.signature
+        self.number_samples_processed += number_complete_samples as u64;
+
+        if let Some(max_history_secs) = self.max_history_secs {
+            self.forget_peaks_before(
+                self.num_spread_ffts_done
+                    .saturating_sub((max_history_secs * 16000 / 128) as u32),
+            );
+        }
+    }
+
+    /// Build a signature out of the frequency peaks found over the last
+    /// `seconds` seconds of fed audio, as if these had been passed to
+    /// `make_signature_from_buffer`.
+    pub fn get_signature_for_last_seconds(&self, seconds: usize) -> DecodedSignature {
+        self.get_signature_for_window(seconds, 0)
+    }
+
+    /// Same, for the `seconds` seconds of fed audio which end
+    /// `seconds_before_end` seconds before the latest samples.
+    pub fn get_signature_for_window(
+        &self,
+        seconds: usize,
+        seconds_before_end: usize,
+    ) -> DecodedSignature {
+        let number_skipped_samples =
+            (self.number_samples_processed as usize).min(seconds_before_end * 16000);
+        let number_samples =
+            (self.number_samples_processed as usize - number_skipped_samples).min(seconds * 16000);
+
+        let end_fft_pass_number = self
+            .num_spread_ffts_done
+            .saturating_sub((number_skipped_samples / 128) as u32);
+        let first_fft_pass_number =
+            end_fft_pass_number.saturating_sub((number_samples / 128) as u32);
+
+        let mut frequency_band_to_sound_peaks: [Vec<FrequencyPeak>; 4] = Default::default();
+
+        for (band_peaks, stored_band_peaks) in frequency_band_to_sound_peaks
+            .iter_mut()
+            .zip(self.signature.frequency_band_to_sound_peaks.iter())
+        {
+            let first_index = stored_band_peaks
+                .partition_point(|peak| peak.fft_pass_number < first_fft_pass_number);
+            let end_index = stored_band_peaks
+                .partition_point(|peak| peak.fft_pass_number < end_fft_pass_number);
+
+            band_peaks.extend(
+                stored_band_peaks[first_index..end_index]
+                    .iter()
+                    .map(|peak| FrequencyPeak {
+                        fft_pass_number: peak.fft_pass_number - first_fft_pass_number,
+                        ..*peak
+                    }),
+            );
+        }
+
+        DecodedSignature {
+            sample_rate_hz: 16000,
+            number_samples: number_samples as u32,
+            frequency_band_to_sound_peaks,
+        }
+    }
+
+    fn forget_peaks_before(&mut self, fft_pass_number: u32) {
+        for band_peaks in self.signature.frequency_band_to_sound_peaks.iter_mut() {
+            let first_index =
+                band_peaks.partition_point(|peak| peak.fft_pass_number < fft_pass_number);
+
+            band_peaks.drain(..first_index);
+        }
     }
 
     fn do_fft(&mut self, s16_mono_16khz_buffer: &[i16; 128]) {
@@ -320,3 +454,92 @@ impl SignatureGenerator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{RngExt, SeedableRng};
+
+    /// Generate 16 KHz mono audio made of random chords changing every
+    /// eighth of a second, so that there are plenty of peaks to compare.
+    fn random_audio(rng: &mut StdRng, seconds: usize) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(seconds * 16000);
+
+        while samples.len() < seconds * 16000 {
+            let frequencies: Vec<f32> = (0..3).map(|_| rng.random_range(300.0..5000.0)).collect();
+
+            for index in 0..2000 {
+                let time = index as f32 / 16000.0;
+                samples.push(
+                    frequencies
+                        .iter()
+                        .map(|frequency| 0.2 * (std::f32::consts::TAU * frequency * time).sin())
+                        .sum(),
+                );
+            }
+        }
+
+        samples.truncate(seconds * 16000);
+        samples
+    }
+
+    /// Feed samples in uneven chunks, as an audio callback would.
+    fn feed_in_chunks(generator: &mut SignatureGenerator, rng: &mut StdRng, samples: &[f32]) {
+        let mut position = 0;
+
+        while position < samples.len() {
+            let end = (position + rng.random_range(1..5000)).min(samples.len());
+            generator.add_samples(&samples[position..end]);
+            position = end;
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_buffer() {
+        let mut rng = StdRng::seed_from_u64(1001);
+        let samples = random_audio(&mut rng, 10);
+
+        let mut generator = SignatureGenerator::streaming(12);
+        feed_in_chunks(&mut generator, &mut rng, &samples);
+
+        let expected = SignatureGenerator::make_signature_from_buffer(&samples);
+        assert!(
+            !expected
+                .frequency_band_to_sound_peaks
+                .iter()
+                .all(Vec::is_empty)
+        );
+
+        assert_eq!(generator.get_signature_for_last_seconds(10), expected);
+    }
+
+    #[test]
+    fn test_streaming_window_matches_buffer() {
+        let mut rng = StdRng::seed_from_u64(2024);
+        let samples = random_audio(&mut rng, 14);
+
+        let mut generator = SignatureGenerator::streaming(14);
+        feed_in_chunks(&mut generator, &mut rng, &samples);
+
+        let expected = SignatureGenerator::make_signature_from_buffer(&samples[..10 * 16000]);
+        assert!(
+            !expected
+                .frequency_band_to_sound_peaks
+                .iter()
+                .all(Vec::is_empty)
+        );
+
+        // A peak is only found 46 FFT passes after it was fed, so that the
+        // streaming generator, which has heard the following seconds, also
+        // knows the peaks at the very end of the window
+        let mut window = generator.get_signature_for_window(10, 4);
+        let number_passes = (10 * 16000 / 128) as u32;
+
+        for band_peaks in window.frequency_band_to_sound_peaks.iter_mut() {
+            band_peaks.retain(|peak| peak.fft_pass_number + 46 <= number_passes);
+        }
+
+        assert_eq!(window, expected);
+    }
+}
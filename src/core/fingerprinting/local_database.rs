@@ -0,0 +1,507 @@
+//! A local reference database of audio fingerprints, allowing to recognize
+//! songs from the user's own audio library without any network access.
+//!
+//! Following the approach described in Avery Wang's paper ("An
+//! Industrial-Strength Audio Search Algorithm", 2003), each frequency peak of
+//! a signature is paired with a few of the peaks that follow it, and every
+//! pair is hashed from the two frequencies and the time delta between them.
+//! A query signature matches a reference track when many of its hashes are
+//! found in this track at the same relative time offset.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use gettextrs::gettext;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::utils::filesystem_operations::is_audio_file;
+
+const DATABASE_MAGIC: u32 = 0x42445253; // "SRDB"
+const DATABASE_VERSION: u32 = 1;
+
+/// Maximal number of peaks following an anchor peak that it gets paired with
+const FAN_OUT: usize = 5;
+
+/// Maximal time delta between the two peaks of a pair, in FFT passes (each
+/// FFT pass covers 128 samples at 16 KHz, that is 8 ms)
+const MAX_PAIR_DELTA: u32 = 127;
+
+/// Minimal number of time-aligned hashes for a match to be reported
+const MIN_ALIGNED_HASHES: u32 = 10;
+
+pub struct LocalTrack {
+    pub path: String,
+    pub artist_name: String,
+    pub song_name: String,
+}
+
+pub struct LocalMatch<'a> {
+    pub track: &'a LocalTrack,
+    /// Number of hashes aligned at the best time offset
+    pub score: u32,
+    /// Position of the start of the query signature within the track
+    pub offset_secs: f32,
+}
+
+#[derive(Default)]
+pub struct LocalDatabase {
+    tracks: Vec<LocalTrack>,
+    /// Peak pair hash => (track index, FFT pass number of the anchor peak)
+    hashes: HashMap<u32, Vec<(u32, u32)>>,
+}
+
+/// Compute the (hash, anchor FFT pass number) of the peak pairs of a signature.
+pub fn hash_peak_pairs(signature: &DecodedSignature) -> Vec<(u32, u32)> {
+    let mut peaks: Vec<(u32, u32)> = signature
+        .frequency_band_to_sound_peaks
+        .iter()
+        .flatten()
+        .map(|peak| {
+            (
+                peak.fft_pass_number,
+                // Strip the sub-bin precision, which is not robust to noise
+                (peak.corrected_peak_frequency_bin >> 6) as u32,
+            )
+        })
+        .collect();
+
+    peaks.sort_unstable();
+
+    let mut hashes = vec![];
+
+    for (index, &(anchor_pass, anchor_bin)) in peaks.iter().enumerate() {
+        for &(target_pass, target_bin) in peaks[index + 1..]
+            .iter()
+            .filter(|(target_pass, _)| *target_pass > anchor_pass)
+            .take_while(|(target_pass, _)| *target_pass - anchor_pass <= MAX_PAIR_DELTA)
+            .take(FAN_OUT)
+        {
+            let hash = ((anchor_bin & 0x3ff) << 17)
+                | ((target_bin & 0x3ff) << 7)
+                | (target_pass - anchor_pass);
+
+            hashes.push((hash, anchor_pass));
+        }
+    }
+
+    hashes
+}
+
+/// Ensure that a length read from the database file does not exceed what
+/// remains of the file, so that a corrupt file can not make us allocate an
+/// arbitrary amount of memory.
+fn check_length<R: Seek>(
+    reader: &mut R,
+    file_size: u64,
+    length: u64,
+) -> Result<(), Box<dyn Error>> {
+    if length > file_size.saturating_sub(reader.stream_position()?) {
+        return Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            gettext("Truncated or corrupt local database file"),
+        )));
+    }
+    Ok(())
+}
+
+fn read_string<R: Read + Seek>(reader: &mut R, file_size: u64) -> Result<String, Box<dyn Error>> {
+    let length = reader.read_u32::<LittleEndian>()? as u64;
+    check_length(reader, file_size, length)?;
+    let mut buffer = vec![0u8; length as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+fn invalid_database_error() -> Box<dyn Error> {
+    Box::new(std::io::Error::other(gettext(
+        "Invalid or unsupported local database file",
+    )))
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> Result<(), Box<dyn Error>> {
+    writer.write_u32::<LittleEndian>(string.len() as u32)?;
+    writer.write_all(string.as_bytes())?;
+    Ok(())
+}
+
+impl LocalDatabase {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = std::fs::File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        if reader.read_u32::<LittleEndian>()? != DATABASE_MAGIC
+            || reader.read_u32::<LittleEndian>()? != DATABASE_VERSION
+        {
+            return Err(invalid_database_error());
+        }
+
+        let mut database = LocalDatabase::default();
+
+        for _ in 0..reader.read_u32::<LittleEndian>()? {
+            database.tracks.push(LocalTrack {
+                path: read_string(&mut reader, file_size)?,
+                artist_name: read_string(&mut reader, file_size)?,
+                song_name: read_string(&mut reader, file_size)?,
+            });
+        }
+
+        for _ in 0..reader.read_u32::<LittleEndian>()? {
+            let hash = reader.read_u32::<LittleEndian>()?;
+            let number_entries = reader.read_u32::<LittleEndian>()?;
+            check_length(&mut reader, file_size, number_entries as u64 * 8)?;
+
+            let mut entries = Vec::with_capacity(number_entries as usize);
+            for _ in 0..number_entries {
+                let track_index = reader.read_u32::<LittleEndian>()?;
+                let fft_pass_number = reader.read_u32::<LittleEndian>()?;
+
+                // Don't let a corrupt file point to a track that does not
+                // exist, which would panic when matching
+                if track_index as usize >= database.tracks.len() {
+                    return Err(invalid_database_error());
+                }
+                entries.push((track_index, fft_pass_number));
+            }
+            database.hashes.insert(hash, entries);
+        }
+
+        Ok(database)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        // Write to a temporary file first so that an interrupted save does
+        // not corrupt the existing database
+
+        let mut temporary_path = path.as_os_str().to_owned();
+        temporary_path.push(".tmp");
+        let temporary_path = PathBuf::from(temporary_path);
+
+        {
+            let mut writer = BufWriter::new(std::fs::File::create(&temporary_path)?);
+
+            writer.write_u32::<LittleEndian>(DATABASE_MAGIC)?;
+            writer.write_u32::<LittleEndian>(DATABASE_VERSION)?;
+
+            writer.write_u32::<LittleEndian>(self.tracks.len() as u32)?;
+            for track in &self.tracks {
+                write_string(&mut writer, &track.path)?;
+                write_string(&mut writer, &track.artist_name)?;
+                write_string(&mut writer, &track.song_name)?;
+            }
+
+            writer.write_u32::<LittleEndian>(self.hashes.len() as u32)?;
+            for (hash, entries) in &self.hashes {
+                writer.write_u32::<LittleEndian>(*hash)?;
+                writer.write_u32::<LittleEndian>(entries.len() as u32)?;
+                for (track_index, fft_pass_number) in entries {
+                    writer.write_u32::<LittleEndian>(*track_index)?;
+                    writer.write_u32::<LittleEndian>(*fft_pass_number)?;
+                }
+            }
+
+            writer.flush()?;
+        }
+
+        std::fs::rename(temporary_path, path)?;
+
+        Ok(())
+    }
+
+    pub fn number_tracks(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn contains_path(&self, path: &str) -> bool {
+        self.tracks.iter().any(|track| track.path == path)
+    }
+
+    /// Add the fingerprint of a whole track to the database.
+    pub fn add_track(&mut self, track: LocalTrack, signature: &DecodedSignature) {
+        let track_index = self.tracks.len() as u32;
+
+        for (hash, fft_pass_number) in hash_peak_pairs(signature) {
+            self.hashes
+                .entry(hash)
+                .or_default()
+                .push((track_index, fft_pass_number));
+        }
+
+        self.tracks.push(track);
+    }
+
+    /// Find the reference track which best matches a query signature, if any.
+    pub fn find_match(&self, signature: &DecodedSignature) -> Option<LocalMatch<'_>> {
+        // Count the hashes found for every (track, time offset) couple
+
+        let mut aligned_hashes: HashMap<(u32, i64), u32> = HashMap::new();
+
+        for (hash, query_fft_pass_number) in hash_peak_pairs(signature) {
+            if let Some(entries) = self.hashes.get(&hash) {
+                for &(track_index, fft_pass_number) in entries {
+                    *aligned_hashes
+                        .entry((
+                            track_index,
+                            fft_pass_number as i64 - query_fft_pass_number as i64,
+                        ))
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        let ((track_index, offset), score) =
+            aligned_hashes.into_iter().max_by_key(|&(_, score)| score)?;
+
+        if score < MIN_ALIGNED_HASHES {
+            return None;
+        }
+
+        Some(LocalMatch {
+            track: &self.tracks[track_index as usize],
+            score,
+            offset_secs: offset as f32 * 128.0 / 16000.0,
+        })
+    }
+
+    /// Recursively fingerprint the audio files of a directory that are not
+    /// indexed yet. Returns the number of newly indexed files.
+    pub fn index_directory(&mut self, directory: &Path) -> Result<usize, Box<dyn Error>> {
+        let mut number_indexed = 0;
+
+        let mut entries: Vec<(PathBuf, std::fs::FileType)> = std::fs::read_dir(directory)?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                Some((entry.path(), entry.file_type().ok()?))
+            })
+            .collect();
+        entries.sort_by(|(path, _), (other_path, _)| path.cmp(other_path));
+
+        for (path, file_type) in entries {
+            if file_type.is_dir() {
+                // Keep indexing the rest of the library when a subdirectory
+                // can not be read
+                match self.index_directory(&path) {
+                    Ok(number_indexed_below) => number_indexed += number_indexed_below,
+                    Err(error) => {
+                        warn!(
+                            "{} {}: {}",
+                            gettext("Could not read the directory:"),
+                            path.display(),
+                            error
+                        );
+                    }
+                }
+                continue;
+            }
+
+            // Don't follow symbolic links to directories, which may loop
+            if file_type.is_symlink() && path.is_dir() {
+                continue;
+            }
+
+            let path_string = path.to_string_lossy().into_owned();
+
+            if !is_audio_file(&path) || self.contains_path(&path_string) {
+                continue;
+            }
+
+            match SignatureGenerator::decode_file(&path_string) {
+                Ok(samples) => {
+                    info!("{} {}", gettext("Indexing:"), path_string);
+
+                    let signature = SignatureGenerator::make_signature_from_buffer(&samples);
+
+                    // Guess the artist and song names from file names like
+                    // "Artist - Title.mp3"
+
+                    let file_stem = path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+
+                    let (artist_name, song_name) = match file_stem.split_once(" - ") {
+                        Some((artist_name, song_name)) => {
+                            (artist_name.trim().to_string(), song_name.trim().to_string())
+                        }
+                        None => (String::new(), file_stem),
+                    };
+
+                    self.add_track(
+                        LocalTrack {
+                            path: path_string,
+                            artist_name,
+                            song_name,
+                        },
+                        &signature,
+                    );
+
+                    number_indexed += 1;
+                }
+                Err(error) => {
+                    warn!(
+                        "{} {}: {}",
+                        gettext("Could not decode:"),
+                        path_string,
+                        error
+                    );
+                }
+            }
+        }
+
+        Ok(number_indexed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::fingerprinting::signature_format::FrequencyPeak;
+    use rand::rngs::StdRng;
+    use rand::{RngExt, SeedableRng};
+
+    fn random_signature(rng: &mut StdRng, number_passes: u32) -> DecodedSignature {
+        let mut signature = DecodedSignature {
+            sample_rate_hz: 16000,
+            number_samples: number_passes * 128,
+            frequency_band_to_sound_peaks: Default::default(),
+        };
+
+        for fft_pass_number in 0..number_passes {
+            if rng.random_bool(0.3) {
+                signature.frequency_band_to_sound_peaks[rng.random_range(0..4)].push(
+                    FrequencyPeak {
+                        fft_pass_number,
+                        peak_magnitude: rng.random(),
+                        corrected_peak_frequency_bin: rng.random_range(640..64960),
+                    },
+                );
+            }
+        }
+
+        signature
+    }
+
+    /// Keep the peaks of a signature within [start, end[ passes, as if the
+    /// corresponding excerpt had been recorded.
+    fn excerpt(signature: &DecodedSignature, start: u32, end: u32) -> DecodedSignature {
+        let mut excerpt = DecodedSignature {
+            sample_rate_hz: 16000,
+            number_samples: (end - start) * 128,
+            frequency_band_to_sound_peaks: Default::default(),
+        };
+
+        for (band, peaks) in signature.frequency_band_to_sound_peaks.iter().enumerate() {
+            for peak in peaks {
+                if peak.fft_pass_number >= start && peak.fft_pass_number < end {
+                    excerpt.frequency_band_to_sound_peaks[band].push(FrequencyPeak {
+                        fft_pass_number: peak.fft_pass_number - start,
+                        ..*peak
+                    });
+                }
+            }
+        }
+
+        excerpt
+    }
+
+    #[test]
+    fn test_find_match_with_offset() {
+        let mut rng = StdRng::seed_from_u64(2003);
+        let mut database = LocalDatabase::default();
+
+        let signatures: Vec<DecodedSignature> =
+            (0..5).map(|_| random_signature(&mut rng, 20000)).collect();
+
+        for (index, signature) in signatures.iter().enumerate() {
+            database.add_track(
+                LocalTrack {
+                    path: format!("track_{}.mp3", index),
+                    artist_name: String::new(),
+                    song_name: format!("Track {}", index),
+                },
+                signature,
+            );
+        }
+
+        let found = database
+            .find_match(&excerpt(&signatures[3], 5000, 6500))
+            .unwrap();
+        assert_eq!(found.track.song_name, "Track 3");
+        assert_eq!(found.offset_secs, 5000.0 * 128.0 / 16000.0);
+
+        assert!(
+            database
+                .find_match(&random_signature(&mut rng, 1500))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_oversized_lengths() {
+        let mut rng = StdRng::seed_from_u64(404);
+        let mut database = LocalDatabase::default();
+
+        database.add_track(
+            LocalTrack {
+                path: "track.mp3".to_string(),
+                artist_name: "Artist".to_string(),
+                song_name: "Song".to_string(),
+            },
+            &random_signature(&mut rng, 2000),
+        );
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("local_database.bin");
+        database.save(&path).unwrap();
+
+        assert_eq!(LocalDatabase::load(&path).unwrap().number_tracks(), 1);
+
+        // Corrupt the length of the first track path, right after the
+        // magic, version and number of tracks
+        let mut data = std::fs::read(&path).unwrap();
+        data[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+
+        assert!(LocalDatabase::load(&path).is_err());
+
+        // Truncate the file in the middle of the hash entries
+        database.save(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 6]).unwrap();
+
+        assert!(LocalDatabase::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_tracks() {
+        let mut rng = StdRng::seed_from_u64(405);
+        let mut database = LocalDatabase::default();
+
+        database.add_track(
+            LocalTrack {
+                path: "track.mp3".to_string(),
+                artist_name: "Artist".to_string(),
+                song_name: "Song".to_string(),
+            },
+            &random_signature(&mut rng, 2000),
+        );
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("local_database.bin");
+        database.save(&path).unwrap();
+
+        // Point the first hash entry to a second track, right after the
+        // header, the strings of the track, the number of hashes, the first
+        // hash and its number of entries
+        let offset = 12 + (4 + 9) + (4 + 6) + (4 + 4) + 4 + 4 + 4;
+        let mut data = std::fs::read(&path).unwrap();
+        data[offset..offset + 4].copy_from_slice(&1u32.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+
+        assert!(LocalDatabase::load(&path).is_err());
+    }
+}
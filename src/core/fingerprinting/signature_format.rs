@@ -3,10 +3,137 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use crc32fast::Hasher;
 use gettextrs::gettext;
 use std::error::Error;
+use std::fmt;
 use std::io::{Cursor, Seek, SeekFrom, Write};
 
 const DATA_URI_PREFIX: &str = "data:audio/vnd.shazam.sig;base64,";
 
+#[derive(Debug)]
+pub enum SignatureDecodeError {
+    BadMagic { expected: u32, actual: u32 },
+    CrcMismatch { expected: u32, actual: u32 },
+    SizeMismatch { expected: usize, actual: usize },
+    TruncatedHeader,
+    TruncatedTlv,
+    UnknownFrequencyBand(u32),
+    UnknownSampleRate(u32),
+    BadDataUriPrefix,
+    Base64(base64::DecodeError),
+}
+
+impl fmt::Display for SignatureDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureDecodeError::BadMagic { expected, actual } => write!(
+                f,
+                "{} (expected {:#010x}, got {:#010x})",
+                gettext("Invalid magic number in decoded Shazam packet"),
+                expected,
+                actual
+            ),
+            SignatureDecodeError::CrcMismatch { expected, actual } => write!(
+                f,
+                "{} (expected {:#010x}, got {:#010x})",
+                gettext("Invalid CRC-32 checksum in decoded Shazam packet"),
+                expected,
+                actual
+            ),
+            SignatureDecodeError::SizeMismatch { expected, actual } => write!(
+                f,
+                "{} (expected {}, got {})",
+                gettext("Invalid size in decoded Shazam packet"),
+                expected,
+                actual
+            ),
+            SignatureDecodeError::TruncatedHeader => write!(
+                f,
+                "{}",
+                gettext("Truncated header in decoded Shazam packet")
+            ),
+            SignatureDecodeError::TruncatedTlv => write!(
+                f,
+                "{}",
+                gettext("Truncated frequency peaks in decoded Shazam packet")
+            ),
+            SignatureDecodeError::UnknownFrequencyBand(band_id) => write!(
+                f,
+                "{} ({:#010x})",
+                gettext("Invalid frequency band in decoded Shazam packet"),
+                band_id
+            ),
+            SignatureDecodeError::UnknownSampleRate(sample_rate_id) => write!(
+                f,
+                "{} ({})",
+                gettext("Invalid sample rate in decoded Shazam packet"),
+                sample_rate_id
+            ),
+            SignatureDecodeError::BadDataUriPrefix => write!(
+                f,
+                "{} {}",
+                gettext("The Shazam fingerprint should start with:"),
+                DATA_URI_PREFIX
+            ),
+            SignatureDecodeError::Base64(error) => write!(
+                f,
+                "{} {}",
+                gettext("Invalid base64 in the Shazam fingerprint:"),
+                error
+            ),
+        }
+    }
+}
+
+impl Error for SignatureDecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SignatureDecodeError::Base64(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SignatureEncodeError {
+    UnsupportedSampleRate(u32),
+    UnorderedPeaks,
+    Io(std::io::Error),
+}
+
+impl fmt::Display for SignatureEncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureEncodeError::UnsupportedSampleRate(sample_rate_hz) => write!(
+                f,
+                "{} ({} Hz)",
+                gettext("Invalid sample rate passed when encoding Shazam packet"),
+                sample_rate_hz
+            ),
+            SignatureEncodeError::UnorderedPeaks => write!(
+                f,
+                "{}",
+                gettext("Unordered frequency peaks passed when encoding Shazam packet")
+            ),
+            SignatureEncodeError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for SignatureEncodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SignatureEncodeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SignatureEncodeError {
+    fn from(error: std::io::Error) -> Self {
+        SignatureEncodeError::Io(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct FrequencyPeak {
     pub fft_pass_number: u32,
     pub peak_magnitude: u16,
@@ -33,6 +160,7 @@ struct RawSignatureHeader {
     _fixed_value: u32, // Calculated as ((15 << 19) + 0x40000) - 0x7c0000 or 00 00 7c 00 - seems pretty constant, may be different in the "SigType.STREAMING" mode
 }
 
+#[derive(Debug, PartialEq)]
 pub struct DecodedSignature {
     pub sample_rate_hz: u32,
     pub number_samples: u32,
@@ -40,36 +168,58 @@ pub struct DecodedSignature {
 }
 
 impl DecodedSignature {
-    pub fn decode_from_binary(data: &[u8]) -> Result<Self, Box<dyn Error>> {
-        assert!(data.len() > 48 + 8);
+    pub fn decode_from_binary(data: &[u8]) -> Result<Self, SignatureDecodeError> {
+        if data.len() < 48 + 8 {
+            return Err(SignatureDecodeError::TruncatedHeader);
+        }
 
         let mut cursor = Cursor::new(data);
 
+        // Reading can't fail past the length check above
+        let mut read_u32 = || cursor.read_u32::<LittleEndian>().unwrap();
+
         let header = RawSignatureHeader {
-            magic1: cursor.read_u32::<LittleEndian>()?,
-            crc32: cursor.read_u32::<LittleEndian>()?,
-            size_minus_header: cursor.read_u32::<LittleEndian>()?,
-            magic2: cursor.read_u32::<LittleEndian>()?,
-            _void1: [
-                cursor.read_u32::<LittleEndian>()?,
-                cursor.read_u32::<LittleEndian>()?,
-                cursor.read_u32::<LittleEndian>()?,
-            ],
-            shifted_sample_rate_id: cursor.read_u32::<LittleEndian>()?,
-            _void2: [
-                cursor.read_u32::<LittleEndian>()?,
-                cursor.read_u32::<LittleEndian>()?,
-            ],
-            number_samples_plus_divided_sample_rate: cursor.read_u32::<LittleEndian>()?,
-            _fixed_value: cursor.read_u32::<LittleEndian>()?,
+            magic1: read_u32(),
+            crc32: read_u32(),
+            size_minus_header: read_u32(),
+            magic2: read_u32(),
+            _void1: [read_u32(), read_u32(), read_u32()],
+            shifted_sample_rate_id: read_u32(),
+            _void2: [read_u32(), read_u32()],
+            number_samples_plus_divided_sample_rate: read_u32(),
+            _fixed_value: read_u32(),
         };
 
+        if header.magic1 != 0xcafe2580 {
+            return Err(SignatureDecodeError::BadMagic {
+                expected: 0xcafe2580,
+                actual: header.magic1,
+            });
+        }
+
         let mut hasher = Hasher::new();
         hasher.update(&data[8..]);
-        assert_eq!(header.magic1, 0xcafe2580);
-        assert_eq!(header.size_minus_header as usize, data.len() - 48);
-        assert_eq!(header.crc32, hasher.finalize());
-        assert_eq!(header.magic2, 0x94119c00);
+        let crc32 = hasher.finalize();
+        if header.crc32 != crc32 {
+            return Err(SignatureDecodeError::CrcMismatch {
+                expected: header.crc32,
+                actual: crc32,
+            });
+        }
+
+        if header.size_minus_header as usize != data.len() - 48 {
+            return Err(SignatureDecodeError::SizeMismatch {
+                expected: header.size_minus_header as usize,
+                actual: data.len() - 48,
+            });
+        }
+
+        if header.magic2 != 0x94119c00 {
+            return Err(SignatureDecodeError::BadMagic {
+                expected: 0x94119c00,
+                actual: header.magic2,
+            });
+        }
 
         let sample_rate_hz: u32 = match header.shifted_sample_rate_id >> 27 {
             1 => 8000,
@@ -78,83 +228,109 @@ impl DecodedSignature {
             4 => 32000,
             5 => 44100,
             6 => 48000,
-            _ => {
-                panic!(
-                    "{}",
-                    gettext("Invalid sample rate in decoded Shazam packet")
-                );
+            sample_rate_id => {
+                return Err(SignatureDecodeError::UnknownSampleRate(sample_rate_id));
             }
         };
 
-        let number_samples: u32 =
-            header.number_samples_plus_divided_sample_rate - (sample_rate_hz as f32 * 0.24) as u32;
+        let number_samples: u32 = header
+            .number_samples_plus_divided_sample_rate
+            .saturating_sub((sample_rate_hz as f32 * 0.24) as u32);
 
         // Read the type-length-value sequence that follows the header
 
         // The first chunk is fixed and has no value, but instead just repeats
         // the length of the message size minus the header:
 
-        assert_eq!(cursor.read_u32::<LittleEndian>()?, 0x40000000);
-        assert_eq!(cursor.read_u32::<LittleEndian>()? as usize, data.len() - 48);
+        let first_chunk_type = cursor.read_u32::<LittleEndian>().unwrap();
+        if first_chunk_type != 0x40000000 {
+            return Err(SignatureDecodeError::BadMagic {
+                expected: 0x40000000,
+                actual: first_chunk_type,
+            });
+        }
+
+        let first_chunk_size = cursor.read_u32::<LittleEndian>().unwrap() as usize;
+        if first_chunk_size != data.len() - 48 {
+            return Err(SignatureDecodeError::SizeMismatch {
+                expected: first_chunk_size,
+                actual: data.len() - 48,
+            });
+        }
 
         // Then, lists of frequency peaks for respective bands follow
 
         let mut frequency_band_to_sound_peaks: [Vec<FrequencyPeak>; 4] = Default::default();
 
         while cursor.position() < data.len() as u64 {
-            let frequency_band_id = cursor.read_u32::<LittleEndian>()?;
-            let frequency_peaks_size = cursor.read_u32::<LittleEndian>()?;
+            let frequency_band_id = cursor
+                .read_u32::<LittleEndian>()
+                .map_err(|_| SignatureDecodeError::TruncatedTlv)?;
+            let frequency_peaks_size = cursor
+                .read_u32::<LittleEndian>()
+                .map_err(|_| SignatureDecodeError::TruncatedTlv)?;
 
             let frequency_peaks_padding = (4 - frequency_peaks_size % 4) % 4;
 
-            let mut frequency_peaks_cursor = Cursor::new(
-                &data[cursor.position() as usize
-                    ..(cursor.position() as u32 + frequency_peaks_size) as usize],
-            );
+            let frequency_peaks_start = cursor.position() as usize;
+            let frequency_peaks_data = frequency_peaks_start
+                .checked_add(frequency_peaks_size as usize)
+                .and_then(|frequency_peaks_end| {
+                    data.get(frequency_peaks_start..frequency_peaks_end)
+                })
+                .ok_or(SignatureDecodeError::TruncatedTlv)?;
+
+            let mut frequency_peaks_cursor = Cursor::new(frequency_peaks_data);
 
             // Decode frequency peaks
 
-            let frequency_band = match frequency_band_id - 0x60030040 {
+            let frequency_band = match frequency_band_id.wrapping_sub(0x60030040) {
                 0 => FrequencyBand::_250_520,
                 1 => FrequencyBand::_520_1450,
                 2 => FrequencyBand::_1450_3500,
                 3 => FrequencyBand::_3500_5500,
                 _ => {
-                    panic!(
-                        "{}",
-                        gettext("Invalid frequency band in decoded Shazam packet")
-                    );
+                    return Err(SignatureDecodeError::UnknownFrequencyBand(
+                        frequency_band_id,
+                    ));
                 }
             };
 
             let mut fft_pass_number: u32 = 0;
 
-            while frequency_peaks_cursor.position() < frequency_peaks_size as u64 {
-                let fft_pass_offset = frequency_peaks_cursor.read_u8()?;
+            let mut decode_peaks = || -> std::io::Result<()> {
+                while frequency_peaks_cursor.position() < frequency_peaks_size as u64 {
+                    let fft_pass_offset = frequency_peaks_cursor.read_u8()?;
+
+                    match fft_pass_offset {
+                        0xff => {
+                            fft_pass_number = frequency_peaks_cursor.read_u32::<LittleEndian>()?;
+                        }
+                        _ => {
+                            fft_pass_number = fft_pass_number.wrapping_add(fft_pass_offset as u32);
+
+                            frequency_band_to_sound_peaks[frequency_band as usize].push(
+                                FrequencyPeak {
+                                    fft_pass_number,
+                                    peak_magnitude: frequency_peaks_cursor
+                                        .read_u16::<LittleEndian>()?,
+                                    corrected_peak_frequency_bin: frequency_peaks_cursor
+                                        .read_u16::<LittleEndian>()?,
+                                },
+                            );
+                        }
+                    };
+                }
+                Ok(())
+            };
 
-                match fft_pass_offset {
-                    0xff => {
-                        fft_pass_number = frequency_peaks_cursor.read_u32::<LittleEndian>()?;
-                    }
-                    _ => {
-                        fft_pass_number += fft_pass_offset as u32;
-
-                        frequency_band_to_sound_peaks[frequency_band as usize].push(
-                            FrequencyPeak {
-                                fft_pass_number,
-                                peak_magnitude: frequency_peaks_cursor
-                                    .read_u16::<LittleEndian>()?,
-                                corrected_peak_frequency_bin: frequency_peaks_cursor
-                                    .read_u16::<LittleEndian>()?,
-                            },
-                        );
-                    }
-                };
-            }
+            decode_peaks().map_err(|_| SignatureDecodeError::TruncatedTlv)?;
 
-            cursor.seek(SeekFrom::Current(
-                (frequency_peaks_size + frequency_peaks_padding) as i64,
-            ))?;
+            cursor
+                .seek(SeekFrom::Current(
+                    (frequency_peaks_size as u64 + frequency_peaks_padding as u64) as i64,
+                ))
+                .map_err(|_| SignatureDecodeError::TruncatedTlv)?;
         }
 
         // Return the decoded object
@@ -166,15 +342,19 @@ impl DecodedSignature {
         })
     }
 
-    pub fn decode_from_uri(uri: &str) -> Result<Self, Box<dyn Error>> {
-        assert!(uri.starts_with(DATA_URI_PREFIX));
+    pub fn decode_from_uri(uri: &str) -> Result<Self, SignatureDecodeError> {
+        let encoded_data = uri
+            .strip_prefix(DATA_URI_PREFIX)
+            .ok_or(SignatureDecodeError::BadDataUriPrefix)?;
 
         DecodedSignature::decode_from_binary(
-            &base64::prelude::BASE64_STANDARD.decode(&uri[DATA_URI_PREFIX.len()..])?,
+            &base64::prelude::BASE64_STANDARD
+                .decode(encoded_data)
+                .map_err(SignatureDecodeError::Base64)?,
         )
     }
 
-    pub fn encode_to_binary(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+    pub fn encode_to_binary(&self) -> Result<Vec<u8>, SignatureEncodeError> {
         let mut cursor = Cursor::new(vec![]);
 
         // Please see the RawSignatureHeader structure definition above for
@@ -196,10 +376,9 @@ impl DecodedSignature {
                 44100 => 5,
                 48000 => 6,
                 _ => {
-                    panic!(
-                        "{}",
-                        gettext("Invalid sample rate passed when encoding Shazam packet")
-                    );
+                    return Err(SignatureEncodeError::UnsupportedSampleRate(
+                        self.sample_rate_hz,
+                    ));
                 }
             } << 27,
         )?; // shifted_sample_rate_id
@@ -224,7 +403,9 @@ impl DecodedSignature {
             let mut fft_pass_number = 0;
 
             for frequency_peak in frequency_peaks {
-                assert!(frequency_peak.fft_pass_number >= fft_pass_number);
+                if frequency_peak.fft_pass_number < fft_pass_number {
+                    return Err(SignatureEncodeError::UnorderedPeaks);
+                }
 
                 if frequency_peak.fft_pass_number - fft_pass_number >= 255 {
                     peaks_cursor.write_u8(0xff)?;
@@ -268,7 +449,7 @@ impl DecodedSignature {
         Ok(cursor.into_inner())
     }
 
-    pub fn encode_to_uri(&self) -> Result<String, Box<dyn Error>> {
+    pub fn encode_to_uri(&self) -> Result<String, SignatureEncodeError> {
         Ok(format!(
             "{}{}",
             DATA_URI_PREFIX,
@@ -276,3 +457,147 @@ impl DecodedSignature {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{RngExt, SeedableRng};
+
+    const SAMPLE_RATES: [u32; 6] = [8000, 11025, 16000, 32000, 44100, 48000];
+
+    fn random_signature(rng: &mut StdRng) -> DecodedSignature {
+        let mut frequency_band_to_sound_peaks: [Vec<FrequencyPeak>; 4] = Default::default();
+
+        for band_peaks in frequency_band_to_sound_peaks.iter_mut() {
+            let mut fft_pass_number: u32 = rng.random_range(0..1000);
+
+            for _ in 0..rng.random_range(0..200) {
+                // Exercise both the one-byte offsets and the 0xff escape
+                fft_pass_number += if rng.random_bool(0.05) {
+                    rng.random_range(255..100_000)
+                } else {
+                    rng.random_range(0..255)
+                };
+
+                band_peaks.push(FrequencyPeak {
+                    fft_pass_number,
+                    peak_magnitude: rng.random(),
+                    corrected_peak_frequency_bin: rng.random(),
+                });
+            }
+        }
+
+        DecodedSignature {
+            sample_rate_hz: SAMPLE_RATES[rng.random_range(0..SAMPLE_RATES.len())],
+            number_samples: rng.random_range(0..100_000_000),
+            frequency_band_to_sound_peaks,
+        }
+    }
+
+    fn fix_crc32(data: &mut [u8]) {
+        let mut hasher = Hasher::new();
+        hasher.update(&data[8..]);
+        data[4..8].copy_from_slice(&hasher.finalize().to_le_bytes());
+    }
+
+    #[test]
+    fn test_round_trip_random_signatures() {
+        let mut rng = StdRng::seed_from_u64(0x5309);
+
+        for _ in 0..500 {
+            let signature = random_signature(&mut rng);
+
+            let binary = signature.encode_to_binary().unwrap();
+            assert_eq!(
+                DecodedSignature::decode_from_binary(&binary).unwrap(),
+                signature
+            );
+
+            let uri = signature.encode_to_uri().unwrap();
+            assert_eq!(DecodedSignature::decode_from_uri(&uri).unwrap(), signature);
+        }
+    }
+
+    #[test]
+    fn test_decode_corrupted_signatures_does_not_panic() {
+        let mut rng = StdRng::seed_from_u64(0x2580);
+
+        for _ in 0..2000 {
+            let mut binary = random_signature(&mut rng).encode_to_binary().unwrap();
+
+            match rng.random_range(0..3) {
+                0 => {
+                    let index = rng.random_range(0..binary.len());
+                    binary[index] = rng.random();
+                }
+                1 => {
+                    binary.truncate(rng.random_range(0..binary.len()));
+                }
+                _ => {
+                    // Keep the checksum valid so that the TLV parsing
+                    // gets exercised with garbage
+                    for _ in 0..rng.random_range(1..8) {
+                        let index = rng.random_range(48..binary.len());
+                        binary[index] = rng.random();
+                    }
+                    fix_crc32(&mut binary);
+                }
+            }
+
+            let _ = DecodedSignature::decode_from_binary(&binary);
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut rng = StdRng::seed_from_u64(0x9411);
+        let binary = random_signature(&mut rng).encode_to_binary().unwrap();
+
+        let mut bad_magic = binary.clone();
+        bad_magic[0] ^= 0xff;
+        assert!(matches!(
+            DecodedSignature::decode_from_binary(&bad_magic),
+            Err(SignatureDecodeError::BadMagic { .. })
+        ));
+
+        let mut bad_crc = binary.clone();
+        *bad_crc.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(
+            DecodedSignature::decode_from_binary(&bad_crc),
+            Err(SignatureDecodeError::CrcMismatch { .. })
+        ));
+
+        let mut bad_sample_rate = binary.clone();
+        bad_sample_rate[28..32].copy_from_slice(&(7u32 << 27).to_le_bytes());
+        fix_crc32(&mut bad_sample_rate);
+        assert!(matches!(
+            DecodedSignature::decode_from_binary(&bad_sample_rate),
+            Err(SignatureDecodeError::UnknownSampleRate(7))
+        ));
+
+        assert!(matches!(
+            DecodedSignature::decode_from_binary(&binary[..20]),
+            Err(SignatureDecodeError::TruncatedHeader)
+        ));
+
+        assert!(matches!(
+            DecodedSignature::decode_from_uri("data:audio/mpeg;base64,AAAA"),
+            Err(SignatureDecodeError::BadDataUriPrefix)
+        ));
+        assert!(matches!(
+            DecodedSignature::decode_from_uri(&format!("{}!!!", DATA_URI_PREFIX)),
+            Err(SignatureDecodeError::Base64(_))
+        ));
+
+        let unsupported = DecodedSignature {
+            sample_rate_hz: 22050,
+            number_samples: 0,
+            frequency_band_to_sound_peaks: Default::default(),
+        };
+        assert!(matches!(
+            unsupported.encode_to_binary(),
+            Err(SignatureEncodeError::UnsupportedSampleRate(22050))
+        ));
+    }
+}
@@ -1,133 +1,133 @@
 use gettextrs::gettext;
-use serde_json::Value;
-use soup::prelude::SessionExt;
-use std::error::Error;
+use log::error;
+use std::sync::{Arc, Mutex};
 
 use crate::core::thread_messages::*;
 
-use crate::core::fingerprinting::communication::{
-    obtain_raw_cover_image, recognize_song_from_signature,
+use crate::core::capture_archive::save_capture;
+use crate::core::preferences::PreferencesInterface;
+use crate::core::recognition_backends::backend::{
+    RecognitionError, get_backend, get_backend_key, recognize_by_vote,
 };
-use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::request_scheduler::RecognitionOutcome;
+use crate::core::song_change::SongChangeDetector;
 
-async fn try_recognize_song(
-    session: &soup::Session,
-    signature: DecodedSignature,
-) -> Result<SongRecognizedMessage, Box<dyn Error>> {
-    let json_object = recognize_song_from_signature(session, &signature).await?;
+pub async fn http_task(
+    http_rx: async_channel::Receiver<HTTPMessage>,
+    gui_tx: async_channel::Sender<GUIMessage>,
+    microphone_tx: async_channel::Sender<MicrophoneMessage>,
+    preferences_interface: Arc<Mutex<PreferencesInterface>>,
+) {
+    let mut backend_key = get_backend_key(&preferences_interface.lock().unwrap().preferences);
+    let mut backend = get_backend(&preferences_interface.lock().unwrap().preferences);
 
-    let mut album_name: Option<String> = None;
-    let mut release_year: Option<String> = None;
+    let mut song_change_detector = SongChangeDetector::default();
 
-    // Sometimes the idea of trying to write functional poetry hurts
+    while let Ok(message) = http_rx.recv().await {
+        // XX USE SOUP3 CF. https://github.com/marin-m/SongRec/issues/223
+        let (signatures, from_microphone, archived_samples) = match message {
+            HTTPMessage::RecognizeSignatures(signatures) => (signatures, false, None),
+            HTTPMessage::RecognizeMicrophoneSignatures {
+                signatures,
+                latest_signature,
+                archived_samples,
+            } => {
+                // Don't query the recognition backend again while the last
+                // recognized song keeps playing
 
-    if let Value::Array(sections) = &json_object["track"]["sections"] {
-        for section in sections {
-            if let Value::String(string) = &section["type"]
-                && string == "SONG"
-            {
-                if let Value::Array(metadata) = &section["metadata"] {
-                    for metadatum in metadata {
-                        if let Value::String(title) = &metadatum["title"] {
-                            if title == "Album"
-                                && let Value::String(text) = &metadatum["text"]
-                            {
-                                album_name = Some(text.to_string());
-                            } else if title == "Released"
-                                && let Value::String(text) = &metadatum["text"]
-                            {
-                                release_year = Some(text.to_string());
-                            }
-                        }
-                    }
-                    break;
+                if song_change_detector.is_same_song(
+                    &latest_signature,
+                    &preferences_interface.lock().unwrap().preferences,
+                ) {
+                    microphone_tx
+                        .try_send(MicrophoneMessage::ProcessingDone(
+                            RecognitionOutcome::SameSong,
+                        ))
+                        .unwrap();
+                    continue;
                 }
+                (signatures, true, archived_samples)
             }
-        }
-    }
+        };
 
-    Ok(SongRecognizedMessage {
-        artist_name: match &json_object["track"]["subtitle"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(Box::new(std::io::Error::other(
-                    gettext("No match for this song").as_str(),
-                )));
-            }
-        },
-        album_name,
-        song_name: match &json_object["track"]["title"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(Box::new(std::io::Error::other(
-                    gettext("No match for this song").as_str(),
-                )));
+        // Pick up changes of the recognition backend preferences
+
+        {
+            let preferences = &preferences_interface.lock().unwrap().preferences;
+            if get_backend_key(preferences) != backend_key {
+                backend_key = get_backend_key(preferences);
+                backend = get_backend(preferences);
             }
-        },
-        cover_image: match &json_object["track"]["images"]["coverart"] {
-            Value::String(string) => Some(obtain_raw_cover_image(session, string).await?),
-            _ => None,
-        },
-        track_key: match &json_object["track"]["key"] {
-            Value::String(string) => string.to_string(),
-            _ => {
-                return Err(Box::new(std::io::Error::other(
-                    gettext("No match for this song").as_str(),
-                )));
+        }
+
+        // Only keep the audio of the requests actually made, so that the
+        // oldest captures are not removed for nothing
+
+        let capture_path = archived_samples.and_then(|archived_samples| {
+            let preferences = preferences_interface.lock().unwrap().preferences.clone();
+            match save_capture(archived_samples, &preferences) {
+                Ok(capture_path) => Some(capture_path),
+                Err(error) => {
+                    error!("{} {}", gettext("When saving the captured audio:"), error);
+                    None
+                }
             }
-        },
-        release_year,
-        genre: match &json_object["track"]["genres"]["primary"] {
-            Value::String(string) => Some(string.to_string()),
-            _ => None,
-        },
-        shazam_json: serde_json::to_string(&json_object).unwrap(),
-    })
-}
+        });
 
-pub async fn http_task(
-    http_rx: async_channel::Receiver<HTTPMessage>,
-    gui_tx: async_channel::Sender<GUIMessage>,
-    microphone_tx: async_channel::Sender<MicrophoneMessage>,
-) {
-    let session = soup::Session::new();
-    session.set_timeout(20);
-    session.set_idle_timeout(2);
+        let outcome = match recognize_by_vote(backend.as_ref(), &signatures).await {
+            Ok(mut recognized_song) => {
+                recognized_song.capture_path = capture_path;
 
-    while let Ok(message) = http_rx.recv().await {
-        // XX USE SOUP3 CF. https://github.com/marin-m/SongRec/issues/223
-        match message {
-            HTTPMessage::RecognizeSignature(signature) => {
-                match try_recognize_song(&session, *signature).await {
-                    Ok(recognized_song) => {
-                        gui_tx
-                            .try_send(GUIMessage::SongRecognized(Box::new(recognized_song)))
-                            .unwrap();
-                        gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
-                        gui_tx.try_send(GUIMessage::RateLimitState(false)).unwrap();
-                    }
-                    Err(error) => match error.to_string().as_str() {
-                        a if a == gettext("No match for this song") => {
-                            gui_tx
-                                .try_send(GUIMessage::ErrorMessage(error.to_string()))
-                                .unwrap();
-                            gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
-                            gui_tx.try_send(GUIMessage::RateLimitState(false)).unwrap();
-                        }
-                        a if a == gettext("Your IP has been rate-limited") => {
-                            gui_tx.try_send(GUIMessage::RateLimitState(true)).unwrap();
-                        }
-                        _ => {
-                            log::error!("Network reach error: {:?}", error);
-                            gui_tx.try_send(GUIMessage::NetworkStatus(false)).unwrap();
-                        }
-                    },
+                let outcome = RecognitionOutcome::Match {
+                    track_key: recognized_song.track_key.clone(),
+                    match_offset_secs: recognized_song.match_offset_secs,
                 };
-
-                microphone_tx
-                    .try_send(MicrophoneMessage::ProcessingDone)
+                gui_tx
+                    .try_send(GUIMessage::SongRecognized(Box::new(recognized_song)))
+                    .unwrap();
+                gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
+                gui_tx.try_send(GUIMessage::RateLimitState(false)).unwrap();
+                outcome
+            }
+            Err(RecognitionError::NoMatch) => {
+                gui_tx
+                    .try_send(GUIMessage::ErrorMessage(
+                        RecognitionError::NoMatch.to_string(),
+                    ))
                     .unwrap();
+                gui_tx.try_send(GUIMessage::NetworkStatus(true)).unwrap();
+                gui_tx.try_send(GUIMessage::RateLimitState(false)).unwrap();
+                RecognitionOutcome::NoMatch
             }
+            Err(RecognitionError::RateLimited) => {
+                gui_tx.try_send(GUIMessage::RateLimitState(true)).unwrap();
+                RecognitionOutcome::RateLimited
+            }
+            Err(RecognitionError::Network(error)) => {
+                error!("Network reach error: {:?}", error);
+                gui_tx.try_send(GUIMessage::NetworkStatus(false)).unwrap();
+                RecognitionOutcome::Failed
+            }
+            Err(RecognitionError::Backend(error)) => {
+                gui_tx
+                    .try_send(GUIMessage::ErrorMessage(error.to_string()))
+                    .unwrap();
+                RecognitionOutcome::Failed
+            }
+        };
+
+        if from_microphone {
+            match outcome {
+                RecognitionOutcome::Match { .. } => {
+                    song_change_detector.set_reference(&signatures[0])
+                }
+                RecognitionOutcome::NoMatch => song_change_detector.clear_reference(),
+                _ => {}
+            }
+
+            microphone_tx
+                .try_send(MicrophoneMessage::ProcessingDone(outcome))
+                .unwrap();
         }
     }
 }
@@ -1,10 +1,17 @@
+use std::collections::VecDeque;
 use std::iter::Copied;
 use std::num::NonZero;
 use std::slice::Iter;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::core::audio_activity::{
+    ANALYSIS_SECS, ActivityThresholds, AudioActivity, SpectrumAnalyzer, audio_content_changed,
+    classify_audio,
+};
+use crate::core::capture_window::CaptureSettings;
 use crate::core::preferences::PreferencesInterface;
+use crate::core::request_scheduler::RequestScheduler;
 use crate::core::thread_messages::{MicrophoneMessage::*, *};
 
 use cpal::platform::Device;
@@ -16,7 +23,7 @@ use rodio::nz;
 
 use crate::core::audio_controllers::audio_backend::get_any_backend;
 
-const BUFFER_SIZE_SECS: usize = 12;
+pub const BUFFER_SIZE_SECS: usize = 12;
 
 struct ProcessingState<'a> {
     input_samples: Vec<f32>,
@@ -25,9 +32,14 @@ struct ProcessingState<'a> {
     channels: u16,
     sample_rate: u32,
     twelve_seconds_buffer: &'a mut [f32; 16000 * BUFFER_SIZE_SECS],
+    archive_buffer: &'a mut VecDeque<f32>, // Only filled while the capture archive is enabled
     number_unprocessed_samples: &'a mut usize,
     number_unmeasured_samples: &'a mut usize,
+    number_unclassified_samples: &'a mut usize,
+    audio_activity: &'a mut Option<AudioActivity>,
+    spectrum_analyzer: &'a mut SpectrumAnalyzer,
     processing_already_ongoing: &'a AtomicBool,
+    request_scheduler: &'a Mutex<RequestScheduler>,
     preferences_interface: &'a Arc<Mutex<PreferencesInterface>>,
 }
 
@@ -123,6 +135,19 @@ pub fn microphone_thread(
 
         let processing_already_ongoing: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
 
+        // When to make the next recognition request, depending on the
+        // results of the previous ones (shared with the CPAL thread too)
+
+        let request_scheduler: Arc<Mutex<RequestScheduler>> =
+            Arc::new(Mutex::new(RequestScheduler::new(
+                preferences_interface
+                    .lock()
+                    .unwrap()
+                    .preferences
+                    .request_interval_secs_v3
+                    .unwrap(),
+            )));
+
         // Send a list of the active microphone-alike devices to the GUI thread
         // (the combo box will be filed with device names when a "DevicesList"
         // inter-thread message will be received at the initialization of the
@@ -169,6 +194,10 @@ pub fn microphone_thread(
 
                     let device: Device = backend.set_device(&host, &device_name);
 
+                    processing_tx
+                        .try_send(ProcessingMessage::ResetMicrophoneSamples)
+                        .unwrap();
+
                     let config = match device.default_input_config() {
                         Ok(res) => res,
                         Err(err) => {
@@ -185,10 +214,15 @@ pub fn microphone_thread(
                     let sample_rate = config.sample_rate();
 
                     let mut twelve_seconds_buffer = Box::new([0.0f32; 16000 * BUFFER_SIZE_SECS]);
+                    let mut archive_buffer: VecDeque<f32> = VecDeque::new(); // The audio of all the capture windows, see capture_archive.rs
                     let mut number_unprocessed_samples: usize = 0; // Sample count for the interval of doing Shazam recognition (every 4 seconds)
                     let mut number_unmeasured_samples: usize = 0; // Sample count for doing volume measurement (every 24th of second)
+                    let mut number_unclassified_samples: usize = 0; // Sample count for classifying the audio as silence, speech or music (every second)
+                    let mut audio_activity: Option<AudioActivity> = None;
+                    let mut spectrum_analyzer = SpectrumAnalyzer::new();
 
                     let processing_already_ongoing_2 = processing_already_ongoing.clone();
+                    let request_scheduler_2 = request_scheduler.clone();
 
                     let preferences_interface = preferences_interface.clone();
                     macro_rules! build_input_streams {
@@ -208,9 +242,14 @@ pub fn microphone_thread(
                                             channels,
                                             sample_rate,
                                             twelve_seconds_buffer: &mut twelve_seconds_buffer,
+                                            archive_buffer: &mut archive_buffer,
                                             number_unprocessed_samples: &mut number_unprocessed_samples,
                                             number_unmeasured_samples: &mut number_unmeasured_samples,
+                                            number_unclassified_samples: &mut number_unclassified_samples,
+                                            audio_activity: &mut audio_activity,
+                                            spectrum_analyzer: &mut spectrum_analyzer,
                                             processing_already_ongoing: &processing_already_ongoing_2,
+                                            request_scheduler: &request_scheduler_2,
                                             preferences_interface: &preferences_interface,
                                         })
                                     },
@@ -254,9 +293,14 @@ pub fn microphone_thread(
                                                 channels,
                                                 sample_rate,
                                                 twelve_seconds_buffer: &mut twelve_seconds_buffer,
+                                                archive_buffer: &mut archive_buffer,
                                                 number_unprocessed_samples: &mut number_unprocessed_samples,
                                                 number_unmeasured_samples: &mut number_unmeasured_samples,
+                                                number_unclassified_samples: &mut number_unclassified_samples,
+                                                audio_activity: &mut audio_activity,
+                                                spectrum_analyzer: &mut spectrum_analyzer,
                                                 processing_already_ongoing: &processing_already_ongoing_2,
+                                                request_scheduler: &request_scheduler_2,
                                                 preferences_interface: &preferences_interface,
                                             })
                                         },
@@ -336,7 +380,9 @@ pub fn microphone_thread(
                     stream = None;
                 }
 
-                ProcessingDone => {
+                ProcessingDone(outcome) => {
+                    debug!("Recognition outcome: {:?}", outcome);
+                    request_scheduler.lock().unwrap().on_outcome(outcome);
                     processing_already_ongoing.store(false, Ordering::SeqCst);
                 }
             }
@@ -347,7 +393,8 @@ pub fn microphone_thread(
 
 fn write_data(state: ProcessingState) {
     // Reassemble data into a 12-second buffer, and do recognition
-    // every 4 seconds if the queue to "processing_tx" is empty
+    // at the interval decided by the request scheduler if the queue to
+    // "processing_tx" is empty
 
     let input_buffer = rodio::buffer::SamplesBuffer::new(
         NonZero::new(state.channels).unwrap(),
@@ -360,12 +407,24 @@ fn write_data(state: ProcessingState) {
 
     let raw_pcm_samples: Vec<f32> = converted_file.collect();
 
-    let request_interval_secs;
+    let base_interval_secs;
+    let skip_silence_and_speech;
+    let activity_thresholds;
+    let archived_secs; // The length of audio to archive, when enabled
     {
         let preferences = &state.preferences_interface.lock().unwrap().preferences;
-        request_interval_secs = preferences.request_interval_secs_v3.unwrap() as usize;
+        base_interval_secs = preferences.request_interval_secs_v3.unwrap();
+        skip_silence_and_speech = preferences.skip_silence_and_speech == Some(true);
+        activity_thresholds = ActivityThresholds::from_preferences(preferences);
+        archived_secs = match preferences.capture_archive {
+            Some(true) => Some(CaptureSettings::from_preferences(preferences).span_secs()),
+            _ => None,
+        };
     }
 
+    let mut request_scheduler = state.request_scheduler.lock().unwrap();
+    request_scheduler.set_base_interval(base_interval_secs);
+
     let twelve_seconds_buffer = &mut state.twelve_seconds_buffer[..16000 * BUFFER_SIZE_SECS];
 
     // Update our buffer with data from CPAL
@@ -382,17 +441,87 @@ fn write_data(state: ProcessingState) {
             .copy_from_slice(&raw_pcm_samples);
     }
 
+    // Keep the audio spanned by the capture windows while it is archived
+
+    match archived_secs {
+        Some(archived_secs) => {
+            state.archive_buffer.extend(raw_pcm_samples.iter());
+            let number_expired_samples = state
+                .archive_buffer
+                .len()
+                .saturating_sub(16000 * archived_secs);
+            state.archive_buffer.drain(..number_expired_samples);
+        }
+        None => *state.archive_buffer = VecDeque::new(),
+    }
+
+    // Have the new samples fingerprinted incrementally by the processing thread
+
+    state
+        .processing_tx
+        .try_send(ProcessingMessage::ProcessMicrophoneSamples(
+            raw_pcm_samples.clone(),
+        ))
+        .unwrap();
+
+    // Tell apart silence, speech and music every second, over the last
+    // seconds of audio, and notice when the audio changes
+
+    *state.number_unclassified_samples += raw_pcm_samples.len();
+
+    if *state.number_unclassified_samples >= 16000 {
+        let audio_activity = classify_audio(
+            state.spectrum_analyzer,
+            &twelve_seconds_buffer[16000 * (BUFFER_SIZE_SECS - ANALYSIS_SECS)..],
+            &activity_thresholds,
+        );
+
+        if *state.audio_activity != Some(audio_activity) {
+            debug!("Audio activity: {:?}", audio_activity);
+            state
+                .gui_tx
+                .try_send(GUIMessage::MicrophoneAudioActivity(audio_activity))
+                .unwrap();
+            *state.audio_activity = Some(audio_activity);
+        }
+
+        if audio_content_changed(
+            state.spectrum_analyzer,
+            &twelve_seconds_buffer
+                [16000 * (BUFFER_SIZE_SECS - ANALYSIS_SECS)..16000 * (BUFFER_SIZE_SECS - 1)],
+            &twelve_seconds_buffer[16000 * (BUFFER_SIZE_SECS - 1)..],
+        ) {
+            request_scheduler.on_content_change();
+        }
+
+        state
+            .gui_tx
+            .try_send(GUIMessage::RequestSchedule(
+                request_scheduler.status((*state.number_unprocessed_samples / 16000) as u64),
+            ))
+            .unwrap();
+
+        *state.number_unclassified_samples = 0;
+    }
+
     *state.number_unprocessed_samples += raw_pcm_samples.len();
 
-    if *state.number_unprocessed_samples >= 16000 * request_interval_secs
+    if *state.number_unprocessed_samples >= 16000 * request_scheduler.interval_secs() as usize
         && !state.processing_already_ongoing.load(Ordering::SeqCst)
     {
-        if !twelve_seconds_buffer.iter().all(|x| *x == 0.0) {
+        let skipped = skip_silence_and_speech
+            && matches!(
+                *state.audio_activity,
+                Some(AudioActivity::Silence) | Some(AudioActivity::Speech)
+            );
+
+        if !skipped && !twelve_seconds_buffer.iter().all(|x| *x == 0.0) {
             state
                 .processing_tx
-                .try_send(ProcessingMessage::ProcessAudioSamples(
-                    twelve_seconds_buffer.to_vec(),
-                ))
+                .try_send(ProcessingMessage::RecognizeMicrophoneSamples {
+                    archived_samples: archived_secs
+                        .map(|_| state.archive_buffer.iter().copied().collect()),
+                })
                 .unwrap();
 
             state
@@ -0,0 +1,295 @@
+//! A small HTTP server exposing the recognized songs to streaming overlays
+//! and dashboards. It serves:
+//!
+//! - "/": a now playing page, which reloads itself every few seconds
+//! - "/now-playing.json": the last recognized song, or null
+//! - "/cover": the cover art of the last recognized song
+//! - "/history.json?limit=20": the last entries of the song history
+//! - "/events": a Server-Sent Events stream, with a "song_recognized" event
+//!   for each new song
+//!
+//! It runs on the main loop, like the HTTP task.
+
+use chrono::{Local, SecondsFormat};
+use gettextrs::gettext;
+use log::{debug, error, info};
+use serde_json::Value;
+use soup::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+use crate::core::thread_messages::SongRecognizedMessage;
+use crate::utils::filesystem_operations::obtain_history_database_path;
+use crate::utils::history_database::{HistoryDatabase, HistoryQuery};
+
+const PAGE_REFRESH_SECS: u32 = 5;
+const DEFAULT_HISTORY_LIMIT: u64 = 20;
+const MAX_HISTORY_LIMIT: u64 = 1000;
+
+struct CurrentSong {
+    song: Value,
+    cover_image: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    current_song: Option<CurrentSong>,
+    event_streams: Vec<soup::ServerMessage>,
+}
+
+pub struct NowPlayingServer {
+    _server: soup::Server,
+    state: Rc<RefCell<ServerState>>,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn cover_content_type(cover_image: &[u8]) -> &'static str {
+    match cover_image {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        _ => "image/jpeg",
+    }
+}
+
+/// The page showing the last recognized song, as returned by
+/// "/now-playing.json".
+pub fn render_page(song: Option<&Value>) -> String {
+    let contents = match song {
+        Some(song) => {
+            let field = |name: &str| escape_html(song[name].as_str().unwrap_or_default());
+            let cover = match song["cover_url"].as_str() {
+                Some(cover_url) => format!(r#"<img src="{}" alt="">"#, escape_html(cover_url)),
+                None => String::new(),
+            };
+            format!(
+                r#"{}<div><div class="song">{}</div><div class="artist">{}</div><div class="album">{}</div></div>"#,
+                cover,
+                field("song_name"),
+                field("artist_name"),
+                field("album_name")
+            )
+        }
+        None => format!(
+            r#"<div class="artist">{}</div>"#,
+            escape_html(&gettext("No song recognized yet"))
+        ),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{}">
+<title>SongRec</title>
+<style>
+body {{ display: flex; align-items: center; gap: 1em; margin: 1em; font-family: sans-serif; color: white; text-shadow: 0 0 4px black; background: transparent; }}
+img {{ width: 8em; height: 8em; border-radius: 0.5em; object-fit: cover; }}
+.song {{ font-size: 2em; font-weight: bold; }}
+.artist {{ font-size: 1.5em; }}
+</style>
+</head>
+<body>{}</body>
+</html>
+"#,
+        PAGE_REFRESH_SECS, contents
+    )
+}
+
+fn song_json(message: &SongRecognizedMessage) -> Value {
+    let mut song = message.to_json();
+    song["recognized_at"] = Local::now()
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+        .into();
+    song["cover_url"] = match message.cover_image {
+        // Changes with the song, so that browsers don't show a cached cover
+        Some(_) => format!(
+            "/cover?track_key={}",
+            glib::Uri::escape_string(&message.track_key, None, false)
+        )
+        .into(),
+        None => Value::Null,
+    };
+    song
+}
+
+fn recent_history(query: &HashMap<&str, &str>) -> Result<Value, Box<dyn Error>> {
+    let limit = query
+        .get("limit")
+        .and_then(|limit| limit.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .min(MAX_HISTORY_LIMIT);
+
+    let database = HistoryDatabase::open(&obtain_history_database_path()?)?;
+    let rows = database.query(&HistoryQuery {
+        descending: true,
+        limit: Some(limit),
+        ..Default::default()
+    })?;
+    Ok(serde_json::to_value(rows)?)
+}
+
+/// Let the pages of other origins, such as dashboards, fetch the current
+/// song. The song history is kept out of their reach.
+fn allow_cross_origin(message: &soup::ServerMessage) {
+    message
+        .response_headers()
+        .unwrap()
+        .append("Access-Control-Allow-Origin", "*");
+}
+
+fn respond(message: &soup::ServerMessage, content_type: &str, body: &[u8]) {
+    message.set_response(Some(content_type), soup::MemoryUse::Copy, body);
+    message.set_status(200, None);
+}
+
+fn handle_request(
+    state: &Rc<RefCell<ServerState>>,
+    message: &soup::ServerMessage,
+    path: &str,
+    query: &HashMap<&str, &str>,
+) {
+    if !matches!(message.method().as_deref(), Some("GET") | Some("HEAD")) {
+        message.set_status(405, None);
+        return;
+    }
+    debug!("Now playing server request: {}", path);
+
+    match path {
+        "/" => {
+            let page = render_page(
+                state
+                    .borrow()
+                    .current_song
+                    .as_ref()
+                    .map(|current_song| &current_song.song),
+            );
+            respond(message, "text/html; charset=utf-8", page.as_bytes());
+        }
+        "/now-playing.json" => {
+            let song = match state.borrow().current_song {
+                Some(ref current_song) => current_song.song.clone(),
+                None => Value::Null,
+            };
+            allow_cross_origin(message);
+            respond(message, "application/json", song.to_string().as_bytes());
+        }
+        "/cover" => match state.borrow().current_song {
+            Some(CurrentSong {
+                cover_image: Some(ref cover_image),
+                ..
+            }) => {
+                allow_cross_origin(message);
+                respond(message, cover_content_type(cover_image), cover_image)
+            }
+            _ => message.set_status(404, None),
+        },
+        "/history.json" => match recent_history(query) {
+            Ok(history) => respond(message, "application/json", history.to_string().as_bytes()),
+            Err(error) => {
+                error!("Could not read the song history: {}", error);
+                message.set_status(500, None);
+            }
+        },
+        "/events" => {
+            // Keep the response open, the events being appended as chunks
+            let headers = message.response_headers().unwrap();
+            headers.set_encoding(soup::Encoding::Chunked);
+            headers.set_content_type(Some("text/event-stream"), None);
+            headers.append("Cache-Control", "no-cache");
+            allow_cross_origin(message);
+            message.set_status(200, None);
+
+            let stream_state = Rc::downgrade(state);
+            message.connect_finished(move |message| {
+                if let Some(state) = stream_state.upgrade() {
+                    state
+                        .borrow_mut()
+                        .event_streams
+                        .retain(|stream| stream != message);
+                }
+            });
+            state.borrow_mut().event_streams.push(message.clone());
+        }
+        _ => message.set_status(404, None),
+    }
+}
+
+impl NowPlayingServer {
+    /// Listen on the given address, such as "127.0.0.1:8080".
+    pub fn start(address: &str) -> Result<Self, Box<dyn Error>> {
+        let socket_address: SocketAddr = address.parse()?;
+
+        let server = soup::Server::builder().server_header("SongRec").build();
+        let state = Rc::new(RefCell::new(ServerState::default()));
+
+        let handler_state = Rc::downgrade(&state);
+        server.add_handler(None, move |_server, message, path, query| {
+            if let Some(state) = handler_state.upgrade() {
+                handle_request(&state, message, path, &query);
+            }
+        });
+        server.listen(
+            &soup::gio::InetSocketAddress::from(socket_address),
+            soup::ServerListenOptions::empty(),
+        )?;
+
+        info!(
+            "{} http://{}/",
+            gettext("Now playing page available at:"),
+            socket_address
+        );
+
+        Ok(NowPlayingServer {
+            _server: server,
+            state,
+        })
+    }
+
+    pub fn song_recognized(&self, message: &SongRecognizedMessage) {
+        let song = song_json(message);
+        let event = glib::Bytes::from_owned(format!("event: song_recognized\ndata: {}\n\n", song));
+
+        let event_streams = {
+            let mut state = self.state.borrow_mut();
+            state.current_song = Some(CurrentSong {
+                song,
+                cover_image: message.cover_image.clone(),
+            });
+            state.event_streams.clone()
+        };
+
+        for stream in event_streams {
+            stream.response_body().unwrap().append_bytes(&event);
+            stream.unpause();
+        }
+    }
+}
+
+#[test]
+fn test_render_page() {
+    let song = serde_json::json!({
+        "song_name": "<script>alert(1)</script>",
+        "artist_name": "Simon & Garfunkel",
+        "album_name": null,
+        "cover_url": "/cover?track_key=1",
+    });
+    let page = render_page(Some(&song));
+    assert!(page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(page.contains("Simon &amp; Garfunkel"));
+    assert!(page.contains(r#"<img src="/cover?track_key=1" alt="">"#));
+    assert!(!page.contains("<script>"));
+
+    assert!(!render_page(None).contains("<img"));
+
+    assert_eq!(cover_content_type(b"\x89PNG\r\n"), "image/png");
+    assert_eq!(cover_content_type(b"\xff\xd8\xff"), "image/jpeg");
+}
@@ -5,6 +5,7 @@ use serde::Serialize;
 use std::error::Error;
 use std::path::PathBuf;
 
+use crate::core::webhooks::WebhookSettings;
 use crate::utils::filesystem_operations::obtain_preferences_file_path;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -14,6 +15,7 @@ pub struct Preferences {
     pub enable_systray: Option<bool>,
     pub enable_mpris: Option<bool>, // Legacy, before setting default to true
     pub enable_mpris_v2: Option<bool>,
+    pub enable_dbus_control: Option<bool>, // The D-Bus control interface of the GUI, see dbus_control.rs
     pub no_duplicates: Option<bool>,
     pub buffer_size_secs: Option<u64>,         // Removed in 0.7.3
     pub request_interval_secs: Option<u64>,    // Legacy, before increasing default from 4 to 10
@@ -22,6 +24,24 @@ pub struct Preferences {
     pub current_device_name: Option<String>,
     pub website_search_url: Option<String>,
     pub website_search_text: Option<String>,
+    pub recognition_backend: Option<String>, // "shazam" (the default), "http" or "local"
+    pub recognition_backend_url: Option<String>, // Used by the "http" backend
+    pub local_database_path: Option<String>, // Used by the "local" backend
+    pub listenbrainz_url: Option<String>, // A ListenBrainz-compatible server, ListenBrainz itself by default
+    pub listenbrainz_token: Option<String>, // Scrobbling is enabled when set
+    pub webhooks: Option<Vec<WebhookSettings>>, // Only editable in the preferences file for now
+    pub http_server_address: Option<String>, // The now playing server is enabled when set, e.g. "127.0.0.1:8080"
+    pub skip_silence_and_speech: Option<bool>, // Only make recognition requests for music
+    pub silence_threshold: Option<f32>, // Only editable in the preferences file, see audio_activity.rs
+    pub noise_flatness_threshold: Option<f32>, // Same
+    pub speech_pause_threshold: Option<f32>, // Same
+    pub song_change_threshold: Option<f32>, // Only editable in the preferences file, see song_change.rs
+    pub song_change_max_age_secs: Option<u64>, // Same
+    pub capture_window_secs: Option<u64>, // The length of the fingerprinted audio, see capture_window.rs
+    pub voting_windows: Option<u64>, // 2 or 3 to only report the songs that most windows agree on
+    pub capture_archive: Option<bool>, // Keep the audio of the recognition requests, see capture_archive.rs
+    pub capture_archive_max_files: Option<u64>, // Only editable in the preferences file
+    pub capture_archive_max_megabytes: Option<u64>, // Same
 }
 
 impl Preferences {
@@ -31,6 +51,7 @@ impl Preferences {
             enable_systray: None,
             enable_mpris: None,
             enable_mpris_v2: None,
+            enable_dbus_control: None,
             no_duplicates: None,
             buffer_size_secs: None,
             request_interval_secs: None,
@@ -39,6 +60,24 @@ impl Preferences {
             current_device_name: None,
             website_search_url: None,
             website_search_text: None,
+            recognition_backend: None,
+            recognition_backend_url: None,
+            local_database_path: None,
+            listenbrainz_url: None,
+            listenbrainz_token: None,
+            webhooks: None,
+            http_server_address: None,
+            skip_silence_and_speech: None,
+            silence_threshold: None,
+            noise_flatness_threshold: None,
+            speech_pause_threshold: None,
+            song_change_threshold: None,
+            song_change_max_age_secs: None,
+            capture_window_secs: None,
+            voting_windows: None,
+            capture_archive: None,
+            capture_archive_max_files: None,
+            capture_archive_max_megabytes: None,
         }
     }
 
@@ -48,6 +87,7 @@ impl Preferences {
             enable_systray: Some(false),
             enable_mpris: None,
             enable_mpris_v2: Some(true),
+            enable_dbus_control: Some(true),
             no_duplicates: Some(false),
             buffer_size_secs: None,
             request_interval_secs: None,
@@ -56,6 +96,24 @@ impl Preferences {
             current_device_name: None,
             website_search_url: Some("https://www.youtube.com/results?search_query=".to_string()),
             website_search_text: Some(gettext("Search on YouTube".to_string())),
+            recognition_backend: Some("shazam".to_string()),
+            recognition_backend_url: None,
+            local_database_path: None,
+            listenbrainz_url: None,
+            listenbrainz_token: None,
+            webhooks: None,
+            http_server_address: None,
+            skip_silence_and_speech: Some(true),
+            silence_threshold: None,
+            noise_flatness_threshold: None,
+            speech_pause_threshold: None,
+            song_change_threshold: None,
+            song_change_max_age_secs: None,
+            capture_window_secs: None,
+            voting_windows: None,
+            capture_archive: Some(false),
+            capture_archive_max_files: None,
+            capture_archive_max_megabytes: None,
         }
     }
 }
@@ -67,6 +125,7 @@ impl Default for Preferences {
             enable_systray: Some(false),
             enable_mpris: None,
             enable_mpris_v2: Some(true),
+            enable_dbus_control: Some(true),
             no_duplicates: Some(false),
             buffer_size_secs: None,
             request_interval_secs: None,
@@ -75,6 +134,24 @@ impl Default for Preferences {
             current_device_name: None,
             website_search_url: Some("https://www.youtube.com/results?search_query=".to_string()),
             website_search_text: Some(gettext("Search on YouTube".to_string())),
+            recognition_backend: Some("shazam".to_string()),
+            recognition_backend_url: None,
+            local_database_path: None,
+            listenbrainz_url: None,
+            listenbrainz_token: None,
+            webhooks: None,
+            http_server_address: None,
+            skip_silence_and_speech: Some(true),
+            silence_threshold: None,
+            noise_flatness_threshold: None,
+            speech_pause_threshold: None,
+            song_change_threshold: None,
+            song_change_max_age_secs: None,
+            capture_window_secs: None,
+            voting_windows: None,
+            capture_archive: Some(false),
+            capture_archive_max_files: None,
+            capture_archive_max_megabytes: None,
         }
     }
 }
@@ -125,6 +202,9 @@ impl PreferencesInterface {
                 .enable_mpris_v2
                 .or(current_preferences.enable_mpris_v2)
                 .or(current_preferences.enable_mpris),
+            enable_dbus_control: update_preferences
+                .enable_dbus_control
+                .or(current_preferences.enable_dbus_control),
             enable_systray: update_preferences
                 .enable_systray
                 .or(current_preferences.enable_systray),
@@ -156,6 +236,60 @@ impl PreferencesInterface {
             website_search_text: update_preferences
                 .website_search_text
                 .or_else(|| current_preferences.website_search_text.clone()),
+            recognition_backend: update_preferences
+                .recognition_backend
+                .or_else(|| current_preferences.recognition_backend.clone()),
+            recognition_backend_url: update_preferences
+                .recognition_backend_url
+                .or_else(|| current_preferences.recognition_backend_url.clone()),
+            local_database_path: update_preferences
+                .local_database_path
+                .or_else(|| current_preferences.local_database_path.clone()),
+            listenbrainz_url: update_preferences
+                .listenbrainz_url
+                .or_else(|| current_preferences.listenbrainz_url.clone()),
+            listenbrainz_token: update_preferences
+                .listenbrainz_token
+                .or_else(|| current_preferences.listenbrainz_token.clone()),
+            webhooks: update_preferences
+                .webhooks
+                .or_else(|| current_preferences.webhooks.clone()),
+            http_server_address: update_preferences
+                .http_server_address
+                .or_else(|| current_preferences.http_server_address.clone()),
+            skip_silence_and_speech: update_preferences
+                .skip_silence_and_speech
+                .or(current_preferences.skip_silence_and_speech),
+            silence_threshold: update_preferences
+                .silence_threshold
+                .or(current_preferences.silence_threshold),
+            noise_flatness_threshold: update_preferences
+                .noise_flatness_threshold
+                .or(current_preferences.noise_flatness_threshold),
+            speech_pause_threshold: update_preferences
+                .speech_pause_threshold
+                .or(current_preferences.speech_pause_threshold),
+            song_change_threshold: update_preferences
+                .song_change_threshold
+                .or(current_preferences.song_change_threshold),
+            song_change_max_age_secs: update_preferences
+                .song_change_max_age_secs
+                .or(current_preferences.song_change_max_age_secs),
+            capture_window_secs: update_preferences
+                .capture_window_secs
+                .or(current_preferences.capture_window_secs),
+            voting_windows: update_preferences
+                .voting_windows
+                .or(current_preferences.voting_windows),
+            capture_archive: update_preferences
+                .capture_archive
+                .or(current_preferences.capture_archive),
+            capture_archive_max_files: update_preferences
+                .capture_archive_max_files
+                .or(current_preferences.capture_archive_max_files),
+            capture_archive_max_megabytes: update_preferences
+                .capture_archive_max_megabytes
+                .or(current_preferences.capture_archive_max_megabytes),
         };
         if let Err(error) = self.write() {
             error!("{} {}", gettext("When saving the preferences file:"), error);
@@ -1,26 +1,65 @@
+use std::sync::{Arc, Mutex};
+
 use crate::core::thread_messages::{ProcessingMessage::*, *};
 
+use crate::core::capture_window::{CaptureSettings, MAX_HISTORY_SECS};
 use crate::core::fingerprinting::algorithm::SignatureGenerator;
+use crate::core::preferences::PreferencesInterface;
+use crate::core::song_change::COMPARISON_SECS;
 
 pub fn processing_thread(
     processing_rx: async_channel::Receiver<ProcessingMessage>,
     http_tx: async_channel::Sender<HTTPMessage>,
     gui_tx: async_channel::Sender<GUIMessage>,
+    preferences_interface: Arc<Mutex<PreferencesInterface>>,
 ) {
+    // Fingerprint the microphone input as it comes, so that overlapping
+    // windows do not need to be computed again at each recognition
+
+    let mut microphone_generator = SignatureGenerator::streaming(MAX_HISTORY_SECS);
+
     while let Ok(message) = processing_rx.recv_blocking() {
-        let signature = match message {
+        let capture_settings =
+            CaptureSettings::from_preferences(&preferences_interface.lock().unwrap().preferences);
+
+        let signatures = match message {
             ProcessAudioFile(input_file_string) => {
-                SignatureGenerator::make_signature_from_file(&input_file_string)
+                SignatureGenerator::make_signatures_from_file(&input_file_string, &capture_settings)
+            }
+            ProcessMicrophoneSamples(audio_samples) => {
+                microphone_generator.add_samples(&audio_samples);
+                continue;
+            }
+            RecognizeMicrophoneSamples { archived_samples } => {
+                http_tx
+                    .try_send(HTTPMessage::RecognizeMicrophoneSignatures {
+                        signatures: capture_settings
+                            .microphone_window_offsets()
+                            .map(|seconds_before_end| {
+                                microphone_generator.get_signature_for_window(
+                                    capture_settings.window_secs,
+                                    seconds_before_end,
+                                )
+                            })
+                            .collect(),
+                        latest_signature: Box::new(
+                            microphone_generator.get_signature_for_last_seconds(COMPARISON_SECS),
+                        ),
+                        archived_samples,
+                    })
+                    .unwrap();
+                continue;
+            }
+            ResetMicrophoneSamples => {
+                microphone_generator = SignatureGenerator::streaming(MAX_HISTORY_SECS);
+                continue;
             }
-            ProcessAudioSamples(audio_samples) => Ok(
-                SignatureGenerator::make_signature_from_buffer(&audio_samples),
-            ),
         };
 
-        match signature {
-            Ok(signature) => {
+        match signatures {
+            Ok(signatures) => {
                 http_tx
-                    .try_send(HTTPMessage::RecognizeSignature(Box::new(signature)))
+                    .try_send(HTTPMessage::RecognizeSignatures(signatures))
                     .unwrap();
             }
             Err(error) => {
@@ -0,0 +1,171 @@
+use gettextrs::gettext;
+use log::{debug, error, warn};
+use soup::prelude::SessionExt;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::core::capture_window::majority_vote;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::preferences::Preferences;
+use crate::core::recognition_backends::custom_http::CustomHttpBackend;
+use crate::core::recognition_backends::local_database::LocalDatabaseBackend;
+use crate::core::recognition_backends::shazam::ShazamBackend;
+use crate::core::thread_messages::SongRecognizedMessage;
+
+/// Number of times a request is retried after being rate-limited
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+pub type RecognitionFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<SongRecognizedMessage, RecognitionError>> + 'a>>;
+
+pub trait RecognitionBackend {
+    fn recognize<'a>(&'a self, signature: &'a DecodedSignature) -> RecognitionFuture<'a>;
+}
+
+#[derive(Debug)]
+pub enum RecognitionError {
+    NoMatch,
+    RateLimited,
+    Network(Box<dyn Error>),
+    Backend(Box<dyn Error>),
+}
+
+impl fmt::Display for RecognitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecognitionError::NoMatch => write!(f, "{}", gettext("No match for this song")),
+            RecognitionError::RateLimited => {
+                write!(f, "{}", gettext("Your IP has been rate-limited"))
+            }
+            RecognitionError::Network(error) | RecognitionError::Backend(error) => {
+                write!(f, "{}", error)
+            }
+        }
+    }
+}
+
+impl Error for RecognitionError {}
+
+impl From<Box<dyn Error>> for RecognitionError {
+    fn from(error: Box<dyn Error>) -> Self {
+        match error.downcast_ref::<std::io::Error>() {
+            Some(io_error) if io_error.kind() == std::io::ErrorKind::QuotaExceeded => {
+                RecognitionError::RateLimited
+            }
+            _ => RecognitionError::Network(error),
+        }
+    }
+}
+
+/// Identifies the backend configured in the preferences, so that the HTTP
+/// task knows when it has to be instantiated again.
+pub fn get_backend_key(
+    preferences: &Preferences,
+) -> (Option<String>, Option<String>, Option<String>) {
+    (
+        preferences.recognition_backend.clone(),
+        preferences.recognition_backend_url.clone(),
+        preferences.local_database_path.clone(),
+    )
+}
+
+pub fn get_backend(preferences: &Preferences) -> Box<dyn RecognitionBackend> {
+    match preferences.recognition_backend.as_deref() {
+        None | Some("shazam") => Box::new(ShazamBackend::new()),
+        Some("http") => match preferences.recognition_backend_url {
+            Some(ref url) => Box::new(CustomHttpBackend::new(url)),
+            None => {
+                error!(
+                    "{}",
+                    gettext("No URL set for the HTTP recognition backend, using Shazam")
+                );
+                Box::new(ShazamBackend::new())
+            }
+        },
+        Some("local") => Box::new(LocalDatabaseBackend::new(
+            preferences.local_database_path.as_deref(),
+        )),
+        Some(other) => {
+            error!(
+                "{} {}",
+                gettext("Unknown recognition backend, using Shazam:"),
+                other
+            );
+            Box::new(ShazamBackend::new())
+        }
+    }
+}
+
+/// Recognize a signature, waiting for one minute and trying again (up to a
+/// few times) when rate-limited. Used by the batch modes, which send many
+/// requests in a row.
+pub async fn recognize_with_retries(
+    backend: &dyn RecognitionBackend,
+    signature: &DecodedSignature,
+) -> Result<SongRecognizedMessage, RecognitionError> {
+    let mut retries = 0;
+    loop {
+        match backend.recognize(signature).await {
+            Err(RecognitionError::RateLimited) if retries < MAX_RATE_LIMITED_RETRIES => {
+                retries += 1;
+                warn!(
+                    "{}",
+                    gettext("Your IP has been rate-limited, waiting one minute...")
+                );
+                glib::timeout_future(Duration::from_secs(60)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Recognize the windows of audio of a request one after the other, and
+/// only report a song that most of them agree on. No more requests are made
+/// once a majority is reached, or after an error other than no match.
+pub async fn recognize_by_vote(
+    backend: &dyn RecognitionBackend,
+    signatures: &[DecodedSignature],
+) -> Result<SongRecognizedMessage, RecognitionError> {
+    let mut recognized_songs: Vec<Option<SongRecognizedMessage>> = vec![];
+
+    for signature in signatures {
+        match backend.recognize(signature).await {
+            Ok(recognized_song) => recognized_songs.push(Some(recognized_song)),
+            Err(RecognitionError::NoMatch) => recognized_songs.push(None),
+            Err(error) => return Err(error),
+        }
+
+        let track_keys: Vec<Option<&str>> = recognized_songs
+            .iter()
+            .map(|recognized_song| {
+                recognized_song
+                    .as_ref()
+                    .map(|recognized_song| recognized_song.track_key.as_str())
+            })
+            .collect();
+
+        if let Some(index) = majority_vote(&track_keys, signatures.len()) {
+            debug!(
+                "{} of {} windows agree on the recognized song",
+                track_keys
+                    .iter()
+                    .filter(|track_key| **track_key == track_keys[index])
+                    .count(),
+                signatures.len()
+            );
+            return Ok(recognized_songs.swap_remove(index).unwrap());
+        }
+    }
+
+    Err(RecognitionError::NoMatch)
+}
+
+pub fn new_session() -> soup::Session {
+    let session = soup::Session::new();
+    session.set_timeout(20);
+    session.set_idle_timeout(2);
+    session
+}
@@ -0,0 +1,86 @@
+use gettextrs::gettext;
+use glib::source::Priority;
+use log::{debug, error};
+use serde_json::{Value, json};
+use soup::prelude::SessionExt;
+
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::recognition_backends::backend::{
+    RecognitionBackend, RecognitionError, RecognitionFuture, new_session,
+};
+use crate::core::recognition_backends::shazam::song_from_shazam_json;
+
+/// A backend for self-hosted matching services (or a local stand-in used
+/// for testing). The signature is POSTed as JSON to the configured URL:
+///
+/// {"signature": {"uri": "data:audio/vnd.shazam.sig;base64,...", "samplems": 12000}}
+///
+/// The service is expected to answer in the same format as Shazam's API
+/// (with at least the "track" -> "key", "title" and "subtitle" fields), with
+/// no "track" object when there is no match, and with the 429 HTTP status
+/// code when the client is rate-limited.
+pub struct CustomHttpBackend {
+    session: soup::Session,
+    url: String,
+}
+
+impl CustomHttpBackend {
+    pub fn new(url: &str) -> Self {
+        CustomHttpBackend {
+            session: new_session(),
+            url: url.to_string(),
+        }
+    }
+}
+
+impl RecognitionBackend for CustomHttpBackend {
+    fn recognize<'a>(&'a self, signature: &'a DecodedSignature) -> RecognitionFuture<'a> {
+        Box::pin(async move {
+            let post_data = json!({
+                "signature": {
+                    "uri": signature.encode_to_uri().map_err(|error| RecognitionError::Backend(Box::new(error)))?,
+                    "samplems": (signature.number_samples as f32 / signature.sample_rate_hz as f32 * 1000.) as u32
+                }
+            })
+            .to_string();
+
+            let message = soup::Message::from_encoded_form("POST", &self.url, post_data.into())
+                .map_err(|error| RecognitionError::Network(Box::new(error)))?;
+
+            let headers = message.request_headers().unwrap();
+            headers.set_content_type(Some("application/json"), None);
+
+            debug!("Sending request to the recognition backend: {}", self.url);
+
+            let response = self
+                .session
+                .send_and_read_future(&message, Priority::DEFAULT)
+                .await
+                .map_err(|error| RecognitionError::Network(Box::new(error)))?;
+
+            match message.status_code() {
+                200 => {}
+                429 => return Err(RecognitionError::RateLimited),
+                status_code => {
+                    error!(
+                        "Received response from the recognition backend: {} {:?}",
+                        status_code,
+                        String::from_utf8_lossy(&response[..])
+                    );
+                    return Err(RecognitionError::Network(Box::new(std::io::Error::other(
+                        format!(
+                            "{} {}",
+                            gettext("The recognition backend returned HTTP status"),
+                            status_code
+                        ),
+                    ))));
+                }
+            }
+
+            let json_object: Value = serde_json::from_slice(&response[..])
+                .map_err(|error| RecognitionError::Network(Box::new(error)))?;
+
+            song_from_shazam_json(&self.session, json_object).await
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use gettextrs::gettext;
+use serde_json::json;
+use std::path::PathBuf;
+
+use crate::core::fingerprinting::local_database::LocalDatabase;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::recognition_backends::backend::{
+    RecognitionBackend, RecognitionError, RecognitionFuture,
+};
+use crate::core::thread_messages::SongRecognizedMessage;
+use crate::utils::filesystem_operations::obtain_local_database_path;
+
+/// An offline backend matching signatures against the database built with
+/// the "songrec index" command.
+pub struct LocalDatabaseBackend {
+    database: Result<LocalDatabase, String>,
+}
+
+impl LocalDatabaseBackend {
+    pub fn new(database_path: Option<&str>) -> Self {
+        let database = match database_path {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => obtain_local_database_path(),
+        }
+        .and_then(|path| LocalDatabase::load(&path))
+        .map_err(|error| {
+            format!(
+                "{} {}",
+                gettext("Could not load the local fingerprint database:"),
+                error
+            )
+        });
+
+        LocalDatabaseBackend { database }
+    }
+}
+
+impl RecognitionBackend for LocalDatabaseBackend {
+    fn recognize<'a>(&'a self, signature: &'a DecodedSignature) -> RecognitionFuture<'a> {
+        Box::pin(async move {
+            let database = self.database.as_ref().map_err(|error| {
+                RecognitionError::Backend(Box::new(std::io::Error::other(error.clone())))
+            })?;
+
+            let found = database
+                .find_match(signature)
+                .ok_or(RecognitionError::NoMatch)?;
+
+            let track_key = format!("local:{}", found.track.path);
+
+            Ok(SongRecognizedMessage {
+                artist_name: found.track.artist_name.clone(),
+                album_name: None,
+                song_name: found.track.song_name.clone(),
+                cover_image: None,
+                track_key: track_key.clone(),
+                release_year: None,
+                genre: None,
+                isrc: None,
+                label: None,
+                match_offset_secs: Some(found.offset_secs as f64),
+                frequency_skew: None,
+                time_skew: None,
+                lyrics: vec![],
+                streaming_links: vec![],
+                related_tracks_url: None,
+                // Mimic the layout of Shazam's responses for the JSON output
+                shazam_json: serde_json::to_string_pretty(&json!({
+                    "matches": [{
+                        "offset": found.offset_secs,
+                    }],
+                    "track": {
+                        "key": track_key,
+                        "title": found.track.song_name,
+                        "subtitle": found.track.artist_name,
+                    },
+                    "local_match": {
+                        "path": found.track.path,
+                        "score": found.score,
+                    },
+                }))
+                .unwrap(),
+                capture_path: None,
+            })
+        })
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::core::fingerprinting::communication::{
+    obtain_raw_cover_image, recognize_song_from_signature,
+};
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::recognition_backends::backend::{
+    RecognitionBackend, RecognitionError, RecognitionFuture, new_session,
+};
+use crate::core::recognition_backends::shazam_response::ShazamResponse;
+use crate::core::thread_messages::SongRecognizedMessage;
+
+pub struct ShazamBackend {
+    session: soup::Session,
+}
+
+impl ShazamBackend {
+    pub fn new() -> Self {
+        ShazamBackend {
+            session: new_session(),
+        }
+    }
+}
+
+impl Default for ShazamBackend {
+    fn default() -> Self {
+        ShazamBackend::new()
+    }
+}
+
+impl RecognitionBackend for ShazamBackend {
+    fn recognize<'a>(&'a self, signature: &'a DecodedSignature) -> RecognitionFuture<'a> {
+        Box::pin(async move {
+            let json_object = recognize_song_from_signature(&self.session, signature).await?;
+
+            song_from_shazam_json(&self.session, json_object).await
+        })
+    }
+}
+
+/// Decode a response in the format of Shazam's API into its typed model.
+/// A response that can not be decoded at all is an error of the backend,
+/// not of the network.
+pub fn parse_shazam_response(json_object: &Value) -> Result<ShazamResponse, RecognitionError> {
+    ShazamResponse::deserialize(json_object)
+        .map_err(|error| RecognitionError::Backend(Box::new(error)))
+}
+
+/// Extract the song information from a response in the format of Shazam's
+/// API, downloading the cover art if any.
+pub async fn song_from_shazam_json(
+    session: &soup::Session,
+    json_object: Value,
+) -> Result<SongRecognizedMessage, RecognitionError> {
+    let response = parse_shazam_response(&json_object)?;
+
+    let track = response.track.ok_or(RecognitionError::NoMatch)?;
+    let best_match = response.matches.first();
+
+    let (Some(artist_name), Some(song_name), Some(track_key)) =
+        (&track.subtitle, &track.title, &track.key)
+    else {
+        return Err(RecognitionError::NoMatch);
+    };
+
+    Ok(SongRecognizedMessage {
+        artist_name: artist_name.clone(),
+        album_name: track.song_metadata("Album"),
+        song_name: song_name.clone(),
+        cover_image: match track.images.coverart {
+            Some(ref url) => Some(obtain_raw_cover_image(session, url).await?),
+            None => None,
+        },
+        track_key: track_key.clone(),
+        release_year: track.song_metadata("Released"),
+        genre: track.genres.primary.clone(),
+        isrc: track.isrc.clone(),
+        label: track.song_metadata("Label"),
+        match_offset_secs: best_match.and_then(|found| found.offset),
+        frequency_skew: best_match.and_then(|found| found.frequencyskew),
+        time_skew: best_match.and_then(|found| found.timeskew),
+        lyrics: track.lyrics(),
+        streaming_links: track.streaming_links(),
+        related_tracks_url: track.related_tracks_url(),
+        shazam_json: serde_json::to_string(&json_object).unwrap(),
+        capture_path: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::recognition_backends::shazam_response::StreamingLink;
+
+    const RESPONSE_FIXTURE: &str = include_str!("fixtures/shazam_response.json");
+
+    #[test]
+    fn test_parse_recorded_response() {
+        let json_object: Value = serde_json::from_str(RESPONSE_FIXTURE).unwrap();
+        let response = parse_shazam_response(&json_object).unwrap();
+
+        let track = response.track.unwrap();
+        assert_eq!(track.key.as_deref(), Some("20066955"));
+        assert_eq!(track.title.as_deref(), Some("Around the World"));
+        assert_eq!(track.subtitle.as_deref(), Some("Daft Punk"));
+        assert_eq!(track.isrc.as_deref(), Some("GBDUW0600007"));
+        assert_eq!(track.genres.primary.as_deref(), Some("Electronic"));
+        assert_eq!(track.song_metadata("Album").as_deref(), Some("Homework"));
+        assert_eq!(track.song_metadata("Released").as_deref(), Some("1997"));
+        assert_eq!(track.lyrics().len(), 2);
+        assert!(track.related_tracks_url().unwrap().contains("20066955"));
+        assert_eq!(track.streaming_links().len(), 3);
+        assert_eq!(
+            track.streaming_links()[1],
+            StreamingLink {
+                provider: "SPOTIFY".to_string(),
+                uri: "spotify:search:Around%20the%20World%20Daft%20Punk".to_string(),
+            }
+        );
+
+        assert_eq!(response.matches[0].offset, Some(45.3359375));
+    }
+
+    #[test]
+    fn test_parse_response_with_unexpected_types() {
+        let mut json_object: Value = serde_json::from_str(RESPONSE_FIXTURE).unwrap();
+
+        json_object["matches"][0]["offset"] = Value::from("45.5");
+        json_object["track"]["key"] = Value::from(20066955);
+        json_object["track"]["sections"][1]["text"] = Value::Null;
+        json_object["track"]["hub"]["providers"] = Value::Null;
+        json_object["track"]["genres"] = Value::from("Electronic");
+
+        let response = parse_shazam_response(&json_object).unwrap();
+        let track = response.track.unwrap();
+
+        assert_eq!(response.matches[0].offset, Some(45.5));
+        assert_eq!(track.key.as_deref(), Some("20066955"));
+        assert_eq!(track.title.as_deref(), Some("Around the World"));
+        assert!(track.lyrics().is_empty());
+        assert_eq!(track.streaming_links().len(), 1);
+        assert_eq!(track.genres.primary, None);
+
+        assert!(matches!(
+            parse_shazam_response(&Value::from("not an object")),
+            Err(RecognitionError::Backend(_))
+        ));
+    }
+}
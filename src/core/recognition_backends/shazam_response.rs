@@ -0,0 +1,263 @@
+//! A typed model of the JSON responses of Shazam's recognition API, as
+//! returned by the "/discovery/v5/..." endpoint (and expected from custom
+//! HTTP backends).
+//!
+//! Every field is optional or defaults to an empty value, as the exact
+//! contents of the responses vary between tracks and over time.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+// The fields of the responses are decoded leniently: a value of an
+// unexpected type (such as a null list or a number sent as a string) is
+// converted when possible, or replaced by the default value, rather than
+// making a successful match fail to decode.
+
+fn lenient<'de, D: Deserializer<'de>, T: DeserializeOwned + Default>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    Ok(T::deserialize(Value::deserialize(deserializer)?).unwrap_or_default())
+}
+
+fn lenient_vec<'de, D: Deserializer<'de>, T: DeserializeOwned>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Array(items) => items
+            .into_iter()
+            .filter_map(|item| T::deserialize(item).ok())
+            .collect(),
+        _ => vec![],
+    })
+}
+
+fn lenient_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(string) => Some(string),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(boolean) => Some(boolean.to_string()),
+        _ => None,
+    })
+}
+
+fn lenient_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Number(number) => number.as_f64(),
+        Value::String(string) => string.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamResponse {
+    #[serde(deserialize_with = "lenient_vec")]
+    pub matches: Vec<ShazamMatch>,
+    #[serde(deserialize_with = "lenient")]
+    pub track: Option<ShazamTrack>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub tagid: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamMatch {
+    #[serde(deserialize_with = "lenient_string")]
+    pub id: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub offset: Option<f64>, // Position of the sample in the track, in seconds
+    #[serde(deserialize_with = "lenient_number")]
+    pub timeskew: Option<f64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub frequencyskew: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamTrack {
+    #[serde(deserialize_with = "lenient_string")]
+    pub key: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub title: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub subtitle: Option<String>, // The artist name
+    #[serde(deserialize_with = "lenient_string")]
+    pub isrc: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub url: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub relatedtracksurl: Option<String>,
+    #[serde(deserialize_with = "lenient")]
+    pub images: ShazamImages,
+    #[serde(deserialize_with = "lenient")]
+    pub genres: ShazamGenres,
+    #[serde(deserialize_with = "lenient")]
+    pub hub: ShazamHub,
+    #[serde(deserialize_with = "lenient_vec")]
+    pub sections: Vec<ShazamSection>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamImages {
+    #[serde(deserialize_with = "lenient_string")]
+    pub coverart: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub coverarthq: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub background: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamGenres {
+    #[serde(deserialize_with = "lenient_string")]
+    pub primary: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamHub {
+    #[serde(rename = "type", deserialize_with = "lenient_string")]
+    pub hub_type: Option<String>,
+    #[serde(deserialize_with = "lenient_vec")]
+    pub actions: Vec<ShazamAction>,
+    #[serde(deserialize_with = "lenient_vec")]
+    pub options: Vec<ShazamHubOption>,
+    #[serde(deserialize_with = "lenient_vec")]
+    pub providers: Vec<ShazamProvider>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamAction {
+    #[serde(deserialize_with = "lenient_string")]
+    pub name: Option<String>,
+    #[serde(rename = "type", deserialize_with = "lenient_string")]
+    pub action_type: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamHubOption {
+    #[serde(deserialize_with = "lenient_string")]
+    pub caption: Option<String>,
+    #[serde(deserialize_with = "lenient_vec")]
+    pub actions: Vec<ShazamAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamProvider {
+    #[serde(rename = "type", deserialize_with = "lenient_string")]
+    pub provider_type: Option<String>, // "SPOTIFY", "DEEZER", "YOUTUBEMUSIC"...
+    #[serde(deserialize_with = "lenient_string")]
+    pub caption: Option<String>,
+    #[serde(deserialize_with = "lenient_vec")]
+    pub actions: Vec<ShazamAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamSection {
+    #[serde(rename = "type", deserialize_with = "lenient_string")]
+    pub section_type: Option<String>, // "SONG", "LYRICS", "VIDEO", "RELATED"...
+    #[serde(deserialize_with = "lenient_vec")]
+    pub metadata: Vec<ShazamMetadatum>,
+    #[serde(deserialize_with = "lenient_vec")]
+    pub text: Vec<String>, // The lines of the lyrics, for the "LYRICS" section
+    #[serde(deserialize_with = "lenient_string")]
+    pub footer: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ShazamMetadatum {
+    #[serde(deserialize_with = "lenient_string")]
+    pub title: Option<String>, // "Album", "Label", "Released"...
+    #[serde(deserialize_with = "lenient_string")]
+    pub text: Option<String>,
+}
+
+/// A link to the song on a streaming service, extracted from the "hub"
+/// object of the response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamingLink {
+    pub provider: String,
+    pub uri: String,
+}
+
+impl ShazamTrack {
+    fn section(&self, section_type: &str) -> Option<&ShazamSection> {
+        self.sections
+            .iter()
+            .find(|section| section.section_type.as_deref() == Some(section_type))
+    }
+
+    /// Look up a field of the "SONG" section, such as "Album", "Label" or
+    /// "Released".
+    pub fn song_metadata(&self, title: &str) -> Option<String> {
+        self.section("SONG")?
+            .metadata
+            .iter()
+            .find(|metadatum| metadatum.title.as_deref() == Some(title))
+            .and_then(|metadatum| metadatum.text.clone())
+    }
+
+    pub fn lyrics(&self) -> Vec<String> {
+        self.section("LYRICS")
+            .map(|section| section.text.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn related_tracks_url(&self) -> Option<String> {
+        self.relatedtracksurl
+            .clone()
+            .or_else(|| self.section("RELATED")?.url.clone())
+    }
+
+    pub fn streaming_links(&self) -> Vec<StreamingLink> {
+        let mut links = vec![];
+
+        // The main hub (usually Apple Music) exposes its links as "options"
+
+        for option in &self.hub.options {
+            for action in &option.actions {
+                if let Some(ref uri) = action.uri {
+                    links.push(StreamingLink {
+                        provider: self
+                            .hub
+                            .hub_type
+                            .clone()
+                            .unwrap_or_else(|| option.caption.clone().unwrap_or_default()),
+                        uri: uri.clone(),
+                    });
+                }
+            }
+        }
+
+        for provider in &self.hub.providers {
+            if let Some(uri) = provider
+                .actions
+                .iter()
+                .find_map(|action| action.uri.clone())
+            {
+                links.push(StreamingLink {
+                    provider: provider
+                        .provider_type
+                        .clone()
+                        .or_else(|| provider.caption.clone())
+                        .unwrap_or_default(),
+                    uri,
+                });
+            }
+        }
+
+        links
+    }
+}
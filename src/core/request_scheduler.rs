@@ -0,0 +1,230 @@
+//! Decides how long to wait between the recognition requests made for the
+//! audio recorded from the microphone, instead of a fixed interval:
+//!
+//! - after being rate limited, the interval is doubled at each new refusal
+//! - while the same track keeps being recognized, the interval grows, but
+//!   not beyond the estimated end of the track
+//! - when the audio changes markedly, the interval is reset, so that a new
+//!   song is recognized quickly
+
+use gettextrs::gettext;
+
+// Used to estimate the remaining time of a track from the match offset,
+// as the recognition backends don't give the duration of tracks
+const TYPICAL_TRACK_SECS: f64 = 240.0;
+
+const MAX_BACKOFF_SECS: u64 = 600;
+const MAX_FOLLOWING_SECS: u64 = 60;
+
+/// The result of a recognition request, as reported by the HTTP task.
+#[derive(Debug)]
+pub enum RecognitionOutcome {
+    Match {
+        track_key: String,
+        match_offset_secs: Option<f64>,
+    },
+    NoMatch,
+    RateLimited,
+    Failed,   // Network or backend error
+    SameSong, // No request was made, see song_change.rs
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScheduleState {
+    Normal,
+    FollowingTrack, // The same track keeps being recognized
+    BackingOff,     // After being rate limited
+    ContentChanged, // The audio changed since the last recognized track
+}
+
+impl ScheduleState {
+    pub fn label(&self) -> String {
+        match self {
+            ScheduleState::Normal => gettext("Listening"),
+            ScheduleState::FollowingTrack => gettext("Following the current track"),
+            ScheduleState::BackingOff => gettext("Rate limited, backing off"),
+            ScheduleState::ContentChanged => gettext("The audio changed"),
+        }
+    }
+
+    /// The identifier exposed by the daemon.
+    pub fn key(&self) -> &'static str {
+        match self {
+            ScheduleState::Normal => "normal",
+            ScheduleState::FollowingTrack => "following_track",
+            ScheduleState::BackingOff => "backing_off",
+            ScheduleState::ContentChanged => "content_changed",
+        }
+    }
+}
+
+/// Sent to the GUI every second by the microphone thread.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduleStatus {
+    pub state: ScheduleState,
+    pub next_request_secs: u64,
+}
+
+impl ScheduleStatus {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} ({})",
+            self.state.label(),
+            gettext("next request in %d s").replace("%d", &self.next_request_secs.to_string())
+        )
+    }
+}
+
+pub struct RequestScheduler {
+    base_interval_secs: u64, // The request interval set in the preferences
+    interval_secs: u64,
+    state: ScheduleState,
+    number_refusals: u32, // Consecutive rate limited requests
+    current_track_key: Option<String>,
+    number_track_matches: u32, // Consecutive matches of the current track
+}
+
+impl RequestScheduler {
+    pub fn new(base_interval_secs: u64) -> Self {
+        RequestScheduler {
+            base_interval_secs,
+            interval_secs: base_interval_secs,
+            state: ScheduleState::Normal,
+            number_refusals: 0,
+            current_track_key: None,
+            number_track_matches: 0,
+        }
+    }
+
+    /// Follow the changes of the request interval preference.
+    pub fn set_base_interval(&mut self, base_interval_secs: u64) {
+        if base_interval_secs != self.base_interval_secs {
+            self.base_interval_secs = base_interval_secs;
+            self.interval_secs = match self.state {
+                ScheduleState::Normal | ScheduleState::ContentChanged => base_interval_secs,
+                _ => self.interval_secs.max(base_interval_secs),
+            };
+        }
+    }
+
+    pub fn interval_secs(&self) -> u64 {
+        self.interval_secs
+    }
+
+    pub fn status(&self, elapsed_secs: u64) -> ScheduleStatus {
+        ScheduleStatus {
+            state: self.state,
+            next_request_secs: self.interval_secs.saturating_sub(elapsed_secs),
+        }
+    }
+
+    fn reset(&mut self, state: ScheduleState) {
+        self.state = state;
+        self.interval_secs = self.base_interval_secs;
+        self.current_track_key = None;
+        self.number_track_matches = 0;
+    }
+
+    pub fn on_outcome(&mut self, outcome: RecognitionOutcome) {
+        match outcome {
+            RecognitionOutcome::RateLimited => {
+                self.number_refusals += 1;
+                self.state = ScheduleState::BackingOff;
+                self.interval_secs = self
+                    .base_interval_secs
+                    .saturating_mul(1 << self.number_refusals.min(16))
+                    .min(MAX_BACKOFF_SECS);
+            }
+            RecognitionOutcome::Match {
+                track_key,
+                match_offset_secs,
+            } => {
+                self.number_refusals = 0;
+
+                if self.current_track_key.as_ref() != Some(&track_key) {
+                    self.reset(ScheduleState::Normal);
+                    self.current_track_key = Some(track_key);
+                    return;
+                }
+
+                // Wait a bit longer after each match of the same track, but
+                // check again around its estimated end
+                self.number_track_matches += 1;
+                self.state = ScheduleState::FollowingTrack;
+
+                let mut interval_secs =
+                    self.base_interval_secs * (1 + self.number_track_matches as u64);
+                if let Some(offset_secs) = match_offset_secs
+                    && offset_secs < TYPICAL_TRACK_SECS
+                {
+                    interval_secs = interval_secs.min((TYPICAL_TRACK_SECS - offset_secs) as u64);
+                }
+                self.interval_secs = interval_secs
+                    .min(MAX_FOLLOWING_SECS)
+                    .max(self.base_interval_secs);
+            }
+            RecognitionOutcome::NoMatch => {
+                self.number_refusals = 0;
+                self.reset(ScheduleState::Normal);
+            }
+            RecognitionOutcome::Failed | RecognitionOutcome::SameSong => {}
+        }
+    }
+
+    /// Called by the microphone thread when the audio changes markedly.
+    pub fn on_content_change(&mut self) {
+        // Keep respecting the rate limits
+        if self.state == ScheduleState::FollowingTrack {
+            self.reset(ScheduleState::ContentChanged);
+        }
+    }
+}
+
+#[test]
+fn test_request_scheduler() {
+    let track = |track_key: &str, match_offset_secs: f64| RecognitionOutcome::Match {
+        track_key: track_key.to_string(),
+        match_offset_secs: Some(match_offset_secs),
+    };
+    let mut scheduler = RequestScheduler::new(8);
+
+    scheduler.on_outcome(RecognitionOutcome::RateLimited);
+    assert_eq!(scheduler.interval_secs(), 16);
+    scheduler.on_outcome(RecognitionOutcome::RateLimited);
+    assert_eq!(scheduler.interval_secs(), 32);
+    assert_eq!(scheduler.status(10).state, ScheduleState::BackingOff);
+    assert_eq!(scheduler.status(10).next_request_secs, 22);
+    for _ in 0..10 {
+        scheduler.on_outcome(RecognitionOutcome::RateLimited);
+    }
+    assert_eq!(scheduler.interval_secs(), MAX_BACKOFF_SECS);
+    scheduler.on_content_change();
+    assert_eq!(scheduler.status(0).state, ScheduleState::BackingOff);
+
+    // A new track resets the interval, then the same one slows it down
+    scheduler.on_outcome(track("1", 20.0));
+    assert_eq!(scheduler.interval_secs(), 8);
+    scheduler.on_outcome(track("1", 40.0));
+    assert_eq!(scheduler.interval_secs(), 16);
+    assert_eq!(scheduler.status(0).state, ScheduleState::FollowingTrack);
+    scheduler.on_outcome(track("1", 60.0));
+    assert_eq!(scheduler.interval_secs(), 24);
+    for _ in 0..10 {
+        scheduler.on_outcome(track("1", 100.0));
+    }
+    assert_eq!(scheduler.interval_secs(), MAX_FOLLOWING_SECS);
+
+    // Near the estimated end of the track
+    scheduler.on_outcome(track("1", 230.0));
+    assert_eq!(scheduler.interval_secs(), 10);
+
+    scheduler.on_content_change();
+    assert_eq!(scheduler.interval_secs(), 8);
+    assert_eq!(scheduler.status(0).state, ScheduleState::ContentChanged);
+
+    scheduler.on_outcome(track("2", 0.0));
+    scheduler.on_outcome(track("2", 10.0));
+    scheduler.on_outcome(RecognitionOutcome::NoMatch);
+    assert_eq!(scheduler.interval_secs(), 8);
+    assert_eq!(scheduler.status(0).state, ScheduleState::Normal);
+}
@@ -0,0 +1,516 @@
+//! Submission of the recognized songs as listens to ListenBrainz, or to any
+//! service implementing its "submit-listens" API (such as a local stand-in
+//! used for testing). Listens are kept in a queue on the disk until they are
+//! accepted, so that they survive network outages and restarts, and failed
+//! submissions are retried with an exponential backoff.
+
+use chrono::{DateTime, Local};
+use gettextrs::gettext;
+use glib::source::Priority;
+use log::{debug, error, info, warn};
+use serde_json::{Value, json};
+use soup::prelude::SessionExt;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::core::preferences::PreferencesInterface;
+use crate::core::recognition_backends::backend::new_session;
+use crate::core::thread_messages::{ScrobblingMessage, SongRecognizedMessage};
+use crate::utils::filesystem_operations::obtain_scrobbling_queue_path;
+use crate::utils::playlist_formats::listenbrainz_track_metadata;
+
+pub const DEFAULT_LISTENBRAINZ_URL: &str = "https://api.listenbrainz.org";
+
+/// ListenBrainz accepts up to 1000 listens per request, keep the requests
+/// reasonably small
+const MAX_LISTENS_PER_REQUEST: usize = 100;
+
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Build the listen submitted for a recognized song.
+pub fn listen_from_song(message: &SongRecognizedMessage, listened_at: DateTime<Local>) -> Value {
+    let origin_url = serde_json::from_str::<Value>(&message.shazam_json)
+        .ok()
+        .and_then(|shazam_json| shazam_json["track"]["url"].as_str().map(str::to_string));
+
+    json!({
+        "listened_at": listened_at.timestamp(),
+        "track_metadata": listenbrainz_track_metadata(
+            &message.artist_name,
+            &message.song_name,
+            message.album_name.as_deref(),
+            message.isrc.as_deref(),
+            origin_url.as_deref(),
+        ),
+    })
+}
+
+/// The listens waiting to be submitted, stored as JSON Lines.
+pub struct ListenQueue {
+    path: PathBuf,
+    listens: Vec<Value>,
+}
+
+impl ListenQueue {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut listens = vec![];
+
+        if path.exists() {
+            for line in std::fs::read_to_string(path)?.lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(line) {
+                    Ok(listen) => listens.push(listen),
+                    Err(error) => error!("Ignoring invalid queued listen: {}", error),
+                }
+            }
+        }
+
+        Ok(ListenQueue {
+            path: path.to_path_buf(),
+            listens,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.listens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listens.is_empty()
+    }
+
+    pub fn push(&mut self, listen: Value) -> Result<(), Box<dyn Error>> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", listen)?;
+        self.listens.push(listen);
+        Ok(())
+    }
+
+    /// The oldest listens, to be submitted next.
+    fn next_batch(&self) -> &[Value] {
+        &self.listens[..self.listens.len().min(MAX_LISTENS_PER_REQUEST)]
+    }
+
+    fn remove_first(&mut self, count: usize) -> Result<(), Box<dyn Error>> {
+        self.listens.drain(..count);
+
+        let mut contents = String::new();
+        for listen in &self.listens {
+            contents += &format!("{}\n", listen);
+        }
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
+
+/// Double the delay after each failed attempt, within bounds.
+pub fn next_retry_delay(previous: Option<Duration>) -> Duration {
+    match previous {
+        None => MIN_RETRY_DELAY,
+        Some(delay) => (delay * 2).min(MAX_RETRY_DELAY),
+    }
+}
+
+enum SubmissionError {
+    /// Try again later, after the delay requested by the server if any
+    Retry(Option<Duration>),
+    /// The listens were refused and should not be sent again
+    Rejected(String),
+}
+
+async fn submit_listens(
+    session: &soup::Session,
+    url: &str,
+    token: &str,
+    listens: &[Value],
+) -> Result<(), SubmissionError> {
+    let post_data = json!({
+        "listen_type": match listens.len() {
+            1 => "single",
+            _ => "import",
+        },
+        "payload": listens,
+    })
+    .to_string();
+    let url = format!("{}/1/submit-listens", url.trim_end_matches('/'));
+
+    let message =
+        soup::Message::from_encoded_form("POST", &url, post_data.into()).map_err(|error| {
+            error!("Invalid scrobbling URL {}: {}", url, error);
+            SubmissionError::Retry(None)
+        })?;
+
+    let headers = message.request_headers().unwrap();
+    headers.set_content_type(Some("application/json"), None);
+    headers.append("Authorization", &format!("Token {}", token));
+
+    debug!("Submitting {} listens to {}", listens.len(), url);
+
+    let response = session
+        .send_and_read_future(&message, Priority::DEFAULT)
+        .await
+        .map_err(|error| {
+            warn!("Could not reach the scrobbling server: {}", error);
+            SubmissionError::Retry(None)
+        })?;
+    let response = String::from_utf8_lossy(&response[..]).to_string();
+
+    match message.status_code() {
+        200 => Ok(()),
+        400 => Err(SubmissionError::Rejected(response)),
+        429 => Err(SubmissionError::Retry(
+            message
+                .response_headers()
+                .and_then(|headers| headers.one("X-RateLimit-Reset-In"))
+                .and_then(|reset_in| reset_in.parse().ok())
+                .map(Duration::from_secs),
+        )),
+        401 => {
+            error!(
+                "{}",
+                gettext(
+                    "The scrobbling server refused the token, please check it in the preferences"
+                )
+            );
+            Err(SubmissionError::Retry(None))
+        }
+        status_code => {
+            warn!(
+                "Received response from the scrobbling server: {} {:?}",
+                status_code, response
+            );
+            Err(SubmissionError::Retry(None))
+        }
+    }
+}
+
+/// Submit the oldest queued listens, removing them from the queue once they
+/// were either accepted or rejected for good. Returns the number of listens
+/// accepted.
+async fn submit_next_batch(
+    session: &soup::Session,
+    url: &str,
+    token: &str,
+    queue: &mut ListenQueue,
+) -> Result<usize, SubmissionError> {
+    let count = queue.next_batch().len();
+    let mut result = submit_listens(session, url, token, queue.next_batch())
+        .await
+        .map(|_| count);
+    let mut number_processed = match result {
+        Err(SubmissionError::Retry(_)) => 0,
+        _ => count,
+    };
+
+    // A single malformed listen gets the whole batch rejected, submit the
+    // listens one by one so that only the faulty ones are dropped
+
+    if count > 1 && matches!(result, Err(SubmissionError::Rejected(_))) {
+        let mut number_accepted = 0;
+        number_processed = 0;
+        result = Ok(0);
+
+        for listen in queue.next_batch() {
+            match submit_listens(session, url, token, std::slice::from_ref(listen)).await {
+                Ok(()) => number_accepted += 1,
+                Err(SubmissionError::Rejected(reason)) => {
+                    error!(
+                        "{} {} ({})",
+                        gettext("The scrobbling server rejected the listen:"),
+                        listen,
+                        reason
+                    );
+                }
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+            number_processed += 1;
+        }
+        if result.is_ok() {
+            result = Ok(number_accepted);
+        }
+    }
+
+    if number_processed > 0
+        && let Err(error) = queue.remove_first(number_processed)
+    {
+        error!(
+            "{} {}",
+            gettext("Could not update the scrobbling queue:"),
+            error
+        );
+    }
+
+    result
+}
+
+/// Receive the listens of the recognized songs and submit them, along with
+/// the ones queued by previous runs, as long as a token is set in the
+/// preferences.
+pub async fn scrobbling_task(
+    scrobbling_rx: async_channel::Receiver<ScrobblingMessage>,
+    preferences_interface: Arc<Mutex<PreferencesInterface>>,
+) {
+    let mut queue = match obtain_scrobbling_queue_path().and_then(|path| ListenQueue::open(&path)) {
+        Ok(queue) => queue,
+        Err(error) => {
+            error!(
+                "{} {}",
+                gettext("Could not open the scrobbling queue:"),
+                error
+            );
+            return;
+        }
+    };
+    let session = new_session();
+
+    let mut retry_delay: Option<Duration> = None;
+    let mut next_attempt = Instant::now();
+
+    loop {
+        // Wait for a new listen, or for the next attempt when some are
+        // still queued
+
+        let message = match queue.is_empty() {
+            true => Some(scrobbling_rx.recv().await),
+            false => glib::future_with_timeout(
+                next_attempt.saturating_duration_since(Instant::now()),
+                scrobbling_rx.recv(),
+            )
+            .await
+            .ok(),
+        };
+
+        let (url, token) = {
+            let preferences = &preferences_interface.lock().unwrap().preferences;
+            (
+                preferences
+                    .listenbrainz_url
+                    .clone()
+                    .filter(|url| !url.is_empty())
+                    .unwrap_or(DEFAULT_LISTENBRAINZ_URL.to_string()),
+                preferences
+                    .listenbrainz_token
+                    .clone()
+                    .filter(|token| !token.is_empty()),
+            )
+        };
+
+        match message {
+            Some(Ok(ScrobblingMessage::SubmitListen(listen))) => {
+                if token.is_none() {
+                    continue;
+                }
+                if let Err(error) = queue.push(listen) {
+                    error!("{} {}", gettext("Could not queue the listen:"), error);
+                }
+            }
+            Some(Err(_)) => break,
+            None => {}
+        }
+
+        if queue.is_empty() || Instant::now() < next_attempt {
+            continue;
+        }
+
+        let Some(token) = token else {
+            // Keep the listens queued by previous runs until scrobbling is
+            // enabled again
+            next_attempt = Instant::now() + MIN_RETRY_DELAY;
+            continue;
+        };
+
+        match submit_next_batch(&session, &url, &token, &mut queue).await {
+            Ok(number_accepted) => {
+                info!("Submitted {} listens to {}", number_accepted, url);
+                retry_delay = None;
+            }
+            Err(SubmissionError::Rejected(reason)) => {
+                error!(
+                    "{} {}",
+                    gettext("The scrobbling server rejected the listens:"),
+                    reason
+                );
+            }
+            Err(SubmissionError::Retry(requested_delay)) => {
+                let delay = next_retry_delay(retry_delay);
+                retry_delay = Some(delay);
+                let delay = requested_delay.unwrap_or(delay);
+
+                warn!(
+                    "{} {} ({} {})",
+                    gettext("Could not submit the listens, next attempt in seconds:"),
+                    delay.as_secs(),
+                    queue.len(),
+                    gettext("queued")
+                );
+                next_attempt = Instant::now() + delay;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_listen_queue() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("scrobbling_queue.jsonl");
+
+    let mut queue = ListenQueue::open(&path).unwrap();
+    assert!(queue.is_empty());
+    for listened_at in 0..(MAX_LISTENS_PER_REQUEST + 2) {
+        queue.push(json!({ "listened_at": listened_at })).unwrap();
+    }
+    assert_eq!(queue.next_batch().len(), MAX_LISTENS_PER_REQUEST);
+
+    queue.remove_first(MAX_LISTENS_PER_REQUEST).unwrap();
+
+    // The remaining listens are found again after a restart
+    let queue = ListenQueue::open(&path).unwrap();
+    assert_eq!(queue.len(), 2);
+    assert_eq!(
+        queue.next_batch()[0]["listened_at"],
+        MAX_LISTENS_PER_REQUEST
+    );
+
+    assert_eq!(next_retry_delay(None), MIN_RETRY_DELAY);
+    assert_eq!(next_retry_delay(Some(MIN_RETRY_DELAY)), MIN_RETRY_DELAY * 2);
+    assert_eq!(next_retry_delay(Some(MAX_RETRY_DELAY)), MAX_RETRY_DELAY);
+}
+
+#[test]
+fn test_submit_listens() {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    let directory = tempfile::tempdir().unwrap();
+    let mut queue = ListenQueue::open(&directory.path().join("scrobbling_queue.jsonl")).unwrap();
+
+    // A local stand-in for the ListenBrainz API, answering with the status
+    // set by the test
+    let context = glib::MainContext::new();
+    context
+        .with_thread_default(|| {
+            let status_code = Rc::new(Cell::new(200));
+            let authorizations: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(vec![]));
+
+            let server = soup::Server::builder().build();
+            let (handler_status_code, handler_authorizations) =
+                (status_code.clone(), authorizations.clone());
+            server.add_handler(
+                Some("/1/submit-listens"),
+                move |_server, message, _path, _query| {
+                    if let Some(authorization) = message
+                        .request_headers()
+                        .and_then(|headers| headers.one("Authorization"))
+                    {
+                        handler_authorizations
+                            .borrow_mut()
+                            .push(authorization.to_string());
+                    }
+                    if handler_status_code.get() == 429 {
+                        message
+                            .response_headers()
+                            .unwrap()
+                            .append("X-RateLimit-Reset-In", "7");
+                    }
+                    message.set_response(
+                        Some("application/json"),
+                        soup::MemoryUse::Copy,
+                        br#"{"status": "ok"}"#,
+                    );
+                    // Reject any request containing a malformed listen
+                    let is_malformed = message
+                        .request_body()
+                        .and_then(|body| body.flatten())
+                        .is_some_and(|body| body.windows(5).any(|window| window == br#""bad""#));
+                    message.set_status(
+                        match is_malformed {
+                            true => 400,
+                            false => handler_status_code.get(),
+                        },
+                        None,
+                    );
+                },
+            );
+            server
+                .listen_local(0, soup::ServerListenOptions::IPV4_ONLY)
+                .unwrap();
+            let url = format!("http://127.0.0.1:{}/", server.uris()[0].port());
+
+            let session = new_session();
+
+            context.block_on(async {
+                for listened_at in 0..3 {
+                    queue.push(json!({ "listened_at": listened_at })).unwrap();
+                }
+
+                // Rate-limited: the listens stay queued, and the delay
+                // requested by the server is honoured
+                status_code.set(429);
+                assert!(matches!(
+                    submit_next_batch(&session, &url, "secret", &mut queue).await,
+                    Err(SubmissionError::Retry(Some(delay))) if delay == Duration::from_secs(7)
+                ));
+                assert_eq!(queue.len(), 3);
+
+                // Accepted: the queue is drained
+                status_code.set(200);
+                assert!(matches!(
+                    submit_next_batch(&session, &url, "secret", &mut queue).await,
+                    Ok(3)
+                ));
+                assert!(queue.is_empty());
+
+                // Rejected: the listens are dropped rather than retried
+                queue.push(json!({ "listened_at": 3 })).unwrap();
+                status_code.set(400);
+                assert!(matches!(
+                    submit_next_batch(&session, &url, "secret", &mut queue).await,
+                    Err(SubmissionError::Rejected(_))
+                ));
+                assert!(queue.is_empty());
+
+                // A malformed listen within a batch: the batch is submitted
+                // again one listen at a time, and only that one is dropped
+                status_code.set(200);
+                queue.push(json!({ "listened_at": 4 })).unwrap();
+                queue
+                    .push(json!({ "listened_at": 5, "bad": true }))
+                    .unwrap();
+                queue.push(json!({ "listened_at": 6 })).unwrap();
+                assert!(matches!(
+                    submit_next_batch(&session, &url, "secret", &mut queue).await,
+                    Ok(2)
+                ));
+                assert!(queue.is_empty());
+            });
+
+            assert_eq!(authorizations.borrow().len(), 7);
+            assert!(
+                authorizations
+                    .borrow()
+                    .iter()
+                    .all(|authorization| authorization == "Token secret")
+            );
+        })
+        .unwrap();
+
+    // The drained queue is also empty after a restart
+    assert!(
+        ListenQueue::open(&directory.path().join("scrobbling_queue.jsonl"))
+            .unwrap()
+            .is_empty()
+    );
+}
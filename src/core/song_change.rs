@@ -0,0 +1,138 @@
+//! Tells whether the song recorded from the microphone changed since the
+//! last recognized one, by comparing their fingerprints locally, so that no
+//! request is made while the same song keeps playing.
+//!
+//! The frequency peaks of the last seconds of audio are paired and hashed
+//! like in the local database, and looked up among those of the signature
+//! of the last recognized song, wherever they are in time. The share of
+//! hashes found measures how similar the audio is to that song.
+
+use log::debug;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::core::fingerprinting::local_database::hash_peak_pairs;
+use crate::core::fingerprinting::signature_format::DecodedSignature;
+use crate::core::preferences::Preferences;
+
+/// The length of the most recent audio compared to the last recognized
+/// song, in seconds. As long as it is shorter than the request interval, it
+/// does not overlap with the audio that was recognized.
+pub const COMPARISON_SECS: usize = 4;
+
+pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.2;
+pub const DEFAULT_MAX_AGE_SECS: u64 = 120;
+
+fn hash_set(signature: &DecodedSignature) -> HashSet<u32> {
+    hash_peak_pairs(signature)
+        .into_iter()
+        .map(|(hash, _)| hash)
+        .collect()
+}
+
+/// The share of the peak pair hashes of a signature found in a reference.
+fn similarity(reference_hashes: &HashSet<u32>, signature: &DecodedSignature) -> f32 {
+    let hashes = hash_peak_pairs(signature);
+    if hashes.is_empty() {
+        return 0.0;
+    }
+
+    let number_found = hashes
+        .iter()
+        .filter(|(hash, _)| reference_hashes.contains(hash))
+        .count();
+
+    number_found as f32 / hashes.len() as f32
+}
+
+struct Reference {
+    hashes: HashSet<u32>,
+    recognized_at: Instant,
+}
+
+#[derive(Default)]
+pub struct SongChangeDetector {
+    reference: Option<Reference>, // The last recognized song
+}
+
+impl SongChangeDetector {
+    /// Remember the signature of a recognized song.
+    pub fn set_reference(&mut self, signature: &DecodedSignature) {
+        self.reference = Some(Reference {
+            hashes: hash_set(signature),
+            recognized_at: Instant::now(),
+        });
+    }
+
+    /// Forget the last recognized song, when the last request didn't
+    /// match any.
+    pub fn clear_reference(&mut self) {
+        self.reference = None;
+    }
+
+    /// Whether the last seconds of audio still sound like the last
+    /// recognized song, which was recognized recently enough.
+    pub fn is_same_song(
+        &self,
+        latest_signature: &DecodedSignature,
+        preferences: &Preferences,
+    ) -> bool {
+        let threshold = preferences
+            .song_change_threshold
+            .unwrap_or(DEFAULT_SIMILARITY_THRESHOLD);
+        let max_age = Duration::from_secs(
+            preferences
+                .song_change_max_age_secs
+                .unwrap_or(DEFAULT_MAX_AGE_SECS),
+        );
+
+        match self.reference {
+            Some(ref reference) if reference.recognized_at.elapsed() < max_age => {
+                let similarity = similarity(&reference.hashes, latest_signature);
+                debug!(
+                    "Similarity with the last recognized song: {:.2}",
+                    similarity
+                );
+                similarity > threshold
+            }
+            _ => false,
+        }
+    }
+}
+
+#[test]
+fn test_similarity() {
+    use crate::core::fingerprinting::signature_format::FrequencyPeak;
+
+    // Peaks following a pseudo-random melody
+    let signature = |seed: u32, first_fft_pass_number: u32| {
+        let mut state = seed;
+        let mut signature = DecodedSignature {
+            sample_rate_hz: 16000,
+            number_samples: 16000 * COMPARISON_SECS as u32,
+            frequency_band_to_sound_peaks: Default::default(),
+        };
+        for fft_pass_number in (0..500).step_by(10) {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            signature.frequency_band_to_sound_peaks[1].push(FrequencyPeak {
+                fft_pass_number: first_fft_pass_number + fft_pass_number,
+                peak_magnitude: 1000,
+                corrected_peak_frequency_bin: ((state >> 20) as u16 % 200 + 50) << 6,
+            });
+        }
+        signature
+    };
+
+    let reference_hashes = hash_set(&signature(1, 0));
+    assert_eq!(similarity(&reference_hashes, &signature(1, 100)), 1.0);
+    assert!(similarity(&reference_hashes, &signature(2, 0)) < DEFAULT_SIMILARITY_THRESHOLD);
+
+    let mut detector = SongChangeDetector::default();
+    let preferences = Preferences::new();
+    assert!(!detector.is_same_song(&signature(1, 0), &preferences));
+    detector.set_reference(&signature(1, 0));
+    assert!(detector.is_same_song(&signature(1, 0), &preferences));
+    assert!(!detector.is_same_song(&signature(2, 0), &preferences));
+    detector.clear_reference();
+    assert!(!detector.is_same_song(&signature(1, 0), &preferences));
+}
@@ -1,9 +1,14 @@
 //! This module contains code used from message-based communication between threads.
 
+use crate::core::audio_activity::AudioActivity;
 use crate::core::fingerprinting::signature_format::DecodedSignature;
 #[cfg(feature = "gui")]
 use crate::core::preferences::Preferences;
+use crate::core::recognition_backends::shazam_response::StreamingLink;
+use crate::core::request_scheduler::{RecognitionOutcome, ScheduleStatus};
+use crate::core::webhooks::WebhookEvent;
 
+use std::path::PathBuf;
 use std::thread;
 
 pub fn spawn_big_thread<F, T>(argument: F)
@@ -30,10 +35,39 @@ pub struct SongRecognizedMessage {
     pub release_year: Option<String>,
     pub genre: Option<String>,
 
+    // Extra metadata, when provided by the recognition backend:
+    pub isrc: Option<String>,
+    pub label: Option<String>,
+    pub match_offset_secs: Option<f64>,
+    pub frequency_skew: Option<f64>,
+    pub time_skew: Option<f64>,
+    pub lyrics: Vec<String>,
+    pub streaming_links: Vec<StreamingLink>,
+    pub related_tracks_url: Option<String>,
+
     pub shazam_json: String,
+
+    pub capture_path: Option<PathBuf>, // The archived audio the song was recognized from
 }
 
-#[derive(Debug)]
+impl SongRecognizedMessage {
+    /// The main fields, as exposed to the clients of the daemon and of the
+    /// now playing server.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "artist_name": self.artist_name,
+            "song_name": self.song_name,
+            "album_name": self.album_name,
+            "track_key": self.track_key,
+            "release_year": self.release_year,
+            "genre": self.genre,
+            "isrc": self.isrc,
+            "label": self.label,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct DeviceListItem {
     pub inner_name: String,
     pub display_name: String,
@@ -61,7 +95,21 @@ pub enum GUIMessage {
     AppendToLog(String),
     MicrophoneRecording,
     MicrophoneVolumePercent(f32),
+    MicrophoneAudioActivity(AudioActivity), // Sent when it changes
+    RequestSchedule(ScheduleStatus),        // Sent every second while recording
     SongRecognized(Box<SongRecognizedMessage>),
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    RemoteControl(ControlRequest),
+}
+
+// Requests received through the D-Bus control interface
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+#[derive(Debug)]
+pub enum ControlRequest {
+    StartRecognition(Option<String>), // The audio device name, or the current one
+    StopRecognition,
+    RecognizeFile(String),
+    SelectDevice(String),
 }
 
 pub enum MicrophoneMessage {
@@ -69,14 +117,35 @@ pub enum MicrophoneMessage {
     MicrophoneRecordSetDevice(String), // The argument is the audio device name (with an initialization delay)
     RefreshDevices,
     MicrophoneRecordStop,
-    ProcessingDone,
+    ProcessingDone(RecognitionOutcome),
 }
 
 pub enum ProcessingMessage {
     ProcessAudioFile(String),
-    ProcessAudioSamples(Vec<f32>), // Prefer to use heap across threads to avoid stack overflow
+    ProcessMicrophoneSamples(Vec<f32>), // 16 KHz mono samples, fingerprinted as they come
+    // Recognize the last windows of fingerprinted samples, see capture_window.rs
+    RecognizeMicrophoneSamples {
+        archived_samples: Option<Vec<f32>>, // The audio of the windows, when the capture archive is enabled
+    },
+    ResetMicrophoneSamples, // Sent when (re)starting the recording
 }
 
 pub enum HTTPMessage {
-    RecognizeSignature(Box<DecodedSignature>),
+    RecognizeSignatures(Vec<DecodedSignature>), // One per window, voted on when several
+    RecognizeMicrophoneSignatures {
+        signatures: Vec<DecodedSignature>, // The most recent window first
+        latest_signature: Box<DecodedSignature>, // The last seconds, compared to the last recognized song
+        archived_samples: Option<Vec<f32>>, // Saved when a request is made, see capture_archive.rs
+    },
+}
+
+pub enum ScrobblingMessage {
+    SubmitListen(serde_json::Value), // A listen in the ListenBrainz format
+}
+
+pub enum WebhookMessage {
+    SongRecognized {
+        event: WebhookEvent,
+        is_favorite: bool,
+    },
 }
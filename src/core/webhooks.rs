@@ -0,0 +1,334 @@
+//! Notification of the recognized songs to the webhooks listed in the
+//! preferences file as "[[webhooks]]" tables (see the README). Failed
+//! deliveries are retried a few times.
+
+use base64::Engine;
+use chrono::{DateTime, Local, SecondsFormat};
+use gettextrs::gettext;
+use glib::source::Priority;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use soup::prelude::SessionExt;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::core::preferences::PreferencesInterface;
+use crate::core::recognition_backends::backend::new_session;
+use crate::core::thread_messages::{SongRecognizedMessage, WebhookMessage};
+
+const DEFAULT_TIMEOUT_SECS: u32 = 10;
+const MAX_ATTEMPTS: u32 = 5;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WebhookSettings {
+    pub url: String,
+    /// A JSON document whose strings may contain the placeholders "{artist}",
+    /// "{title}", "{song_name}", "{album}", "{track_key}", "{release_year}",
+    /// "{genre}", "{isrc}", "{label}", "{recognized_at}", "{device}",
+    /// "{shazam_url}" and "{cover_base64}". By default, all the fields are
+    /// sent.
+    pub template: Option<String>,
+    /// Send the cover art, encoded in base64
+    pub include_cover: Option<bool>,
+    /// Skip the recognitions of the same track as the previous one
+    pub only_new_tracks: Option<bool>,
+    pub only_favorites: Option<bool>,
+    pub timeout_secs: Option<u32>,
+}
+
+/// The fields of a recognized song made available to the payloads.
+pub struct WebhookEvent {
+    fields: BTreeMap<&'static str, String>,
+    cover_image: Option<Vec<u8>>,
+}
+
+impl WebhookEvent {
+    pub fn new(
+        message: &SongRecognizedMessage,
+        recognized_at: DateTime<Local>,
+        device_name: Option<&str>,
+    ) -> Self {
+        let shazam_url = serde_json::from_str::<Value>(&message.shazam_json)
+            .ok()
+            .and_then(|shazam_json| shazam_json["track"]["url"].as_str().map(str::to_string));
+
+        let fields = BTreeMap::from([
+            ("artist", message.artist_name.clone()),
+            ("title", message.song_name.clone()),
+            (
+                "song_name",
+                format!("{} - {}", message.artist_name, message.song_name),
+            ),
+            ("album", message.album_name.clone().unwrap_or_default()),
+            ("track_key", message.track_key.clone()),
+            (
+                "release_year",
+                message.release_year.clone().unwrap_or_default(),
+            ),
+            ("genre", message.genre.clone().unwrap_or_default()),
+            ("isrc", message.isrc.clone().unwrap_or_default()),
+            ("label", message.label.clone().unwrap_or_default()),
+            (
+                "recognized_at",
+                recognized_at.to_rfc3339_opts(SecondsFormat::Secs, false),
+            ),
+            ("device", device_name.unwrap_or_default().to_string()),
+            ("shazam_url", shazam_url.unwrap_or_default()),
+        ]);
+
+        WebhookEvent {
+            fields,
+            cover_image: message.cover_image.clone(),
+        }
+    }
+
+    fn payload(&self, webhook: &WebhookSettings) -> Result<Value, Box<dyn Error>> {
+        let mut fields = self.fields.clone();
+        fields.insert(
+            "cover_base64",
+            match (webhook.include_cover, &self.cover_image) {
+                (Some(true), Some(cover_image)) => {
+                    base64::prelude::BASE64_STANDARD.encode(cover_image)
+                }
+                _ => String::new(),
+            },
+        );
+        render_payload(webhook.template.as_deref(), &fields)
+    }
+}
+
+fn fill_placeholders(value: &mut Value, fields: &BTreeMap<&'static str, String>) {
+    match value {
+        Value::String(string) => {
+            // In a single pass, so that the values are never substituted
+            // again
+            let mut output = String::new();
+            let mut rest = string.as_str();
+            while let Some(start) = rest.find('{') {
+                output += &rest[..start];
+                rest = &rest[start..];
+                match rest
+                    .find('}')
+                    .and_then(|end| Some((end, fields.get(&rest[1..end])?)))
+                {
+                    Some((end, field)) => {
+                        output += field;
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        output.push('{');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            output += rest;
+            *string = output;
+        }
+        Value::Array(values) => {
+            for value in values {
+                fill_placeholders(value, fields);
+            }
+        }
+        Value::Object(object) => {
+            for value in object.values_mut() {
+                fill_placeholders(value, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Fill the template with the fields of the event. Placeholders are
+/// substituted within the strings of the parsed template, so that the values
+/// are always correctly escaped.
+pub fn render_payload(
+    template: Option<&str>,
+    fields: &BTreeMap<&'static str, String>,
+) -> Result<Value, Box<dyn Error>> {
+    let mut payload = match template {
+        Some(template) => serde_json::from_str(template)?,
+        None => {
+            let mut payload = Map::new();
+            payload.insert("event".to_string(), "song_recognized".into());
+            for (name, field) in fields {
+                if !field.is_empty() || *name != "cover_base64" {
+                    payload.insert(name.to_string(), format!("{{{}}}", name).into());
+                }
+            }
+            Value::Object(payload)
+        }
+    };
+    fill_placeholders(&mut payload, fields);
+    Ok(payload)
+}
+
+struct PendingDelivery {
+    url: String,
+    payload: Value,
+    timeout_secs: u32,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+async fn deliver(delivery: &PendingDelivery) -> Result<u32, Box<dyn Error>> {
+    let session = new_session();
+    session.set_timeout(delivery.timeout_secs);
+
+    let message = soup::Message::from_encoded_form(
+        "POST",
+        &delivery.url,
+        delivery.payload.to_string().into(),
+    )?;
+    let headers = message.request_headers().unwrap();
+    headers.set_content_type(Some("application/json"), None);
+
+    session
+        .send_and_read_future(&message, Priority::DEFAULT)
+        .await?;
+
+    match message.status_code() {
+        status_code @ 200..=299 => Ok(status_code),
+        status_code => Err(format!("HTTP {}", status_code).into()),
+    }
+}
+
+/// Receive the recognized songs and deliver them to the webhooks set in the
+/// preferences.
+pub async fn webhook_task(
+    webhook_rx: async_channel::Receiver<WebhookMessage>,
+    preferences_interface: Arc<Mutex<PreferencesInterface>>,
+) {
+    let mut pending: Vec<PendingDelivery> = vec![];
+    let mut last_track_key: Option<String> = None;
+
+    loop {
+        // Wait for a new recognition, or for the next retry
+
+        let message = match pending.iter().map(|delivery| delivery.next_attempt).min() {
+            None => Some(webhook_rx.recv().await),
+            Some(next_attempt) => glib::future_with_timeout(
+                next_attempt.saturating_duration_since(Instant::now()),
+                webhook_rx.recv(),
+            )
+            .await
+            .ok(),
+        };
+
+        match message {
+            Some(Ok(WebhookMessage::SongRecognized { event, is_favorite })) => {
+                let track_key = event.fields["track_key"].clone();
+                let is_new_track = last_track_key.as_ref() != Some(&track_key);
+                last_track_key = Some(track_key);
+
+                let webhooks = preferences_interface
+                    .lock()
+                    .unwrap()
+                    .preferences
+                    .webhooks
+                    .clone()
+                    .unwrap_or_default();
+
+                for webhook in webhooks {
+                    if (webhook.only_new_tracks == Some(true) && !is_new_track)
+                        || (webhook.only_favorites == Some(true) && !is_favorite)
+                    {
+                        debug!("Skipping webhook {}", webhook.url);
+                        continue;
+                    }
+                    match event.payload(&webhook) {
+                        Ok(payload) => pending.push(PendingDelivery {
+                            url: webhook.url,
+                            payload,
+                            timeout_secs: webhook.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS),
+                            attempts: 0,
+                            next_attempt: Instant::now(),
+                        }),
+                        Err(error) => {
+                            error!(
+                                "{} {}: {}",
+                                gettext("Invalid webhook template for"),
+                                webhook.url,
+                                error
+                            );
+                        }
+                    }
+                }
+            }
+            Some(Err(_)) => break,
+            None => {}
+        }
+
+        let now = Instant::now();
+        let (due, waiting): (Vec<PendingDelivery>, Vec<PendingDelivery>) = pending
+            .drain(..)
+            .partition(|delivery| delivery.next_attempt <= now);
+        pending = waiting;
+
+        for mut delivery in due {
+            match deliver(&delivery).await {
+                Ok(status_code) => {
+                    info!(
+                        "{} {} (HTTP {})",
+                        gettext("Webhook delivered:"),
+                        delivery.url,
+                        status_code
+                    );
+                }
+                Err(error) => {
+                    delivery.attempts += 1;
+                    if delivery.attempts >= MAX_ATTEMPTS {
+                        error!(
+                            "{} {} ({})",
+                            gettext("Giving up on webhook delivery:"),
+                            delivery.url,
+                            error
+                        );
+                        continue;
+                    }
+
+                    let delay = FIRST_RETRY_DELAY * 2_u32.pow(delivery.attempts - 1);
+                    warn!(
+                        "{} {} ({}), {} {}",
+                        gettext("Webhook delivery failed:"),
+                        delivery.url,
+                        error,
+                        gettext("next attempt in seconds:"),
+                        delay.as_secs()
+                    );
+                    delivery.next_attempt = Instant::now() + delay;
+                    pending.push(delivery);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_render_payload() {
+    let fields = BTreeMap::from([
+        ("artist", "Daft Punk {title}".to_string()),
+        ("title", "Around the \"World\"".to_string()),
+        ("cover_base64", String::new()),
+    ]);
+
+    let payload = render_payload(
+        Some(r#"{"text": "Now playing: {artist} - {title}", "tags": ["{artist}"], "volume": 3}"#),
+        &fields,
+    )
+    .unwrap();
+    assert_eq!(
+        payload.to_string(),
+        r#"{"tags":["Daft Punk {title}"],"text":"Now playing: Daft Punk {title} - Around the \"World\"","volume":3}"#
+    );
+
+    let payload = render_payload(None, &fields).unwrap();
+    assert_eq!(payload["event"], "song_recognized");
+    assert_eq!(payload["artist"], "Daft Punk {title}");
+    assert!(payload.get("cover_base64").is_none());
+
+    assert!(render_payload(Some("{not json"), &fields).is_err());
+}
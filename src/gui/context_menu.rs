@@ -1,4 +1,5 @@
 use gdk::{Key, ModifierType, Rectangle};
+use gettextrs::gettext;
 use gio::prelude::*;
 use glib::Propagation;
 use gtk::glib::clone;
@@ -14,6 +15,7 @@ use std::sync::Mutex;
 use crate::gui::song_history_interface::FavoritesInterface;
 
 use crate::core::preferences::PreferencesInterface;
+use crate::core::thread_messages::{GUIMessage, ProcessingMessage};
 use crate::gui::history_entry::HistoryEntry;
 use crate::gui::song_history_interface::{RecognitionHistoryInterface, SongRecordInterface};
 
@@ -163,6 +165,8 @@ impl ContextMenuUtil {
         history_interface: Rc<RefCell<RecognitionHistoryInterface>>,
         favorites_interface: Rc<RefCell<FavoritesInterface>>,
         preferences_interface: Arc<Mutex<PreferencesInterface>>,
+        processing_tx: async_channel::Sender<ProcessingMessage>,
+        gui_tx: async_channel::Sender<GUIMessage>,
     ) {
         let item = ctx_selected_item.clone();
         let action_copy_artist_track = gio::ActionEntry::builder("copy-artist-track")
@@ -298,6 +302,69 @@ impl ContextMenuUtil {
             })
             .build();
 
+        // The audio archived when the song was recognized, see
+        // capture_archive.rs
+
+        let item = ctx_selected_item.clone();
+        let history = history_interface.clone();
+        let gui_tx_2 = gui_tx.clone();
+        let action_play_capture = gio::ActionEntry::builder("play-capture")
+            .activate(clone!(
+                #[weak]
+                window,
+                move |_, _, _| {
+                    if let Some(entry) = &*item.borrow() {
+                        let Some(capture_path) = history
+                            .borrow()
+                            .capture_path(&entry.get_song_history_record())
+                        else {
+                            gui_tx_2
+                                .try_send(GUIMessage::ErrorMessage(gettext(
+                                    "No recorded audio was kept for this recognition",
+                                )))
+                                .unwrap();
+                            return;
+                        };
+
+                        glib::spawn_future_local(async move {
+                            info!("Playing: {}", capture_path.display());
+                            if let Err(err) =
+                                gtk::FileLauncher::new(Some(&gio::File::for_path(&capture_path)))
+                                    .launch_future(Some(&window))
+                                    .await
+                            {
+                                error!("Could not play {}: {:?}", capture_path.display(), err);
+                            }
+                        });
+                    }
+                }
+            ))
+            .build();
+
+        let item = ctx_selected_item.clone();
+        let history = history_interface.clone();
+        let action_recognize_capture = gio::ActionEntry::builder("recognize-capture")
+            .activate(move |_, _, _| {
+                if let Some(entry) = &*item.borrow() {
+                    match history
+                        .borrow()
+                        .capture_path(&entry.get_song_history_record())
+                    {
+                        Some(capture_path) => processing_tx
+                            .try_send(ProcessingMessage::ProcessAudioFile(
+                                capture_path.to_string_lossy().into_owned(),
+                            ))
+                            .unwrap(),
+                        None => gui_tx
+                            .try_send(GUIMessage::ErrorMessage(gettext(
+                                "No recorded audio was kept for this recognition",
+                            )))
+                            .unwrap(),
+                    }
+                }
+            })
+            .build();
+
         let actions = gio::SimpleActionGroup::new();
         actions.add_action_entries([
             action_copy_artist_track,
@@ -308,6 +375,8 @@ impl ContextMenuUtil {
             action_remove_history,
             action_remove_favorites,
             action_search_youtube,
+            action_play_capture,
+            action_recognize_capture,
         ]);
         window.insert_action_group("history-menu", Some(&actions));
     }
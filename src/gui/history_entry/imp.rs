@@ -21,6 +21,10 @@ pub struct HistoryEntry {
     genre: RefCell<Option<String>>,
     #[property(construct_only, get)]
     recognition_date: RefCell<String>,
+    #[property(construct_only, get)]
+    isrc: RefCell<Option<String>>,
+    #[property(construct_only, get)]
+    label: RefCell<Option<String>>,
 }
 
 // The central trait for subclassing a GObject
@@ -15,6 +15,8 @@ impl HistoryEntry {
             .property("release_year", &song.release_year)
             .property("genre", &song.genre)
             .property("recognition_date", &song.recognition_date)
+            .property("isrc", &song.isrc)
+            .property("label", &song.label)
             .build()
 
         /*
@@ -43,6 +45,8 @@ impl HistoryEntry {
             release_year: self.release_year(),
             genre: self.genre(),
             recognition_date: self.recognition_date(),
+            isrc: self.isrc(),
+            label: self.label(),
         }
     }
 
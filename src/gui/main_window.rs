@@ -11,29 +11,39 @@ use std::error::Error;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
+use crate::core::capture_window::CaptureSettings;
 use crate::core::http_task::http_task;
 use crate::core::logging::Logging;
 use crate::core::microphone_thread::microphone_thread;
+use crate::core::now_playing_server::NowPlayingServer;
 use crate::core::processing_thread::processing_thread;
+use crate::core::scrobbling::{DEFAULT_LISTENBRAINZ_URL, listen_from_song, scrobbling_task};
 use crate::core::thread_messages::{GUIMessage::*, *};
+use crate::core::webhooks::{WebhookEvent, webhook_task};
 
 use crate::gui::song_history_interface::FavoritesInterface;
 
 use crate::gui::song_history_interface::{RecognitionHistoryInterface, SongRecordInterface};
+#[cfg(all(target_os = "linux", feature = "dbus"))]
+use crate::plugins::dbus_control::ControlService;
 #[cfg(target_os = "linux")]
 use crate::plugins::ksni::SystrayInterface;
 #[cfg(all(target_os = "linux", feature = "mpris"))]
 use crate::plugins::mpris_player::{get_player, update_song};
 use crate::utils::csv_song_history::SongHistoryRecord;
 use crate::utils::filesystem_operations::{
-    clear_cache, obtain_favorites_csv_path, obtain_recognition_history_csv_path,
+    clear_cache, obtain_favorites_csv_path, obtain_history_database_path,
+    obtain_recognition_history_csv_path,
 };
+use crate::utils::history_database::{RecognitionDetails, store_cover_art};
+use crate::utils::playlist_formats::PlaylistFormat;
 
 use crate::core::preferences::{Preferences, PreferencesInterface};
 
 use crate::gui::context_menu::ContextMenuUtil;
 use crate::gui::history_entry::HistoryEntry;
 use crate::gui::listed_device::ListedDevice;
+use crate::gui::statistics_page::StatisticsPage;
 
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
@@ -45,7 +55,13 @@ pub fn gui_main(
     enable_mpris_cli: bool,
     enable_pipewire_cli: bool,
 ) -> Result<(), Box<dyn Error>> {
-    let app = App::new(log_object);
+    let app = App::new(log_object).inspect_err(|error_info| {
+        error!(
+            "{} {}",
+            gettext("Error when opening the song history database:"),
+            error_info
+        );
+    })?;
     app.run(recording, enable_mpris_cli, enable_pipewire_cli, input_file);
 
     Ok(())
@@ -73,15 +89,21 @@ struct App {
     processing_rx: async_channel::Receiver<ProcessingMessage>,
     http_tx: async_channel::Sender<HTTPMessage>,
     http_rx: async_channel::Receiver<HTTPMessage>,
+    scrobbling_tx: async_channel::Sender<ScrobblingMessage>,
+    scrobbling_rx: async_channel::Receiver<ScrobblingMessage>,
+    webhook_tx: async_channel::Sender<WebhookMessage>,
+    webhook_rx: async_channel::Receiver<WebhookMessage>,
 }
 
 // #[gtk::template_callbacks(functions)]
 impl App {
-    fn new(log_object: Logging) -> App {
+    fn new(log_object: Logging) -> Result<App, Box<dyn Error>> {
         let (gui_tx, gui_rx) = async_channel::unbounded();
         let (microphone_tx, microphone_rx) = async_channel::unbounded();
         let (processing_tx, processing_rx) = async_channel::unbounded();
         let (http_tx, http_rx) = async_channel::unbounded();
+        let (scrobbling_tx, scrobbling_rx) = async_channel::unbounded();
+        let (webhook_tx, webhook_rx) = async_channel::unbounded();
 
         log_object.connect_to_gui_logger(gui_tx.clone());
 
@@ -93,19 +115,16 @@ impl App {
             Rc::new(RefCell::new(None));
 
         let history_list_store: gio::ListStore = gio::ListStore::new::<HistoryEntry>();
-        let song_history_interface = Rc::new(RefCell::new(
-            RecognitionHistoryInterface::new(
-                history_list_store.clone(),
-                obtain_recognition_history_csv_path,
-            )
-            .unwrap(),
-        ));
+        let song_history_interface = Rc::new(RefCell::new(RecognitionHistoryInterface::new(
+            history_list_store.clone(),
+            obtain_history_database_path,
+        )?));
 
         let favorites_list_store = gio::ListStore::new::<HistoryEntry>();
-        let favorites_interface = Rc::new(RefCell::new(
-            FavoritesInterface::new(favorites_list_store.clone(), obtain_favorites_csv_path)
-                .unwrap(),
-        ));
+        let favorites_interface = Rc::new(RefCell::new(FavoritesInterface::new(
+            favorites_list_store.clone(),
+            obtain_history_database_path,
+        )?));
 
         let builder = gtk::Builder::new();
 
@@ -140,7 +159,13 @@ impl App {
         let request_interval_value: gtk::Adjustment = builder.object("interval_value").unwrap();
         request_interval_value.set_value(old_preferences.request_interval_secs_v3.unwrap() as f64);
 
-        App {
+        let capture_settings = CaptureSettings::from_preferences(&old_preferences);
+        let capture_window_value: gtk::Adjustment = builder.object("capture_window_value").unwrap();
+        capture_window_value.set_value(capture_settings.window_secs as f64);
+        let voting_windows_value: gtk::Adjustment = builder.object("voting_windows_value").unwrap();
+        voting_windows_value.set_value(capture_settings.number_windows as f64);
+
+        Ok(App {
             builder,
 
             song_history_interface,
@@ -163,7 +188,11 @@ impl App {
             processing_rx,
             http_tx,
             http_rx,
-        }
+            scrobbling_tx,
+            scrobbling_rx,
+            webhook_tx,
+            webhook_rx,
+        })
     }
 
     fn load_resources() {
@@ -278,6 +307,25 @@ impl App {
         }
     }
 
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    fn select_device(
+        adw_combo_row: &adw::ComboRow,
+        g_list_store: &gio::ListStore,
+        device_name: &str,
+    ) {
+        for position in 0..g_list_store.n_items() {
+            let device = g_list_store
+                .item(position)
+                .unwrap()
+                .downcast::<ListedDevice>()
+                .unwrap();
+            if device.inner_name() == device_name {
+                adw_combo_row.set_selected(position);
+                break;
+            }
+        }
+    }
+
     fn update_website_search_text(&self) {
         let search_row: adw::ActionRow = self.builder.object("search_youtube_row").unwrap();
         let search_name_row: adw::EntryRow = self.builder.object("search_engine_name").unwrap();
@@ -304,6 +352,24 @@ impl App {
         search_row.set_title(&label);
     }
 
+    fn update_scrobbling_preferences(&self) {
+        let url_row: adw::EntryRow = self.builder.object("listenbrainz_url").unwrap();
+        let token_row: adw::PasswordEntryRow = self.builder.object("listenbrainz_token").unwrap();
+
+        let preferences = &self.old_preferences;
+
+        url_row.set_text(
+            preferences
+                .listenbrainz_url
+                .as_deref()
+                .unwrap_or(DEFAULT_LISTENBRAINZ_URL),
+        );
+        token_row.set_text(preferences.listenbrainz_token.as_deref().unwrap_or(""));
+
+        let address_row: adw::EntryRow = self.builder.object("http_server_address").unwrap();
+        address_row.set_text(preferences.http_server_address.as_deref().unwrap_or(""));
+    }
+
     fn on_startup(
         &self,
         application: &adw::Application,
@@ -326,6 +392,7 @@ impl App {
         }
         self.setup_context_menus();
         self.update_website_search_text();
+        self.update_scrobbling_preferences();
         self.show_window(application);
     }
 
@@ -392,6 +459,8 @@ impl App {
             self.song_history_interface.clone(),
             self.favorites_interface.clone(),
             self.preferences_interface.clone(),
+            self.processing_tx.clone(),
+            self.gui_tx.clone(),
         );
 
         // See:
@@ -449,6 +518,74 @@ impl App {
             None
         });
 
+        let gui_tx = gui_tx_shared.clone();
+        let preferences = preferences_shared.clone();
+
+        builder_scope.add_callback("listenbrainz_url_changed", move |values| {
+            let entry_row = values[0].get::<adw::EntryRow>().unwrap();
+
+            let lock = preferences.lock().unwrap();
+            if lock
+                .preferences
+                .listenbrainz_url
+                .as_deref()
+                .unwrap_or(DEFAULT_LISTENBRAINZ_URL)
+                != entry_row.text().as_str()
+            {
+                let mut new_preference = Preferences::new();
+                new_preference.listenbrainz_url = Some(entry_row.text().to_string());
+                gui_tx
+                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .unwrap();
+            }
+
+            None
+        });
+
+        let gui_tx = gui_tx_shared.clone();
+        let preferences = preferences_shared.clone();
+
+        builder_scope.add_callback("listenbrainz_token_changed", move |values| {
+            let entry_row = values[0].get::<adw::PasswordEntryRow>().unwrap();
+
+            let lock = preferences.lock().unwrap();
+            if lock.preferences.listenbrainz_token.as_deref().unwrap_or("")
+                != entry_row.text().as_str()
+            {
+                let mut new_preference = Preferences::new();
+                new_preference.listenbrainz_token = Some(entry_row.text().to_string());
+                gui_tx
+                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .unwrap();
+            }
+
+            None
+        });
+
+        let gui_tx = gui_tx_shared.clone();
+        let preferences = preferences_shared.clone();
+
+        builder_scope.add_callback("http_server_address_changed", move |values| {
+            let entry_row = values[0].get::<adw::EntryRow>().unwrap();
+
+            let lock = preferences.lock().unwrap();
+            if lock
+                .preferences
+                .http_server_address
+                .as_deref()
+                .unwrap_or("")
+                != entry_row.text().as_str()
+            {
+                let mut new_preference = Preferences::new();
+                new_preference.http_server_address = Some(entry_row.text().to_string());
+                gui_tx
+                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .unwrap();
+            }
+
+            None
+        });
+
         let microphone_tx = microphone_tx_shared.clone();
         let builder = builder_shared.clone();
 
@@ -686,6 +823,32 @@ impl App {
             None
         });
 
+        let gui_tx = gui_tx_shared.clone();
+
+        builder_scope.add_callback("capture_window_changed", move |values| {
+            let adjustment = values[0].get::<gtk::Adjustment>().unwrap();
+            debug!("Capture window set to: {}", adjustment.value());
+            let mut new_preference = Preferences::new();
+            new_preference.capture_window_secs = Some(adjustment.value() as u64);
+            gui_tx
+                .try_send(GUIMessage::UpdatePreference(new_preference))
+                .unwrap();
+            None
+        });
+
+        let gui_tx = gui_tx_shared.clone();
+
+        builder_scope.add_callback("voting_windows_changed", move |values| {
+            let adjustment = values[0].get::<gtk::Adjustment>().unwrap();
+            debug!("Voting windows set to: {}", adjustment.value());
+            let mut new_preference = Preferences::new();
+            new_preference.voting_windows = Some(adjustment.value() as u64);
+            gui_tx
+                .try_send(GUIMessage::UpdatePreference(new_preference))
+                .unwrap();
+            None
+        });
+
         let builder = builder_shared;
 
         builder_scope.add_callback("about_dialog_closed", move |_values| {
@@ -727,16 +890,34 @@ impl App {
         let processing_rx = self.processing_rx.clone();
         let http_tx = self.http_tx.clone();
         let gui_tx = self.gui_tx.clone();
+        let preferences_interface = self.preferences_interface.clone();
         spawn_big_thread(move || {
-            processing_thread(processing_rx, http_tx, gui_tx);
+            processing_thread(processing_rx, http_tx, gui_tx, preferences_interface);
         });
 
         let http_rx = self.http_rx.clone();
         let gui_tx = self.gui_tx.clone();
         let microphone_tx = self.microphone_tx.clone();
-        glib::spawn_future_local(http_task(http_rx, gui_tx, microphone_tx));
+        let preferences_interface = self.preferences_interface.clone();
+        glib::spawn_future_local(http_task(
+            http_rx,
+            gui_tx,
+            microphone_tx,
+            preferences_interface,
+        ));
+
+        let scrobbling_rx = self.scrobbling_rx.clone();
+        let preferences_interface = self.preferences_interface.clone();
+        glib::spawn_future_local(scrobbling_task(scrobbling_rx, preferences_interface));
+
+        let webhook_rx = self.webhook_rx.clone();
+        let preferences_interface = self.preferences_interface.clone();
+        glib::spawn_future_local(webhook_task(webhook_rx, preferences_interface));
 
         let gui_rx = self.gui_rx.clone();
+        let scrobbling_tx = self.scrobbling_tx.clone();
+        let webhook_tx = self.webhook_tx.clone();
+        let favorites_interface = self.favorites_interface.clone();
         let preferences_interface_ptr = self.preferences_interface.clone();
 
         let old_device_name = self.old_preferences.current_device_name.clone();
@@ -747,6 +928,10 @@ impl App {
         #[cfg(all(target_os = "linux", feature = "mpris"))]
         let mpris_setting: adw::SwitchRow = self.builder.object("mpris_setting").unwrap();
 
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        let dbus_control_setting: adw::SwitchRow =
+            self.builder.object("dbus_control_setting").unwrap();
+
         let window: adw::ApplicationWindow = self.builder.object("main_window").unwrap();
         let adw_combo_row: adw::ComboRow = self.builder.object("audio_inputs").unwrap();
         let g_list_store: gio::ListStore = self.builder.object("audio_inputs_model").unwrap();
@@ -756,6 +941,9 @@ impl App {
         let spinner_row: adw::PreferencesRow = self.builder.object("spinner_row").unwrap();
         let volume_row: adw::PreferencesRow = self.builder.object("volume_row").unwrap();
         let volume_gauge: gtk::ProgressBar = self.builder.object("volume_gauge").unwrap();
+        let audio_activity_label: gtk::Label = self.builder.object("audio_activity_label").unwrap();
+        let request_schedule_label: gtk::Label =
+            self.builder.object("request_schedule_label").unwrap();
         let results_section: adw::PreferencesGroup =
             self.builder.object("results_section").unwrap();
         let no_network_message: gtk::Label = self.builder.object("no_network_message").unwrap();
@@ -770,6 +958,9 @@ impl App {
         #[cfg(all(target_os = "linux", feature = "mpris"))]
         mpris_setting.set_visible(true);
 
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        dbus_control_setting.set_visible(true);
+
         microphone_switch.set_active(set_recording);
 
         let song_history_interface = self.song_history_interface.clone();
@@ -777,7 +968,32 @@ impl App {
         let ctx_buffered_log = self.ctx_buffered_log.clone();
         let application = application.clone();
 
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        let (gui_tx, processing_tx) = (self.gui_tx.clone(), self.processing_tx.clone());
+
+        // Applied when restarting SongRec
+        let now_playing_server = match self.old_preferences.http_server_address.as_deref() {
+            Some(address) if !address.is_empty() => match NowPlayingServer::start(address) {
+                Ok(server) => Some(server),
+                Err(error) => {
+                    error!(
+                        "{} {}",
+                        gettext("Could not start the now playing server:"),
+                        error
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+
         glib::spawn_future_local(async move {
+            #[cfg(all(target_os = "linux", feature = "dbus"))]
+            let control_service = match _old_preferences.enable_dbus_control {
+                Some(false) => None,
+                _ => ControlService::start(gui_tx).await,
+            };
+
             #[cfg(all(target_os = "linux", feature = "mpris"))]
             let mut mpris_obj = {
                 let player = if _enable_mpris_cli && _old_preferences.enable_mpris_v2 != Some(false)
@@ -824,7 +1040,7 @@ impl App {
                         }
                     }
                 } else {
-                    if let MicrophoneVolumePercent(_) = gui_message {
+                    if let MicrophoneVolumePercent(_) | RequestSchedule(_) = gui_message {
                         trace!("Received GUI message: {:?}", gui_message);
                     } else if let SongRecognized(ref msg) = gui_message {
                         debug!(
@@ -837,6 +1053,14 @@ impl App {
                                 "track_key": msg.track_key,
                                 "release_year": msg.release_year,
                                 "genre": msg.genre,
+                                "isrc": msg.isrc,
+                                "label": msg.label,
+                                "match_offset_secs": msg.match_offset_secs,
+                                "frequency_skew": msg.frequency_skew,
+                                "time_skew": msg.time_skew,
+                                "lyrics": msg.lyrics,
+                                "streaming_links": msg.streaming_links,
+                                "related_tracks_url": msg.related_tracks_url,
                                 "shazam_json": msg.shazam_json,
                             })
                         );
@@ -979,6 +1203,20 @@ impl App {
                             if results_label.text().as_str() != song_name {
                                 results_label.set_label(&song_name);
 
+                                let details: Vec<String> = [
+                                    (gettext("Label:"), &message.label),
+                                    (gettext("ISRC:"), &message.isrc),
+                                ]
+                                .into_iter()
+                                .filter_map(|(title, value)| {
+                                    value.as_ref().map(|value| format!("{} {}", title, value))
+                                })
+                                .collect();
+                                results_label.set_tooltip_text(match details.is_empty() {
+                                    true => None,
+                                    false => Some(&details.join("\n")),
+                                });
+
                                 let notification =
                                     gio::Notification::new(&gettext("Song recognized"));
                                 notification.set_body(Some(&song_name));
@@ -1013,6 +1251,15 @@ impl App {
                                     update_song(player, &message, &mut last_cover_path).await;
                                 }
 
+                                #[cfg(all(target_os = "linux", feature = "dbus"))]
+                                if let Some(ref control_service) = control_service {
+                                    control_service.song_recognized(&message).await;
+                                }
+
+                                if let Some(ref now_playing_server) = now_playing_server {
+                                    now_playing_server.song_recognized(&message);
+                                }
+
                                 if preferences_interface_ptr
                                     .lock()
                                     .unwrap()
@@ -1024,6 +1271,49 @@ impl App {
                                         .send_notification(Some("recognized-song"), &notification);
                                 }
 
+                                scrobbling_tx
+                                    .try_send(ScrobblingMessage::SubmitListen(listen_from_song(
+                                        &message,
+                                        Local::now(),
+                                    )))
+                                    .unwrap();
+
+                                let device_name = preferences_interface_ptr
+                                    .lock()
+                                    .unwrap()
+                                    .preferences
+                                    .current_device_name
+                                    .clone();
+                                let webhook_event = WebhookEvent::new(
+                                    &message,
+                                    Local::now(),
+                                    device_name.as_deref(),
+                                );
+
+                                let details = RecognitionDetails {
+                                    artist_name: Some(message.artist_name.clone()),
+                                    device_name,
+                                    shazam_json: Some(message.shazam_json.clone()),
+                                    cover_path: message.cover_image.as_ref().and_then(
+                                        |cover_image| match store_cover_art(
+                                            &message.track_key,
+                                            cover_image,
+                                        ) {
+                                            Ok(cover_path) => Some(cover_path),
+                                            Err(error_info) => {
+                                                error!(
+                                                    "Could not save the cover art: {}",
+                                                    error_info
+                                                );
+                                                None
+                                            }
+                                        },
+                                    ),
+                                    capture_path: message.capture_path.as_ref().map(
+                                        |capture_path| capture_path.to_string_lossy().into_owned(),
+                                    ),
+                                };
+
                                 let new_entry = SongHistoryRecord {
                                     song_name,
                                     album: Some(message.album_name.unwrap_or_default()),
@@ -1031,8 +1321,19 @@ impl App {
                                     release_year: Some(message.release_year.unwrap_or_default()),
                                     genre: Some(message.genre.unwrap_or_default()),
                                     recognition_date: Local::now().format("%c").to_string(),
+                                    isrc: message.isrc,
+                                    label: message.label,
                                 };
 
+                                webhook_tx
+                                    .try_send(WebhookMessage::SongRecognized {
+                                        event: webhook_event,
+                                        is_favorite: favorites_interface
+                                            .borrow()
+                                            .is_favorite(new_entry.clone()),
+                                    })
+                                    .unwrap();
+
                                 if preferences_interface_ptr
                                     .lock()
                                     .unwrap()
@@ -1046,10 +1347,15 @@ impl App {
                                 }
                                 song_history_interface
                                     .borrow_mut()
-                                    .add_row_and_save(new_entry);
+                                    .add_recognition(new_entry, &details);
                             }
                         }
                         DevicesList(devices) => {
+                            #[cfg(all(target_os = "linux", feature = "dbus"))]
+                            if let Some(ref control_service) = control_service {
+                                control_service.set_devices(&devices);
+                            }
+
                             let mut initial_device_index: u32 = 0;
                             let mut initial_device: Option<ListedDevice> = None;
                             let mut found_monitor_device = false;
@@ -1108,6 +1414,8 @@ impl App {
                                 microphone_switch.is_active() || loopback_switch.is_active(),
                             );
                             volume_gauge.set_fraction(0.0);
+                            audio_activity_label.set_label("");
+                            request_schedule_label.set_label("");
                         }
 
                         MicrophoneVolumePercent(percent) => {
@@ -1115,6 +1423,14 @@ impl App {
                             volume_gauge.set_fraction((clamped_percent / 100.0) as f64);
                         }
 
+                        MicrophoneAudioActivity(audio_activity) => {
+                            audio_activity_label.set_label(&audio_activity.label());
+                        }
+
+                        RequestSchedule(status) => {
+                            request_schedule_label.set_label(&status.describe());
+                        }
+
                         WipeSongHistory => {
                             let dialog = adw::AlertDialog::builder()
                                 .body(gettext("Are you sure you want to wipe history?"))
@@ -1147,6 +1463,48 @@ impl App {
                             application.quit();
                         }
 
+                        #[cfg(all(target_os = "linux", feature = "dbus"))]
+                        RemoteControl(request) => match request {
+                            ControlRequest::StartRecognition(device_name) => {
+                                if let Some(device_name) = device_name {
+                                    Self::select_device(
+                                        &adw_combo_row,
+                                        &g_list_store,
+                                        &device_name,
+                                    );
+                                }
+
+                                // Turning a switch on triggers its callback,
+                                // which starts the recording
+
+                                if let Some(device) = adw_combo_row.selected_item() {
+                                    let device = device.downcast::<ListedDevice>().unwrap();
+                                    match device.is_monitor() {
+                                        true => loopback_switch.set_active(true),
+                                        false => microphone_switch.set_active(true),
+                                    }
+                                }
+                            }
+                            ControlRequest::SelectDevice(device_name) => {
+                                // Triggers the "input_device_switched"
+                                // callback, which restarts the recording
+                                // when needed
+                                Self::select_device(&adw_combo_row, &g_list_store, &device_name);
+                            }
+                            ControlRequest::StopRecognition => {
+                                microphone_switch.set_active(false);
+                                loopback_switch.set_active(false);
+                            }
+                            ControlRequest::RecognizeFile(file_path) => {
+                                recognize_file_row.set_sensitive(false);
+                                spinner_row.set_visible(true);
+
+                                processing_tx
+                                    .try_send(ProcessingMessage::ProcessAudioFile(file_path))
+                                    .unwrap();
+                            }
+                        },
+
                         _ => {
                             debug!("(parsing unimplemented yet): {:?}", gui_message);
                         }
@@ -1296,8 +1654,20 @@ impl App {
             })
             .build();
 
+        let song_history_interface = self.song_history_interface.clone();
+
         let action_export_to_csv = gio::ActionEntry::builder("export-to-csv")
             .activate(move |_window: &adw::ApplicationWindow, _action, _obj| {
+                // The CSV file is only written on demand, the history being
+                // stored in a database
+                if let Err(error_info) = song_history_interface
+                    .borrow()
+                    .export_csv(&obtain_recognition_history_csv_path().unwrap())
+                {
+                    error!("Could not export the song history: {}", error_info);
+                    return;
+                }
+
                 #[cfg(not(windows))]
                 {
                     let window = _window.clone();
@@ -1330,8 +1700,18 @@ impl App {
             })
             .build();
 
+        let favorites_interface = self.favorites_interface.clone();
+
         let action_export_favorites_to_csv = gio::ActionEntry::builder("export-favorites-to-csv")
             .activate(move |_window: &adw::ApplicationWindow, _action, _obj| {
+                if let Err(error_info) = favorites_interface
+                    .borrow()
+                    .export_csv(&obtain_favorites_csv_path().unwrap())
+                {
+                    error!("Could not export the favorites: {}", error_info);
+                    return;
+                }
+
                 #[cfg(not(windows))]
                 {
                     let window = _window.clone();
@@ -1361,6 +1741,75 @@ impl App {
             })
             .build();
 
+        let song_history_interface = self.song_history_interface.clone();
+        let favorites_interface = self.favorites_interface.clone();
+
+        // The parameter is the list to export, either "history" or
+        // "favorites"; the format follows the extension of the chosen file
+        let action_export_playlist = gio::ActionEntry::builder("export-playlist")
+            .parameter_type(Some(glib::VariantTy::STRING))
+            .activate(move |window: &adw::ApplicationWindow, _action, parameter| {
+                let is_favorites =
+                    parameter.and_then(|parameter| parameter.str()) == Some("favorites");
+
+                let filters = gio::ListStore::new::<gtk::FileFilter>();
+                for (name, suffixes) in [
+                    (gettext("M3U8 playlist"), &["m3u8", "m3u"][..]),
+                    (gettext("XSPF playlist"), &["xspf"][..]),
+                    (gettext("JSPF playlist"), &["jspf"][..]),
+                    (gettext("ListenBrainz listens (JSON)"), &["json"][..]),
+                    (gettext("CSV file"), &["csv"][..]),
+                ] {
+                    let filter = gtk::FileFilter::new();
+                    filter.set_name(Some(&name));
+                    for suffix in suffixes {
+                        filter.add_suffix(suffix);
+                    }
+                    filters.append(&filter);
+                }
+
+                let dialog = gtk::FileDialog::builder()
+                    .title(gettext("Export as playlist"))
+                    .initial_name(match is_favorites {
+                        true => "songrec_favorites.m3u8",
+                        false => "songrec_history.m3u8",
+                    })
+                    .filters(&filters)
+                    .build();
+
+                let window = window.clone();
+                let song_history_interface = song_history_interface.clone();
+                let favorites_interface = favorites_interface.clone();
+
+                glib::spawn_future_local(async move {
+                    let path = match dialog.save_future(Some(&window)).await {
+                        Ok(file) => file.path().unwrap(),
+                        Err(error) => {
+                            error!("Error picking file: {:?}", error);
+                            return;
+                        }
+                    };
+                    let (path, format) = match PlaylistFormat::from_path(&path) {
+                        Some(format) => (path, format),
+                        None => (path.with_extension("m3u8"), PlaylistFormat::M3U8),
+                    };
+
+                    let result = match is_favorites {
+                        true => favorites_interface.borrow().export_playlist(format, &path),
+                        false => song_history_interface
+                            .borrow()
+                            .export_playlist(format, &path),
+                    };
+                    match result {
+                        Ok(()) => info!("Exported playlist: {}", path.display()),
+                        Err(error_info) => {
+                            error!("Could not export the playlist: {}", error_info)
+                        }
+                    }
+                });
+            })
+            .build();
+
         let gui_tx = self.gui_tx.clone();
 
         let action_wipe_history = gio::ActionEntry::builder("wipe-history")
@@ -1461,12 +1910,103 @@ impl App {
             })
             .build();
 
+        let gui_tx = self.gui_tx.clone();
+
+        let action_skip_silence_setting = gio::ActionEntry::builder("skip-silence-setting")
+            .state(
+                self.old_preferences
+                    .skip_silence_and_speech
+                    .unwrap_or(true)
+                    .to_variant(),
+            )
+            .activate(move |_, action, _| {
+                let state = action.state().unwrap();
+                let action_state: bool = state.get().unwrap();
+                let new_state = !action_state; // toggle
+                action.set_state(&new_state.to_variant());
+
+                let mut new_preference: Preferences = Preferences::new();
+                new_preference.skip_silence_and_speech = Some(new_state);
+                gui_tx
+                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .unwrap();
+            })
+            .build();
+
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        let gui_tx = self.gui_tx.clone();
+
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        let action_dbus_control_setting = gio::ActionEntry::builder("dbus-control-setting")
+            .state(
+                self.old_preferences
+                    .enable_dbus_control
+                    .unwrap_or(true)
+                    .to_variant(),
+            )
+            .activate(move |_, action, _| {
+                let state = action.state().unwrap();
+                let action_state: bool = state.get().unwrap();
+                let new_state = !action_state; // toggle
+                action.set_state(&new_state.to_variant());
+
+                let mut new_preference: Preferences = Preferences::new();
+                new_preference.enable_dbus_control = Some(new_state);
+                gui_tx
+                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .unwrap();
+            })
+            .build();
+
+        let gui_tx = self.gui_tx.clone();
+
+        let action_capture_archive_setting = gio::ActionEntry::builder("capture-archive-setting")
+            .state(
+                self.old_preferences
+                    .capture_archive
+                    .unwrap_or(false)
+                    .to_variant(),
+            )
+            .activate(move |_, action, _| {
+                let state = action.state().unwrap();
+                let action_state: bool = state.get().unwrap();
+                let new_state = !action_state; // toggle
+                action.set_state(&new_state.to_variant());
+
+                let mut new_preference: Preferences = Preferences::new();
+                new_preference.capture_archive = Some(new_state);
+                gui_tx
+                    .try_send(GUIMessage::UpdatePreference(new_preference))
+                    .unwrap();
+            })
+            .build();
+
         let action_close = gio::ActionEntry::builder("close")
             .activate(move |window: &adw::ApplicationWindow, _, _| {
                 window.close();
             })
             .build();
 
+        let statistics_page = RefCell::new(StatisticsPage::new(
+            self.builder.object("statistics_preferences_page").unwrap(),
+        ));
+        let song_history_interface = self.song_history_interface.clone();
+        let navigation_view_2 = navigation_view.clone();
+
+        let action_show_statistics = gio::ActionEntry::builder("show-statistics")
+            .activate(
+                move |_, _, _| match song_history_interface.borrow().statistics(10) {
+                    Ok(statistics) => {
+                        statistics_page.borrow_mut().show(&statistics, Local::now());
+                        navigation_view_2.push_by_tag("statistics_tag");
+                    }
+                    Err(error_info) => {
+                        error!("Could not compute the statistics: {}", error_info);
+                    }
+                },
+            )
+            .build();
+
         let action_show_preferences = gio::ActionEntry::builder("show-preferences")
             .activate(move |_, _, _| {
                 navigation_view.push_by_tag("settings_tag");
@@ -1495,12 +2035,16 @@ impl App {
             action_search_youtube,
             action_export_to_csv,
             action_export_favorites_to_csv,
+            action_export_playlist,
             action_wipe_history,
             action_show_preferences,
+            action_show_statistics,
             action_notification_setting,
             #[cfg(target_os = "linux")]
             action_systray_setting,
             action_no_dupes_setting,
+            action_skip_silence_setting,
+            action_capture_archive_setting,
             action_refresh_devices,
             action_close,
             action_show_menu,
@@ -1511,6 +2055,9 @@ impl App {
             window.add_action_entries([action_mpris_setting]);
         }
 
+        #[cfg(all(target_os = "linux", feature = "dbus"))]
+        window.add_action_entries([action_dbus_control_setting]);
+
         // GDK key names are available here:
         // https://gitlab.gnome.org/GNOME/gtk/-/blob/main/gdk/gdkkeysyms.h
 
@@ -1,19 +1,21 @@
-/// This file contains code for interfacing between the CSV Song history
-/// format defined within the "src/utils/csv_song_history.rs" file, the
-/// GTK-rs GUI of SongRec and the filesystem while using the GUI.
+/// This file contains code for interfacing between the song history
+/// database defined within the "src/utils/history_database.rs" file and the
+/// GTK-rs GUI of SongRec.
 use crate::gui::history_entry::HistoryEntry;
 use crate::utils::csv_song_history::{HasSong, Song, SongHistoryRecord};
+use crate::utils::history_database::{HistoryDatabase, HistoryQuery, RecognitionDetails, SongList};
+use crate::utils::listening_statistics::{ListeningStatistics, compute_statistics};
+use crate::utils::playlist_formats::{PlaylistFormat, write_playlist};
 use gettextrs::gettext;
 use gtk::prelude::*;
 use log::error;
 use std::collections::HashSet;
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 trait SongHistoryRecordListStore {
     fn add_song_history_record(&mut self, to_add: &SongHistoryRecord);
     fn remove_song(&mut self, to_remove: Song);
-    fn remove_song_history_record(&mut self, to_remove: SongHistoryRecord);
 }
 
 // Extend gio::ListStore to integrate with SongHistoryRecord
@@ -36,20 +38,16 @@ impl SongHistoryRecordListStore for gio::ListStore {
             item.get_song() != to_remove
         })
     }
-
-    fn remove_song_history_record(&mut self, to_remove: SongHistoryRecord) {
-        self.remove_song(to_remove.get_song());
-    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct RecognitionHistoryInterface {
-    csv_path: PathBuf,
+    database: HistoryDatabase,
     list_store: gio::ListStore,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct FavoritesInterface {
-    csv_path: PathBuf,
+    database: HistoryDatabase,
     list_store: gio::ListStore,
     is_favorite: HashSet<Song>,
 }
@@ -57,7 +55,7 @@ pub struct FavoritesInterface {
 pub trait SongRecordInterface {
     fn new(
         list_store: gio::ListStore,
-        get_csv_path: fn() -> Result<PathBuf, Box<dyn Error>>,
+        get_database_path: fn() -> Result<PathBuf, Box<dyn Error>>,
     ) -> Result<Self, Box<dyn Error>>
     where
         Self: Sized;
@@ -67,7 +65,8 @@ pub trait SongRecordInterface {
 
     fn load(&mut self) -> Result<(), Box<dyn Error>>;
     fn remove(&mut self, record: SongHistoryRecord);
-    fn save(&mut self);
+    fn export_csv(&self, csv_path: &Path) -> Result<(), Box<dyn Error>>;
+    fn export_playlist(&self, format: PlaylistFormat, path: &Path) -> Result<(), Box<dyn Error>>;
 }
 
 impl dyn SongRecordInterface {}
@@ -82,10 +81,10 @@ fn test_item_date() {
 impl SongRecordInterface for RecognitionHistoryInterface {
     fn new(
         list_store: gio::ListStore,
-        get_csv_path: fn() -> Result<PathBuf, Box<dyn Error>>,
+        get_database_path: fn() -> Result<PathBuf, Box<dyn Error>>,
     ) -> Result<Self, Box<dyn Error>> {
         let mut interface = RecognitionHistoryInterface {
-            csv_path: get_csv_path()?,
+            database: HistoryDatabase::open(&get_database_path()?)?,
             list_store,
         };
 
@@ -101,19 +100,8 @@ impl SongRecordInterface for RecognitionHistoryInterface {
     }
 
     fn load(&mut self) -> Result<(), Box<dyn Error>> {
-        if let Ok(mut reader) = csv::ReaderBuilder::new()
-            .flexible(true)
-            .from_path(&self.csv_path)
-        {
-            let mut read = reader.deserialize().collect::<Vec<_>>();
-            fn item_date(item: &csv::Result<SongHistoryRecord>) -> Option<chrono::NaiveDateTime> {
-                let s = &item.as_ref().ok()?.recognition_date;
-                chrono::NaiveDateTime::parse_from_str(s, "%c").ok()
-            }
-            read.sort_by_cached_key(item_date);
-            for result in read {
-                self.list_store.add_song_history_record(&result?)
-            }
+        for record in self.database.load(SongList::History)? {
+            self.list_store.add_song_history_record(&record);
         }
         Ok(())
     }
@@ -121,40 +109,90 @@ impl SongRecordInterface for RecognitionHistoryInterface {
     fn wipe_and_save(&mut self) {
         self.list_store.remove_all();
 
-        let mut writer = csv::Writer::from_path(&self.csv_path).unwrap();
-
-        writer.flush().unwrap();
+        if let Err(error_info) = self.database.wipe(SongList::History) {
+            error!(
+                "{} {}",
+                gettext("Error when saving the song history:"),
+                error_info
+            );
+        }
     }
 
     fn add_row_and_save(&mut self, record: SongHistoryRecord) {
-        self.list_store.add_song_history_record(&record);
+        self.add_recognition(record, &RecognitionDetails::default());
+    }
+
+    fn remove(&mut self, song_record: SongHistoryRecord) {
+        let song = song_record.get_song();
 
-        self.save();
+        if let Err(error_info) = self.database.remove_song(SongList::History, &song) {
+            error!(
+                "{} {}",
+                gettext("Error when saving the song history:"),
+                error_info
+            );
+        }
+        self.list_store.remove_song(song);
     }
 
-    fn save(&mut self) {
-        let mut writer = csv::Writer::from_path(&self.csv_path).unwrap();
+    fn export_csv(&self, csv_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.database.export_csv(SongList::History, csv_path)
+    }
 
-        for item in self.list_store.iter::<glib::Object>() {
-            let item = item.unwrap().downcast::<HistoryEntry>().unwrap();
-            writer.serialize(item.get_song_history_record()).unwrap();
+    fn export_playlist(&self, format: PlaylistFormat, path: &Path) -> Result<(), Box<dyn Error>> {
+        let rows = self.database.query(&HistoryQuery {
+            list: SongList::History,
+            ..Default::default()
+        })?;
+        std::fs::write(
+            path,
+            write_playlist(format, &rows, &gettext("SongRec history"))?,
+        )?;
+        Ok(())
+    }
+}
+
+impl RecognitionHistoryInterface {
+    /// Append a newly recognized song, along with the details which are only
+    /// kept in the database.
+    pub fn add_recognition(&mut self, record: SongHistoryRecord, details: &RecognitionDetails) {
+        self.list_store.add_song_history_record(&record);
+
+        if let Err(error_info) = self.database.add(SongList::History, &record, details) {
+            error!(
+                "{} {}",
+                gettext("Error when saving the song history:"),
+                error_info
+            );
         }
-        writer.flush().unwrap();
     }
 
-    fn remove(&mut self, song_record: SongHistoryRecord) {
-        self.list_store.remove_song_history_record(song_record);
-        self.save()
+    /// The archived audio a history entry was recognized from, if it is
+    /// still on the disk.
+    pub fn capture_path(&self, record: &SongHistoryRecord) -> Option<PathBuf> {
+        match self.database.capture_path(record) {
+            Ok(capture_path) => capture_path
+                .map(PathBuf::from)
+                .filter(|capture_path| capture_path.exists()),
+            Err(error_info) => {
+                error!(
+                    "{} {}",
+                    gettext("Error when reading the song history on the disk:"),
+                    error_info
+                );
+                None
+            }
+        }
     }
 }
 
 impl SongRecordInterface for FavoritesInterface {
     fn new(
         list_store: gio::ListStore,
-        get_csv_path: fn() -> Result<PathBuf, Box<dyn Error>>,
+        get_database_path: fn() -> Result<PathBuf, Box<dyn Error>>,
     ) -> Result<Self, Box<dyn Error>> {
         let mut interface = FavoritesInterface {
-            csv_path: get_csv_path()?,
+            database: HistoryDatabase::open(&get_database_path()?)?,
             list_store,
             is_favorite: HashSet::<Song>::new(),
         };
@@ -171,15 +209,9 @@ impl SongRecordInterface for FavoritesInterface {
     }
 
     fn load(&mut self) -> Result<(), Box<dyn Error>> {
-        if let Ok(mut reader) = csv::ReaderBuilder::new()
-            .flexible(true)
-            .from_path(&self.csv_path)
-        {
-            for result in reader.deserialize() {
-                let record = result?;
-                self.list_store.add_song_history_record(&record);
-                self.is_favorite.insert(record.get_song());
-            }
+        for record in self.database.load(SongList::Favorites)? {
+            self.list_store.add_song_history_record(&record);
+            self.is_favorite.insert(record.get_song());
         }
         Ok(())
     }
@@ -187,32 +219,60 @@ impl SongRecordInterface for FavoritesInterface {
     fn wipe_and_save(&mut self) {
         self.list_store.remove_all();
         self.is_favorite.clear();
-        let mut writer = csv::Writer::from_path(&self.csv_path).unwrap();
 
-        writer.flush().unwrap();
+        if let Err(error_info) = self.database.wipe(SongList::Favorites) {
+            error!(
+                "{} {}",
+                gettext("Error when saving the favorites:"),
+                error_info
+            );
+        }
     }
 
     fn add_row_and_save(&mut self, record: SongHistoryRecord) {
         self.list_store.add_song_history_record(&record);
-        self.is_favorite.insert(record.get_song());
-        self.save();
-    }
-
-    fn save(&mut self) {
-        let mut writer = csv::Writer::from_path(&self.csv_path).unwrap();
+        self.is_favorite.insert(record.clone().get_song());
 
-        for item in self.list_store.iter::<glib::Object>() {
-            let item = item.unwrap().downcast::<HistoryEntry>().unwrap();
-            writer.serialize(item.get_song_history_record()).unwrap();
+        if let Err(error_info) =
+            self.database
+                .add(SongList::Favorites, &record, &RecognitionDetails::default())
+        {
+            error!(
+                "{} {}",
+                gettext("Error when saving the favorites:"),
+                error_info
+            );
         }
-        writer.flush().unwrap();
     }
 
     fn remove(&mut self, song_record: SongHistoryRecord) {
         let song = song_record.get_song();
         self.is_favorite.remove(&song);
+
+        if let Err(error_info) = self.database.remove_song(SongList::Favorites, &song) {
+            error!(
+                "{} {}",
+                gettext("Error when saving the favorites:"),
+                error_info
+            );
+        }
         self.list_store.remove_song(song);
-        self.save()
+    }
+
+    fn export_csv(&self, csv_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.database.export_csv(SongList::Favorites, csv_path)
+    }
+
+    fn export_playlist(&self, format: PlaylistFormat, path: &Path) -> Result<(), Box<dyn Error>> {
+        let rows = self.database.query(&HistoryQuery {
+            list: SongList::Favorites,
+            ..Default::default()
+        })?;
+        std::fs::write(
+            path,
+            write_playlist(format, &rows, &gettext("SongRec favorites"))?,
+        )?;
+        Ok(())
     }
 }
 
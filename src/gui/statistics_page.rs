@@ -0,0 +1,138 @@
+//! The Statistics page of the main window, whose contents are rebuilt from
+//! the song history each time it is displayed.
+
+use adw::prelude::*;
+use chrono::{DateTime, Local};
+use gettextrs::gettext;
+
+use crate::utils::listening_statistics::{
+    ListeningStatistics, RankedEntry, format_day, render_day_heatmap, render_hour_heatmap,
+};
+
+pub struct StatisticsPage {
+    page: adw::PreferencesPage,
+    groups: Vec<adw::PreferencesGroup>,
+}
+
+fn count_row(title: &str, subtitle: Option<&str>, count: u32) -> adw::ActionRow {
+    let row = adw::ActionRow::builder()
+        .title(title)
+        .use_markup(false)
+        .build();
+    if let Some(subtitle) = subtitle {
+        row.set_subtitle(subtitle);
+    }
+    row.add_suffix(&gtk::Label::new(Some(&count.to_string())));
+    row
+}
+
+fn heatmap_row(heatmap: &str) -> adw::PreferencesRow {
+    let label = gtk::Label::builder()
+        .label(heatmap)
+        .css_classes(["monospace"])
+        .xalign(0.0)
+        .margin_top(10)
+        .margin_bottom(10)
+        .margin_start(10)
+        .margin_end(10)
+        .build();
+
+    adw::PreferencesRow::builder()
+        .child(&label)
+        .activatable(false)
+        .build()
+}
+
+impl StatisticsPage {
+    pub fn new(page: adw::PreferencesPage) -> Self {
+        StatisticsPage {
+            page,
+            groups: vec![],
+        }
+    }
+
+    fn add_group(&mut self, title: &str, rows: Vec<gtk::Widget>) {
+        let group = adw::PreferencesGroup::builder().title(title).build();
+        if rows.is_empty() {
+            group.set_description(Some(&gettext("Nothing yet")));
+        }
+        for row in rows {
+            group.add(&row);
+        }
+        self.page.add(&group);
+        self.groups.push(group);
+    }
+
+    pub fn show(&mut self, statistics: &ListeningStatistics, now: DateTime<Local>) {
+        for group in self.groups.drain(..) {
+            self.page.remove(&group);
+        }
+
+        self.add_group(
+            &gettext("Overview"),
+            vec![
+                count_row(
+                    &gettext("Recognitions"),
+                    None,
+                    statistics.number_recognitions,
+                )
+                .upcast(),
+                count_row(&gettext("Distinct songs"), None, statistics.number_songs).upcast(),
+            ],
+        );
+
+        let ranked_rows = |entries: &[RankedEntry]| -> Vec<gtk::Widget> {
+            entries
+                .iter()
+                .map(|entry| count_row(&entry.name, None, entry.count).upcast())
+                .collect()
+        };
+        self.add_group(
+            &gettext("Top artists"),
+            ranked_rows(&statistics.top_artists),
+        );
+        self.add_group(&gettext("Top genres"), ranked_rows(&statistics.top_genres));
+
+        let first_heard =
+            |timestamp: &str| format!("{} {}", gettext("First heard:"), format_day(timestamp));
+        self.add_group(
+            &gettext("Top tracks"),
+            statistics
+                .top_tracks
+                .iter()
+                .map(|track| {
+                    count_row(
+                        &track.song_name,
+                        Some(&first_heard(&track.first_heard)),
+                        track.count,
+                    )
+                    .upcast()
+                })
+                .collect(),
+        );
+        self.add_group(
+            &gettext("New this month"),
+            statistics
+                .new_this_month
+                .iter()
+                .map(|track| {
+                    count_row(
+                        &track.song_name,
+                        Some(&first_heard(&track.first_heard)),
+                        track.count,
+                    )
+                    .upcast()
+                })
+                .collect(),
+        );
+
+        self.add_group(
+            &gettext("Activity per hour"),
+            vec![heatmap_row(&render_hour_heatmap(statistics)).upcast()],
+        );
+        self.add_group(
+            &gettext("Activity per day"),
+            vec![heatmap_row(&render_day_heatmap(statistics, now)).upcast()],
+        );
+    }
+}
@@ -3,12 +3,23 @@
 pub mod cli_main;
 
 mod core {
+    pub mod audio_activity;
+    pub mod batch_recognition;
+    pub mod capture_archive;
+    pub mod capture_window;
+    #[cfg(unix)]
+    pub mod daemon;
+    pub mod file_scanner;
     pub mod http_task;
     pub mod logging;
     pub mod microphone_thread;
+    pub mod now_playing_server;
     pub mod preferences;
     pub mod processing_thread;
+    pub mod scrobbling;
+    pub mod song_change;
     pub mod thread_messages;
+    pub mod webhooks;
 
     mod audio_controllers {
         pub mod audio_backend;
@@ -17,10 +28,21 @@ mod core {
         pub mod pulseaudio;
     }
 
+    pub mod request_scheduler;
+
+    pub mod recognition_backends {
+        pub mod backend;
+        pub mod custom_http;
+        pub mod local_database;
+        pub mod shazam;
+        pub mod shazam_response;
+    }
+
     pub mod fingerprinting {
         pub mod algorithm;
         pub mod communication;
         mod hanning;
+        pub mod local_database;
         pub mod signature_format;
         mod user_agent;
     }
@@ -34,15 +56,25 @@ mod gui {
     pub mod context_menu;
     pub mod history_entry;
     pub mod listed_device;
+    pub mod statistics_page;
 }
 
 mod utils {
+    pub mod audio_tagging;
     pub mod csv_song_history;
+    pub mod file_organizer;
     pub mod filesystem_operations;
+    pub mod history_database;
+    pub mod history_import;
     pub mod internationalization;
+    pub mod listening_statistics;
+    pub mod playlist_formats;
+    pub mod tracklist_formats;
 }
 
 mod plugins {
+    #[cfg(all(target_os = "linux", feature = "dbus"))]
+    pub mod dbus_control;
     #[cfg(feature = "ffmpeg")]
     pub mod ffmpeg_wrapper;
     #[cfg(feature = "gui")]
@@ -54,19 +86,35 @@ mod plugins {
 
 use crate::core::fingerprinting::algorithm::SignatureGenerator;
 use crate::core::fingerprinting::communication::recognize_song_from_signature;
+use crate::core::fingerprinting::local_database::LocalDatabase;
 use crate::core::fingerprinting::signature_format::DecodedSignature;
 
-use crate::cli_main::{CLIOutputType, CLIParameters, cli_main};
+use crate::cli_main::{
+    CLIBatchParameters, CLIHistoryParameters, CLIOrganizeParameters, CLIOutputType, CLIParameters,
+    CLIScanParameters, CLIStatisticsParameters, CLITagParameters, batch_main, captures_main,
+    cli_main, favorites_main, history_main, import_main, organize_main, scan_main, statistics_main,
+    tag_main,
+};
+#[cfg(unix)]
+use crate::cli_main::{CLIDaemonParameters, daemon_main};
+use crate::core::batch_recognition::BatchParameters;
+use crate::core::file_scanner::ScanParameters;
 use crate::core::logging::Logging;
 #[cfg(feature = "gui")]
 use crate::gui::main_window::gui_main;
+use crate::utils::audio_tagging::TaggingOptions;
+use crate::utils::filesystem_operations::obtain_local_database_path;
+use crate::utils::history_database::{HistoryQuery, HistorySortKey, SongList, parse_date_bound};
 use crate::utils::internationalization::setup_internationalization;
+use crate::utils::playlist_formats::PlaylistFormat;
+use crate::utils::tracklist_formats::TracklistFormat;
 
 use clap::{Arg, ArgAction, Command, command};
 use gettextrs::gettext;
 use log::debug;
 use soup::prelude::SessionExt;
 use std::error::Error;
+use std::path::PathBuf;
 
 macro_rules! base_app {
     () => {
@@ -119,6 +167,56 @@ SongRec {version}
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(6..=20))
+                        .help(gettext("Length of the audio recognized at each request, in seconds (12 by default)"))
+                )
+                .arg(
+                    Arg::new("voting-windows")
+                        .long("voting-windows")
+                        .value_parser(clap::value_parser!(u64).range(1..=3))
+                        .help(gettext("Number of staggered windows recognized at each request, only reporting the songs most of them agree on"))
+                )
+                .arg(
+                    Arg::new("capture-archive")
+                        .long("capture-archive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Keep the recorded audio of the recognition requests, see the \"captures\" subcommand"))
+                )
+                .arg(
+                    Arg::new("no-audio-gating")
+                        .long("no-audio-gating")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Also make recognition requests when only silence or speech is heard"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -134,12 +232,145 @@ SongRec {version}
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("no-history")
+                        .long("no-history")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't add the recognized songs to the song history"))
+                )
+                .arg(
+                    Arg::new("no-scrobble")
+                        .long("no-scrobble")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't submit the recognized songs to ListenBrainz, even when a token is set in the preferences"))
+                )
+                .arg(
+                    Arg::new("listenbrainz-url")
+                        .long("listenbrainz-url")
+                        .conflicts_with("no-scrobble")
+                        .help(gettext("The root URL of the ListenBrainz-compatible server to submit the recognized songs to"))
+                )
+                .arg(
+                    Arg::new("listenbrainz-token")
+                        .long("listenbrainz-token")
+                        .conflicts_with("no-scrobble")
+                        .help(gettext("The user token used to submit the recognized songs, instead of the one set in the preferences"))
+                )
+                .arg(
+                    Arg::new("no-webhooks")
+                        .long("no-webhooks")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't notify the webhooks set in the preferences"))
+                )
                 .arg(
                     Arg::new("disable-mpris")
                         .long("disable-mpris")
                         .action(ArgAction::SetTrue)
                         .help(gettext("Disable MPRIS support"))
                 )
+                .arg(
+                    Arg::new("disable-dbus")
+                        .long("disable-dbus")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Disable the D-Bus control interface"))
+                )
+                .arg(
+                    Arg::new("http-server")
+                        .long("http-server")
+                        .value_name("ADDRESS")
+                        .help(gettext("Serve a now playing page and JSON endpoints on this address, such as 127.0.0.1:8080"))
+                )
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about(gettext("Run in the background, recognizing songs from an audio device and answering JSON-RPC requests from local clients over a Unix socket"))
+                .arg(
+                    Arg::new("socket")
+                        .long("socket")
+                        .help(gettext("The path of the Unix socket to listen on"))
+                )
+                .arg(
+                    Arg::new("audio-device")
+                        .short('d')
+                        .long("audio-device")
+                        .help(gettext("Specify the audio device to use"))
+                )
+                .arg(
+                    Arg::new("idle")
+                        .long("idle")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't start recording before a client requests it"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(6..=20))
+                        .help(gettext("Length of the audio recognized at each request, in seconds (12 by default)"))
+                )
+                .arg(
+                    Arg::new("voting-windows")
+                        .long("voting-windows")
+                        .value_parser(clap::value_parser!(u64).range(1..=3))
+                        .help(gettext("Number of staggered windows recognized at each request, only reporting the songs most of them agree on"))
+                )
+                .arg(
+                    Arg::new("capture-archive")
+                        .long("capture-archive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Keep the recorded audio of the recognition requests, see the \"captures\" subcommand"))
+                )
+                .arg(
+                    Arg::new("no-audio-gating")
+                        .long("no-audio-gating")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Also make recognition requests when only silence or speech is heard"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("no-history")
+                        .long("no-history")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't add the recognized songs to the song history"))
+                )
+                .arg(
+                    Arg::new("http-server")
+                        .long("http-server")
+                        .value_name("ADDRESS")
+                        .help(gettext("Serve a now playing page and JSON endpoints on this address, such as 127.0.0.1:8080"))
+                )
         )
         .subcommand(
             Command::new("recognize")
@@ -166,6 +397,44 @@ SongRec {version}
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64).range(6..=20))
+                        .help(gettext("Length of the audio recognized at each request, in seconds (12 by default)"))
+                )
+                .arg(
+                    Arg::new("voting-windows")
+                        .long("voting-windows")
+                        .value_parser(clap::value_parser!(u64).range(1..=3))
+                        .help(gettext("Number of staggered windows recognized at each request, only reporting the songs most of them agree on"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
                 .arg(
                     Arg::new("json")
                         .short('j')
@@ -181,6 +450,12 @@ SongRec {version}
                         .action(ArgAction::SetTrue)
                         .help(gettext("Enable printing full song info in the CSV format"))
                 )
+                .arg(
+                    Arg::new("no-history")
+                        .long("no-history")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't add the recognized songs to the song history"))
+                )
                 .arg(
                     Arg::new("input_file")
                         .required(false)
@@ -220,6 +495,31 @@ SongRec {version}
                         .value_parser(clap::value_parser!(u64))
                         .help(gettext("Shazam interval between requests in seconds (increase if you are rate-limited)"))
                 )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
         )
         .subcommand(
             Command::new("audio-file-to-fingerprint")
@@ -239,6 +539,508 @@ SongRec {version}
                         .help(gettext("The data-URI Shazam fingerprint to recognize."))
                 )
         )
+        .subcommand(
+            Command::new("scan")
+                .about(gettext("Recognize every song within a long recording (DJ set, radio show...) and print a timestamped tracklist."))
+                .arg(
+                    Arg::new("window")
+                        .short('w')
+                        .long("window")
+                        .default_value("12")
+                        .value_parser(clap::value_parser!(u64).range(3..=20))
+                        .help(gettext("Length of the recognized windows, in seconds"))
+                )
+                .arg(
+                    Arg::new("step")
+                        .short('s')
+                        .long("step")
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help(gettext("Interval between the start of two windows, in seconds"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Pause between two requests in seconds (increase if you are rate-limited, ignored with the local database)"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .conflicts_with("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in JSON"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Enable printing the tracklist in the CSV format"))
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .value_parser(["cue", "ffmetadata", "webvtt", "audacity"])
+                        .conflicts_with_all(["json", "csv"])
+                        .help(gettext("Print the tracklist as a CUE sheet, FFmpeg chapters (;FFMETADATA1), WebVTT subtitles or an Audacity label track"))
+                )
+                .arg(
+                    Arg::new("input_file")
+                        .required(true)
+                        .help(gettext("The audio file to scan."))
+                )
+        )
+        .subcommand(
+            Command::new("batch")
+                .about(gettext("Recognize many audio files or directories, printing one result per file in the JSON Lines or CSV format."))
+                .arg(
+                    Arg::new("recursive")
+                        .short('r')
+                        .long("recursive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Walk directories recursively"))
+                )
+                .arg(
+                    Arg::new("include")
+                        .long("include")
+                        .action(ArgAction::Append)
+                        .help(gettext("Only process the files whose name matches this pattern (such as \"*.flac\"), may be repeated"))
+                )
+                .arg(
+                    Arg::new("exclude")
+                        .long("exclude")
+                        .action(ArgAction::Append)
+                        .help(gettext("Skip the files whose name matches this pattern, may be repeated"))
+                )
+                .arg(
+                    Arg::new("workers")
+                        .short('w')
+                        .long("workers")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help(gettext("Number of files decoded in parallel (defaults to the number of CPUs)"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Pause between two requests in seconds (increase if you are rate-limited, ignored with the local database)"))
+                )
+                .arg(
+                    Arg::new("state-file")
+                        .long("state-file")
+                        .help(gettext("File recording the processed files, used to resume an interrupted batch"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the results in the CSV format instead of JSON Lines"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("paths")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The audio files and directories to recognize."))
+                )
+        )
+        .subcommand(
+            Command::new("organize")
+                .about(gettext("Recognize audio files and rename or move them according to a template, such as \"{artist}/{album}/{title}.{ext}\"."))
+                .arg(
+                    Arg::new("template")
+                        .short('t')
+                        .long("template")
+                        .default_value("{artist}/{album}/{title}.{ext}")
+                        .help(gettext("The path of the organized files, relative to the destination directory. Available fields: {artist}, {title}, {album}, {year}, {genre}, {isrc}, {ext}"))
+                )
+                .arg(
+                    Arg::new("destination")
+                        .short('o')
+                        .long("destination")
+                        .default_value(".")
+                        .help(gettext("The directory to move the files into"))
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .short('n')
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print the moves which would be made"))
+                )
+                .arg(
+                    Arg::new("journal")
+                        .long("journal")
+                        .help(gettext("The file recording the moves, in order to undo them (a new file in the SongRec data directory by default)"))
+                )
+                .arg(
+                    Arg::new("undo")
+                        .long("undo")
+                        .conflicts_with("paths")
+                        .help(gettext("Move back the files listed in the given journal file"))
+                )
+                .arg(
+                    Arg::new("recursive")
+                        .short('r')
+                        .long("recursive")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Walk directories recursively"))
+                )
+                .arg(
+                    Arg::new("include")
+                        .long("include")
+                        .action(ArgAction::Append)
+                        .help(gettext("Only process the files whose name matches this pattern (such as \"*.flac\"), may be repeated"))
+                )
+                .arg(
+                    Arg::new("exclude")
+                        .long("exclude")
+                        .action(ArgAction::Append)
+                        .help(gettext("Skip the files whose name matches this pattern, may be repeated"))
+                )
+                .arg(
+                    Arg::new("request-interval")
+                        .short('i')
+                        .long("request-interval")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Pause between two requests in seconds (increase if you are rate-limited, ignored with the local database)"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("paths")
+                        .required_unless_present("undo")
+                        .num_args(1..)
+                        .help(gettext("The audio files and directories to organize."))
+                )
+        )
+        .subcommand(
+            Command::new("tag")
+                .about(gettext("Recognize audio files and write the obtained information (title, artist, album, year, genre, ISRC, cover art) into their tags."))
+                .arg(
+                    Arg::new("dry-run")
+                        .short('n')
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print the changes which would be made to the tags"))
+                )
+                .arg(
+                    Arg::new("only-fill-empty")
+                        .short('e')
+                        .long("only-fill-empty")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Don't overwrite the tags which already have a value"))
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .value_parser(["shazam", "http", "local"])
+                        .default_value("shazam")
+                        .help(gettext("The recognition backend to use"))
+                )
+                .arg(
+                    Arg::new("backend-url")
+                        .long("backend-url")
+                        .required_if_eq("backend", "http")
+                        .help(gettext("The URL of the matching service used by the \"http\" recognition backend"))
+                )
+                .arg(
+                    Arg::new("local-db")
+                        .long("local-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("backend-url")
+                        .help(gettext("Recognize offline against the local fingerprint database (same as \"--backend local\")"))
+                )
+                .arg(
+                    Arg::new("local-db-path")
+                        .long("local-db-path")
+                        .help(gettext("The local fingerprint database file to use"))
+                )
+                .arg(
+                    Arg::new("input_files")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The audio files to recognize and tag."))
+                )
+        )
+        .subcommand(
+            Command::new("index")
+                .about(gettext("Fingerprint the audio files of a directory into the local database, for offline recognition with \"--local-db\"."))
+                .arg(
+                    Arg::new("directory")
+                        .required(true)
+                        .help(gettext("The directory to index recursively."))
+                )
+                .arg(
+                    Arg::new("database")
+                        .long("database")
+                        .help(gettext("The local fingerprint database file to write"))
+                )
+                .arg(
+                    Arg::new("rebuild")
+                        .long("rebuild")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Discard the existing database contents instead of only adding new files"))
+                )
+        )
+        .subcommand(
+            Command::new("history")
+                .about(gettext("Print the song history, as recorded by the GUI and the \"listen\" and \"recognize\" commands."))
+                .arg(
+                    Arg::new("list")
+                        .long("list")
+                        .value_parser(["history", "favorites"])
+                        .default_value("history")
+                        .help(gettext("Whether to read the song history or the favorites"))
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .help(gettext("Only print the songs recognized from this date (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .help(gettext("Only print the songs recognized until this date, included (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("artist")
+                        .long("artist")
+                        .help(gettext("Only print the songs whose artist name contains this text"))
+                )
+                .arg(
+                    Arg::new("genre")
+                        .long("genre")
+                        .help(gettext("Only print the songs of this genre"))
+                )
+                .arg(
+                    Arg::new("favorite")
+                        .long("favorite")
+                        .value_parser(["yes", "no"])
+                        .help(gettext("Only print the songs which are (or are not) in the favorites"))
+                )
+                .arg(
+                    Arg::new("sort")
+                        .long("sort")
+                        .value_parser(["date", "artist", "song", "genre"])
+                        .default_value("date")
+                        .help(gettext("The field to sort the songs by"))
+                )
+                .arg(
+                    Arg::new("reverse")
+                        .short('r')
+                        .long("reverse")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Sort in descending order (most recent songs first when sorting by date)"))
+                )
+                .arg(
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Print at most this number of songs"))
+                )
+                .arg(
+                    Arg::new("offset")
+                        .long("offset")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("Skip this number of songs first"))
+                )
+                .arg(
+                    Arg::new("dedupe")
+                        .long("dedupe")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print the most recent recognition of each song"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .conflicts_with("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print one JSON object per song, with all the stored information"))
+                )
+                .arg(
+                    Arg::new("csv")
+                        .short('c')
+                        .long("csv")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the songs in the CSV format"))
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["m3u8", "xspf", "jspf", "listenbrainz"])
+                        .conflicts_with_all(["json", "csv"])
+                        .help(gettext("Print the songs as a playlist in this format (\"listenbrainz\" being the JSON format of ListenBrainz listens)"))
+                )
+        )
+        .subcommand(
+            Command::new("statistics")
+                .about(gettext("Print statistics about the song history: top artists, genres and tracks, listening activity and new songs."))
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .help(gettext("Only count the songs recognized from this date (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .help(gettext("Only count the songs recognized until this date, included (YYYY-MM-DD or RFC 3339)"))
+                )
+                .arg(
+                    Arg::new("top")
+                        .long("top")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64))
+                        .help(gettext("The number of entries of the rankings"))
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the statistics in JSON"))
+                )
+        )
+        .subcommand(
+            Command::new("import")
+                .about(gettext("Merge a history exported from the Shazam app, or the CSV history of another SongRec install, into the song history."))
+                .arg(
+                    Arg::new("favorites")
+                        .long("favorites")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Import into the favorites instead of the song history"))
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Only print what would be imported"))
+                )
+                .arg(
+                    Arg::new("input_files")
+                        .required(true)
+                        .num_args(1..)
+                        .help(gettext("The CSV files to import."))
+                )
+        )
+        .subcommand(
+            Command::new("favorites")
+                .about(gettext("Manage the favorites shared with the GUI."))
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about(gettext("Add a song of the history to the favorites."))
+                        .arg(
+                            Arg::new("song")
+                                .required(true)
+                                .help(gettext("The Shazam track key or the name (\"Artist - Title\") of the song"))
+                        )
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about(gettext("Remove a song from the favorites."))
+                        .arg(
+                            Arg::new("song")
+                                .required(true)
+                                .help(gettext("The Shazam track key or the name (\"Artist - Title\") of the song"))
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("captures")
+                .about(gettext("List the audio kept for the recognition requests, from the most recent, along with the songs recognized from it. It can be recognized again with the \"recognize\" subcommand."))
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help(gettext("Print the captures in JSON Lines"))
+                )
+        )
     };
 }
 
@@ -401,10 +1203,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .subcommand_matches("fingerprint-to-recognized-song")
                 .unwrap();
 
-            let fingerprint_string = subcommand_args
-                .get_one::<String>("fingerprint")
-                .unwrap()
-                .clone();
+            let signature = DecodedSignature::decode_from_uri(
+                subcommand_args.get_one::<String>("fingerprint").unwrap(),
+            )?;
 
             let session = soup::Session::new();
             session.set_timeout(20);
@@ -415,12 +1216,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                 println!(
                     "{}",
                     serde_json::to_string_pretty(
-                        &recognize_song_from_signature(
-                            &session,
-                            &DecodedSignature::decode_from_uri(&fingerprint_string).unwrap()
-                        )
-                        .await
-                        .unwrap()
+                        &recognize_song_from_signature(&session, &signature)
+                            .await
+                            .unwrap()
                     )
                     .unwrap()
                 );
@@ -428,6 +1226,291 @@ fn main() -> Result<(), Box<dyn Error>> {
             });
             main_loop.run();
         }
+        Some("scan") => {
+            let subcommand_args = args.subcommand_matches("scan").unwrap();
+            let enable_json = subcommand_args.get_flag("json");
+            let enable_csv = subcommand_args.get_flag("csv");
+            let recognition_backend = match subcommand_args.get_flag("local-db") {
+                true => Some("local".to_string()),
+                false => subcommand_args.get_one::<String>("backend").cloned(),
+            };
+
+            scan_main(CLIScanParameters {
+                input_file: subcommand_args
+                    .get_one::<String>("input_file")
+                    .unwrap()
+                    .clone(),
+                scan_parameters: ScanParameters {
+                    window_secs: *subcommand_args.get_one::<u64>("window").unwrap() as usize,
+                    step_secs: *subcommand_args.get_one::<u64>("step").unwrap() as usize,
+                    request_interval_secs: match recognition_backend.as_deref() {
+                        Some("local") => 0,
+                        _ => *subcommand_args.get_one::<u64>("request-interval").unwrap(),
+                    },
+                },
+                output_type: match (enable_json, enable_csv) {
+                    (true, _) => CLIOutputType::JSON,
+                    (false, true) => CLIOutputType::CSV,
+                    (false, false) => CLIOutputType::SongName,
+                },
+                tracklist_format: match subcommand_args
+                    .get_one::<String>("format")
+                    .map(|s| s.as_str())
+                {
+                    Some("cue") => Some(TracklistFormat::CueSheet),
+                    Some("ffmetadata") => Some(TracklistFormat::FFMetadata),
+                    Some("webvtt") => Some(TracklistFormat::WebVTT),
+                    Some("audacity") => Some(TracklistFormat::AudacityLabels),
+                    _ => None,
+                },
+                recognition_backend,
+                recognition_backend_url: subcommand_args.get_one::<String>("backend-url").cloned(),
+                local_database_path: subcommand_args.get_one::<String>("local-db-path").cloned(),
+            })?;
+        }
+        Some("batch") => {
+            let subcommand_args = args.subcommand_matches("batch").unwrap();
+            let recognition_backend = match subcommand_args.get_flag("local-db") {
+                true => Some("local".to_string()),
+                false => subcommand_args.get_one::<String>("backend").cloned(),
+            };
+            let get_strings = |id: &str| -> Vec<String> {
+                subcommand_args
+                    .get_many::<String>(id)
+                    .map(|values| values.cloned().collect())
+                    .unwrap_or_default()
+            };
+
+            batch_main(CLIBatchParameters {
+                batch_parameters: BatchParameters {
+                    paths: get_strings("paths"),
+                    recursive: subcommand_args.get_flag("recursive"),
+                    include_patterns: get_strings("include"),
+                    exclude_patterns: get_strings("exclude"),
+                    number_workers: match subcommand_args.get_one::<u64>("workers") {
+                        Some(workers) => *workers as usize,
+                        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+                    },
+                    request_interval_secs: match recognition_backend.as_deref() {
+                        Some("local") => 0,
+                        _ => *subcommand_args.get_one::<u64>("request-interval").unwrap(),
+                    },
+                    state_file: subcommand_args.get_one::<String>("state-file").cloned(),
+                },
+                output_type: match subcommand_args.get_flag("csv") {
+                    true => CLIOutputType::CSV,
+                    false => CLIOutputType::JSON,
+                },
+                recognition_backend,
+                recognition_backend_url: subcommand_args.get_one::<String>("backend-url").cloned(),
+                local_database_path: subcommand_args.get_one::<String>("local-db-path").cloned(),
+            })?;
+        }
+        Some("organize") => {
+            let subcommand_args = args.subcommand_matches("organize").unwrap();
+            let recognition_backend = match subcommand_args.get_flag("local-db") {
+                true => Some("local".to_string()),
+                false => subcommand_args.get_one::<String>("backend").cloned(),
+            };
+            let get_strings = |id: &str| -> Vec<String> {
+                subcommand_args
+                    .get_many::<String>(id)
+                    .map(|values| values.cloned().collect())
+                    .unwrap_or_default()
+            };
+
+            organize_main(CLIOrganizeParameters {
+                batch_parameters: BatchParameters {
+                    paths: get_strings("paths"),
+                    recursive: subcommand_args.get_flag("recursive"),
+                    include_patterns: get_strings("include"),
+                    exclude_patterns: get_strings("exclude"),
+                    number_workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+                    request_interval_secs: match recognition_backend.as_deref() {
+                        Some("local") => 0,
+                        _ => *subcommand_args.get_one::<u64>("request-interval").unwrap(),
+                    },
+                    state_file: None,
+                },
+                template: subcommand_args
+                    .get_one::<String>("template")
+                    .unwrap()
+                    .clone(),
+                destination: subcommand_args
+                    .get_one::<String>("destination")
+                    .unwrap()
+                    .clone(),
+                dry_run: subcommand_args.get_flag("dry-run"),
+                journal_path: subcommand_args.get_one::<String>("journal").cloned(),
+                undo_journal_path: subcommand_args.get_one::<String>("undo").cloned(),
+                recognition_backend,
+                recognition_backend_url: subcommand_args.get_one::<String>("backend-url").cloned(),
+                local_database_path: subcommand_args.get_one::<String>("local-db-path").cloned(),
+            })?;
+        }
+        Some("tag") => {
+            let subcommand_args = args.subcommand_matches("tag").unwrap();
+            let recognition_backend = match subcommand_args.get_flag("local-db") {
+                true => Some("local".to_string()),
+                false => subcommand_args.get_one::<String>("backend").cloned(),
+            };
+
+            tag_main(CLITagParameters {
+                input_files: subcommand_args
+                    .get_many::<String>("input_files")
+                    .unwrap()
+                    .cloned()
+                    .collect(),
+                tagging_options: TaggingOptions {
+                    dry_run: subcommand_args.get_flag("dry-run"),
+                    only_fill_empty: subcommand_args.get_flag("only-fill-empty"),
+                },
+                recognition_backend,
+                recognition_backend_url: subcommand_args.get_one::<String>("backend-url").cloned(),
+                local_database_path: subcommand_args.get_one::<String>("local-db-path").cloned(),
+            })?;
+        }
+        Some("history") => {
+            let subcommand_args = args.subcommand_matches("history").unwrap();
+
+            history_main(CLIHistoryParameters {
+                query: HistoryQuery {
+                    list: match subcommand_args.get_one::<String>("list").unwrap().as_str() {
+                        "favorites" => SongList::Favorites,
+                        _ => SongList::History,
+                    },
+                    since: match subcommand_args.get_one::<String>("since") {
+                        Some(since) => Some(parse_date_bound(since, false)?),
+                        None => None,
+                    },
+                    until: match subcommand_args.get_one::<String>("until") {
+                        Some(until) => Some(parse_date_bound(until, true)?),
+                        None => None,
+                    },
+                    artist: subcommand_args.get_one::<String>("artist").cloned(),
+                    genre: subcommand_args.get_one::<String>("genre").cloned(),
+                    is_favorite: subcommand_args
+                        .get_one::<String>("favorite")
+                        .map(|favorite| favorite == "yes"),
+                    sort_key: match subcommand_args.get_one::<String>("sort").unwrap().as_str() {
+                        "artist" => HistorySortKey::Artist,
+                        "song" => HistorySortKey::Song,
+                        "genre" => HistorySortKey::Genre,
+                        _ => HistorySortKey::Date,
+                    },
+                    descending: subcommand_args.get_flag("reverse"),
+                    limit: subcommand_args.get_one::<u64>("limit").copied(),
+                    offset: *subcommand_args.get_one::<u64>("offset").unwrap(),
+                    deduplicate: subcommand_args.get_flag("dedupe"),
+                },
+                output_type: match (
+                    subcommand_args.get_flag("json"),
+                    subcommand_args.get_flag("csv"),
+                ) {
+                    (true, _) => CLIOutputType::JSON,
+                    (false, true) => CLIOutputType::CSV,
+                    (false, false) => CLIOutputType::SongName,
+                },
+                playlist_format: match subcommand_args
+                    .get_one::<String>("format")
+                    .map(String::as_str)
+                {
+                    Some("m3u8") => Some(PlaylistFormat::M3U8),
+                    Some("xspf") => Some(PlaylistFormat::XSPF),
+                    Some("jspf") => Some(PlaylistFormat::JSPF),
+                    Some("listenbrainz") => Some(PlaylistFormat::ListenBrainz),
+                    _ => None,
+                },
+            })?;
+        }
+        Some("statistics") => {
+            let subcommand_args = args.subcommand_matches("statistics").unwrap();
+
+            statistics_main(CLIStatisticsParameters {
+                query: HistoryQuery {
+                    since: match subcommand_args.get_one::<String>("since") {
+                        Some(since) => Some(parse_date_bound(since, false)?),
+                        None => None,
+                    },
+                    until: match subcommand_args.get_one::<String>("until") {
+                        Some(until) => Some(parse_date_bound(until, true)?),
+                        None => None,
+                    },
+                    ..Default::default()
+                },
+                top_count: *subcommand_args.get_one::<u64>("top").unwrap() as usize,
+                output_type: match subcommand_args.get_flag("json") {
+                    true => CLIOutputType::JSON,
+                    false => CLIOutputType::SongName,
+                },
+            })?;
+        }
+        Some("import") => {
+            let subcommand_args = args.subcommand_matches("import").unwrap();
+
+            import_main(
+                subcommand_args
+                    .get_many::<String>("input_files")
+                    .unwrap()
+                    .cloned()
+                    .collect(),
+                match subcommand_args.get_flag("favorites") {
+                    true => SongList::Favorites,
+                    false => SongList::History,
+                },
+                subcommand_args.get_flag("dry-run"),
+            )?;
+        }
+        Some("favorites") => {
+            let subcommand_args = args.subcommand_matches("favorites").unwrap();
+
+            match subcommand_args.subcommand() {
+                Some(("add", add_args)) => {
+                    favorites_main(add_args.get_one::<String>("song").unwrap(), true)?;
+                }
+                Some(("remove", remove_args)) => {
+                    favorites_main(remove_args.get_one::<String>("song").unwrap(), false)?;
+                }
+                _ => unreachable!(),
+            }
+        }
+        Some("captures") => {
+            let subcommand_args = args.subcommand_matches("captures").unwrap();
+
+            captures_main(subcommand_args.get_flag("json"))?;
+        }
+        Some("index") => {
+            let subcommand_args = args.subcommand_matches("index").unwrap();
+
+            let database_path = match subcommand_args.get_one::<String>("database") {
+                Some(path) => PathBuf::from(path),
+                None => obtain_local_database_path()?,
+            };
+
+            let mut database = if subcommand_args.get_flag("rebuild") || !database_path.exists() {
+                LocalDatabase::default()
+            } else {
+                LocalDatabase::load(&database_path)?
+            };
+
+            let number_indexed = database.index_directory(&PathBuf::from(
+                subcommand_args.get_one::<String>("directory").unwrap(),
+            ))?;
+
+            database.save(&database_path)?;
+
+            println!("{} {}", gettext("Newly indexed files:"), number_indexed);
+            println!(
+                "{} {}",
+                gettext("Total files in the local database:"),
+                database.number_tracks()
+            );
+            println!(
+                "{} {}",
+                gettext("Local database saved to:"),
+                database_path.display()
+            );
+        }
         Some("listen") => {
             let subcommand_args = args.subcommand_matches("listen").unwrap();
             let list_devices = subcommand_args.get_flag("list-devices");
@@ -436,6 +1519,13 @@ fn main() -> Result<(), Box<dyn Error>> {
             let enable_mpris = !subcommand_args.get_flag("disable-mpris");
             let enable_json = subcommand_args.get_flag("json");
             let enable_csv = subcommand_args.get_flag("csv");
+            let recognition_backend = match subcommand_args.get_flag("local-db") {
+                true => Some("local".to_string()),
+                false => subcommand_args.get_one::<String>("backend").cloned(),
+            };
+            let recognition_backend_url = subcommand_args.get_one::<String>("backend-url").cloned();
+            let local_database_path = subcommand_args.get_one::<String>("local-db-path").cloned();
+            let record_history = !subcommand_args.get_flag("no-history");
 
             cli_main(CLIParameters {
                 enable_mpris,
@@ -451,8 +1541,57 @@ fn main() -> Result<(), Box<dyn Error>> {
                 } else {
                     CLIOutputType::SongName
                 },
+                recognition_backend,
+                recognition_backend_url,
+                local_database_path,
+                record_history,
+                enable_scrobbling: !subcommand_args.get_flag("no-scrobble"),
+                listenbrainz_url: subcommand_args
+                    .get_one::<String>("listenbrainz-url")
+                    .cloned(),
+                listenbrainz_token: subcommand_args
+                    .get_one::<String>("listenbrainz-token")
+                    .cloned(),
+                enable_webhooks: !subcommand_args.get_flag("no-webhooks"),
+                enable_dbus: !subcommand_args.get_flag("disable-dbus"),
+                http_server_address: subcommand_args.get_one::<String>("http-server").cloned(),
+                capture_window_secs: subcommand_args.get_one::<u64>("window").copied(),
+                voting_windows: subcommand_args.get_one::<u64>("voting-windows").copied(),
+                capture_archive: subcommand_args.get_flag("capture-archive"),
+                audio_gating: !subcommand_args.get_flag("no-audio-gating"),
+            })?;
+        }
+        #[cfg(unix)]
+        Some("daemon") => {
+            let subcommand_args = args.subcommand_matches("daemon").unwrap();
+
+            daemon_main(CLIDaemonParameters {
+                socket_path: subcommand_args.get_one::<String>("socket").cloned(),
+                audio_device: subcommand_args.get_one::<String>("audio-device").cloned(),
+                start_idle: subcommand_args.get_flag("idle"),
+                request_interval: *subcommand_args.get_one::<u64>("request-interval").unwrap(),
+                recognition_backend: match subcommand_args.get_flag("local-db") {
+                    true => Some("local".to_string()),
+                    false => subcommand_args.get_one::<String>("backend").cloned(),
+                },
+                recognition_backend_url: subcommand_args.get_one::<String>("backend-url").cloned(),
+                local_database_path: subcommand_args.get_one::<String>("local-db-path").cloned(),
+                record_history: !subcommand_args.get_flag("no-history"),
+                http_server_address: subcommand_args.get_one::<String>("http-server").cloned(),
+                capture_window_secs: subcommand_args.get_one::<u64>("window").copied(),
+                voting_windows: subcommand_args.get_one::<u64>("voting-windows").copied(),
+                capture_archive: subcommand_args.get_flag("capture-archive"),
+                audio_gating: !subcommand_args.get_flag("no-audio-gating"),
             })?;
         }
+        #[cfg(not(unix))]
+        Some("daemon") => {
+            eprintln!(
+                "{}",
+                gettext("The daemon mode is only available on Unix-like systems")
+            );
+            std::process::exit(1);
+        }
         Some("recognize") => {
             let subcommand_args = args.subcommand_matches("recognize").unwrap();
             let list_devices = subcommand_args.get_flag("list-devices");
@@ -461,6 +1600,13 @@ fn main() -> Result<(), Box<dyn Error>> {
             let input_file = subcommand_args.get_one::<String>("input_file").cloned();
             let enable_json = subcommand_args.get_flag("json");
             let enable_csv = subcommand_args.get_flag("csv");
+            let recognition_backend = match subcommand_args.get_flag("local-db") {
+                true => Some("local".to_string()),
+                false => subcommand_args.get_one::<String>("backend").cloned(),
+            };
+            let recognition_backend_url = subcommand_args.get_one::<String>("backend-url").cloned();
+            let local_database_path = subcommand_args.get_one::<String>("local-db-path").cloned();
+            let record_history = !subcommand_args.get_flag("no-history");
 
             cli_main(CLIParameters {
                 enable_mpris: false,
@@ -477,6 +1623,20 @@ fn main() -> Result<(), Box<dyn Error>> {
                 } else {
                     CLIOutputType::SongName
                 },
+                recognition_backend,
+                recognition_backend_url,
+                local_database_path,
+                record_history,
+                enable_scrobbling: false,
+                listenbrainz_url: None,
+                listenbrainz_token: None,
+                enable_webhooks: false,
+                enable_dbus: false,
+                http_server_address: None,
+                capture_window_secs: subcommand_args.get_one::<u64>("window").copied(),
+                voting_windows: subcommand_args.get_one::<u64>("voting-windows").copied(),
+                capture_archive: false,
+                audio_gating: false,
             })?;
         }
         Some("microphone-to-recognized-song") => {
@@ -486,6 +1646,12 @@ fn main() -> Result<(), Box<dyn Error>> {
             let list_devices = subcommand_args.get_flag("list-devices");
             let audio_device = subcommand_args.get_one::<String>("audio-device").cloned();
             let request_interval = *subcommand_args.get_one::<u64>("request-interval").unwrap();
+            let recognition_backend = match subcommand_args.get_flag("local-db") {
+                true => Some("local".to_string()),
+                false => subcommand_args.get_one::<String>("backend").cloned(),
+            };
+            let recognition_backend_url = subcommand_args.get_one::<String>("backend-url").cloned();
+            let local_database_path = subcommand_args.get_one::<String>("local-db-path").cloned();
 
             cli_main(CLIParameters {
                 enable_mpris: false,
@@ -495,6 +1661,20 @@ fn main() -> Result<(), Box<dyn Error>> {
                 request_interval,
                 input_file: None,
                 output_type: CLIOutputType::JSON,
+                recognition_backend,
+                recognition_backend_url,
+                local_database_path,
+                record_history: false,
+                enable_scrobbling: false,
+                listenbrainz_url: None,
+                listenbrainz_token: None,
+                enable_webhooks: false,
+                enable_dbus: false,
+                http_server_address: None,
+                capture_window_secs: None,
+                voting_windows: None,
+                capture_archive: false,
+                audio_gating: false,
             })?;
         }
         #[cfg(feature = "gui")]
@@ -533,6 +1713,20 @@ fn main() -> Result<(), Box<dyn Error>> {
                 request_interval: 10,
                 input_file: None,
                 output_type: CLIOutputType::SongName,
+                recognition_backend: None,
+                recognition_backend_url: None,
+                local_database_path: None,
+                record_history: false,
+                enable_scrobbling: false,
+                listenbrainz_url: None,
+                listenbrainz_token: None,
+                enable_webhooks: false,
+                enable_dbus: false,
+                http_server_address: None,
+                capture_window_secs: None,
+                voting_windows: None,
+                capture_archive: false,
+                audio_gating: false,
             })?;
         }
         _ => unreachable!(),
@@ -0,0 +1,210 @@
+//! A D-Bus interface allowing other programs to drive SongRec: starting and
+//! stopping the recognition from an audio device, recognizing a file,
+//! selecting the audio device and reading the song history, plus a signal
+//! emitted for each recognized song. For example:
+//!
+//! busctl --user call re.fossplant.songrec.Control /re/fossplant/songrec/Control \
+//!     re.fossplant.songrec.Control StartRecognition s ""
+//!
+//! The requests are handed to the GUI or to the "listen" command through
+//! `GUIMessage::RemoteControl`, so that they can keep their own state in sync.
+
+use log::{error, info};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+use crate::core::thread_messages::{
+    ControlRequest, DeviceListItem, GUIMessage, SongRecognizedMessage,
+};
+use crate::utils::filesystem_operations::obtain_history_database_path;
+use crate::utils::history_database::{HistoryDatabase, HistoryQuery};
+
+const CONTROL_BUS_NAME: &str = "re.fossplant.songrec.Control";
+const CONTROL_OBJECT_PATH: &str = "/re/fossplant/songrec/Control";
+
+struct ControlInterface {
+    gui_tx: async_channel::Sender<GUIMessage>,
+    /// The last list of audio devices received from the microphone thread
+    devices: Arc<Mutex<Vec<DeviceListItem>>>,
+}
+
+impl ControlInterface {
+    fn request(&self, request: ControlRequest) -> fdo::Result<()> {
+        self.gui_tx
+            .try_send(GUIMessage::RemoteControl(request))
+            .map_err(|error| fdo::Error::Failed(error.to_string()))
+    }
+
+    fn check_device(&self, device_name: &str) -> fdo::Result<()> {
+        let devices = self.devices.lock().unwrap();
+        match devices
+            .iter()
+            .any(|device| device.inner_name == device_name)
+        {
+            true => Ok(()),
+            false => Err(fdo::Error::InvalidArgs(format!(
+                "Unknown audio device: {}",
+                device_name
+            ))),
+        }
+    }
+}
+
+fn recent_songs(count: u32) -> Result<Vec<HashMap<String, String>>, Box<dyn Error>> {
+    let database = HistoryDatabase::open(&obtain_history_database_path()?)?;
+    let rows = database.query(&HistoryQuery {
+        descending: true,
+        limit: Some(count as u64),
+        ..Default::default()
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let mut song = HashMap::from([
+                ("song_name".to_string(), row.song_name),
+                ("recognized_at".to_string(), row.recognized_at),
+                ("is_favorite".to_string(), row.is_favorite.to_string()),
+            ]);
+            for (key, value) in [
+                ("artist_name", row.artist_name),
+                ("album", row.album),
+                ("track_key", row.track_key),
+                ("release_year", row.release_year),
+                ("genre", row.genre),
+                ("isrc", row.isrc),
+                ("label", row.label),
+            ] {
+                if let Some(value) = value {
+                    song.insert(key.to_string(), value);
+                }
+            }
+            song
+        })
+        .collect())
+}
+
+#[zbus::interface(name = "re.fossplant.songrec.Control")]
+impl ControlInterface {
+    /// Start recognizing from the given audio device, or from the current
+    /// one when empty.
+    async fn start_recognition(&self, device_name: String) -> fdo::Result<()> {
+        if device_name.is_empty() {
+            return self.request(ControlRequest::StartRecognition(None));
+        }
+        self.check_device(&device_name)?;
+        self.request(ControlRequest::StartRecognition(Some(device_name)))
+    }
+
+    async fn stop_recognition(&self) -> fdo::Result<()> {
+        self.request(ControlRequest::StopRecognition)
+    }
+
+    async fn recognize_file(&self, path: String) -> fdo::Result<()> {
+        if !Path::new(&path).is_file() {
+            return Err(fdo::Error::FileNotFound(path));
+        }
+        self.request(ControlRequest::RecognizeFile(path))
+    }
+
+    /// The internal name, display name and whether it records the audio
+    /// output, for each audio device.
+    async fn list_devices(&self) -> Vec<(String, String, bool)> {
+        self.devices
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|device| {
+                (
+                    device.inner_name.clone(),
+                    device.display_name.clone(),
+                    device.is_monitor,
+                )
+            })
+            .collect()
+    }
+
+    /// Use another audio device, the recognition being restarted on it if
+    /// it was running.
+    async fn select_device(&self, device_name: String) -> fdo::Result<()> {
+        self.check_device(&device_name)?;
+        self.request(ControlRequest::SelectDevice(device_name))
+    }
+
+    /// The last entries of the song history, most recent first.
+    async fn recent_songs(&self, count: u32) -> fdo::Result<Vec<HashMap<String, String>>> {
+        recent_songs(count).map_err(|error| fdo::Error::Failed(error.to_string()))
+    }
+
+    #[zbus(signal)]
+    async fn song_recognized(
+        emitter: &SignalEmitter<'_>,
+        artist_name: &str,
+        song_name: &str,
+        album_name: &str,
+        track_key: &str,
+    ) -> zbus::Result<()>;
+}
+
+pub struct ControlService {
+    connection: zbus::Connection,
+    devices: Arc<Mutex<Vec<DeviceListItem>>>,
+}
+
+impl ControlService {
+    pub async fn start(gui_tx: async_channel::Sender<GUIMessage>) -> Option<Self> {
+        let devices = Arc::new(Mutex::new(vec![]));
+        let interface = ControlInterface {
+            gui_tx,
+            devices: devices.clone(),
+        };
+
+        let connection = async {
+            zbus::connection::Builder::session()?
+                .name(CONTROL_BUS_NAME)?
+                .serve_at(CONTROL_OBJECT_PATH, interface)?
+                .build()
+                .await
+        };
+        match connection.await {
+            Ok(connection) => {
+                info!("D-Bus control interface available as {}", CONTROL_BUS_NAME);
+                Some(ControlService {
+                    connection,
+                    devices,
+                })
+            }
+            Err(error) => {
+                error!("Could not register the D-Bus control interface: {}", error);
+                None
+            }
+        }
+    }
+
+    pub fn set_devices(&self, devices: &[DeviceListItem]) {
+        *self.devices.lock().unwrap() = devices.to_vec();
+    }
+
+    pub async fn song_recognized(&self, message: &SongRecognizedMessage) {
+        let result = match SignalEmitter::new(&self.connection, CONTROL_OBJECT_PATH) {
+            Ok(emitter) => {
+                ControlInterface::song_recognized(
+                    &emitter,
+                    &message.artist_name,
+                    &message.song_name,
+                    message.album_name.as_deref().unwrap_or_default(),
+                    &message.track_key,
+                )
+                .await
+            }
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            error!("Could not emit the SongRecognized D-Bus signal: {}", error);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Writing the metadata of a recognized song into the tags of the audio file
+//! it was recognized from (ID3v2 for MP3, Vorbis comments for Ogg and FLAC,
+//! MP4 atoms for M4A...), through the lofty library.
+
+use gettextrs::gettext;
+use lofty::config::WriteOptions;
+use lofty::picture::{Picture, PictureType};
+use lofty::prelude::*;
+use lofty::tag::Tag;
+use std::error::Error;
+
+use crate::core::thread_messages::SongRecognizedMessage;
+
+pub struct TagChange {
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: String,
+}
+
+pub struct TaggingOptions {
+    /// Only compute the changes, don't write them to the file
+    pub dry_run: bool,
+    /// Don't overwrite the fields which already have a value
+    pub only_fill_empty: bool,
+}
+
+/// Write the song information to the primary tag of the file (created if
+/// missing), returning the list of the fields that were changed.
+pub fn tag_file(
+    file_path: &str,
+    message: &SongRecognizedMessage,
+    options: &TaggingOptions,
+) -> Result<Vec<TagChange>, Box<dyn Error>> {
+    let mut tagged_file = lofty::read_from_path(file_path)?;
+
+    if tagged_file.primary_tag().is_none() {
+        tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
+    }
+    let tag = tagged_file.primary_tag_mut().unwrap();
+
+    let mut changes = vec![];
+
+    let text_fields = [
+        (
+            gettext("Title"),
+            ItemKey::TrackTitle,
+            Some(&message.song_name),
+        ),
+        (
+            gettext("Artist"),
+            ItemKey::TrackArtist,
+            Some(&message.artist_name),
+        ),
+        (
+            gettext("Album"),
+            ItemKey::AlbumTitle,
+            message.album_name.as_ref(),
+        ),
+        (
+            gettext("Year"),
+            ItemKey::RecordingDate,
+            message.release_year.as_ref(),
+        ),
+        (gettext("Genre"), ItemKey::Genre, message.genre.as_ref()),
+        (gettext("ISRC"), ItemKey::Isrc, message.isrc.as_ref()),
+    ];
+
+    for (field, item_key, new_value) in text_fields {
+        let Some(new_value) = new_value.filter(|value| !value.is_empty()) else {
+            continue;
+        };
+        let old_value = tag
+            .get_string(item_key)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.to_string());
+
+        if old_value.as_ref() == Some(new_value) || (options.only_fill_empty && old_value.is_some())
+        {
+            continue;
+        }
+
+        tag.insert_text(item_key, new_value.clone());
+
+        changes.push(TagChange {
+            field,
+            old_value,
+            new_value: new_value.clone(),
+        });
+    }
+
+    if let Some(ref cover_image) = message.cover_image {
+        let old_cover = tag.get_picture_type(PictureType::CoverFront);
+
+        let describe = |picture: &Picture| {
+            format!(
+                "{} ({} {})",
+                picture
+                    .mime_type()
+                    .map(|mime_type| mime_type.to_string())
+                    .unwrap_or_default(),
+                picture.data().len(),
+                gettext("bytes")
+            )
+        };
+
+        if old_cover.map(|picture| picture.data()) != Some(&cover_image[..])
+            && !(options.only_fill_empty && old_cover.is_some())
+        {
+            let mut new_cover = Picture::from_reader(&mut &cover_image[..])?;
+            new_cover.set_pic_type(PictureType::CoverFront);
+
+            changes.push(TagChange {
+                field: gettext("Cover art"),
+                old_value: old_cover.map(describe),
+                new_value: describe(&new_cover),
+            });
+
+            tag.remove_picture_type(PictureType::CoverFront);
+            tag.push_picture(new_cover);
+        }
+    }
+
+    if !options.dry_run && !changes.is_empty() {
+        tagged_file.save_to_path(file_path, WriteOptions::default())?;
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write one second of silence as a 16-bit mono WAV file.
+    fn write_silent_wav(path: &std::path::Path) {
+        let number_samples: u32 = 16000;
+        let data_size = number_samples * 2;
+
+        let mut data = vec![];
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + data_size).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&32000u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&data_size.to_le_bytes());
+        data.resize(data.len() + data_size as usize, 0);
+
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn png_cover() -> Vec<u8> {
+        let mut cover = std::io::Cursor::new(vec![]);
+        image::RgbImage::new(2, 2)
+            .write_to(&mut cover, image::ImageFormat::Png)
+            .unwrap();
+        cover.into_inner()
+    }
+
+    fn message(song_name: &str, genre: &str, isrc: Option<&str>) -> SongRecognizedMessage {
+        SongRecognizedMessage {
+            artist_name: "Daft Punk".to_string(),
+            album_name: Some("Homework".to_string()),
+            song_name: song_name.to_string(),
+            cover_image: Some(png_cover()),
+            track_key: "20066955".to_string(),
+            release_year: Some("1997".to_string()),
+            genre: Some(genre.to_string()),
+            isrc: isrc.map(str::to_string),
+            label: None,
+            match_offset_secs: None,
+            frequency_skew: None,
+            time_skew: None,
+            lyrics: vec![],
+            streaming_links: vec![],
+            related_tracks_url: None,
+            shazam_json: "{}".to_string(),
+            capture_path: None,
+        }
+    }
+
+    fn read_tag(path: &str, item_key: ItemKey) -> Option<String> {
+        lofty::read_from_path(path)
+            .unwrap()
+            .primary_tag()
+            .and_then(|tag| tag.get_string(item_key).map(str::to_string))
+    }
+
+    #[test]
+    fn test_tag_file_round_trip() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("track.wav");
+        write_silent_wav(&path);
+        let path = path.to_str().unwrap();
+
+        let write = TaggingOptions {
+            dry_run: false,
+            only_fill_empty: false,
+        };
+
+        // A dry run reports the changes without writing them
+
+        let changes = tag_file(
+            path,
+            &message("Around the World", "Electronic", None),
+            &TaggingOptions {
+                dry_run: true,
+                only_fill_empty: false,
+            },
+        )
+        .unwrap();
+        assert!(!changes.is_empty());
+        assert_eq!(read_tag(path, ItemKey::TrackTitle), None);
+
+        tag_file(
+            path,
+            &message("Around the World", "Electronic", None),
+            &write,
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_tag(path, ItemKey::TrackTitle).as_deref(),
+            Some("Around the World")
+        );
+        assert_eq!(
+            read_tag(path, ItemKey::TrackArtist).as_deref(),
+            Some("Daft Punk")
+        );
+        assert_eq!(
+            read_tag(path, ItemKey::AlbumTitle).as_deref(),
+            Some("Homework")
+        );
+        assert_eq!(
+            read_tag(path, ItemKey::Genre).as_deref(),
+            Some("Electronic")
+        );
+        assert_eq!(
+            lofty::read_from_path(path)
+                .unwrap()
+                .primary_tag()
+                .unwrap()
+                .pictures()
+                .len(),
+            1
+        );
+
+        // Tagging again with the same information changes nothing
+
+        assert!(
+            tag_file(
+                path,
+                &message("Around the World", "Electronic", None),
+                &write
+            )
+            .unwrap()
+            .is_empty()
+        );
+
+        // Keeping the existing tags only fills in the missing fields
+
+        let changes = tag_file(
+            path,
+            &message("Da Funk", "House", Some("GBDUW0600007")),
+            &TaggingOptions {
+                dry_run: false,
+                only_fill_empty: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].new_value, "GBDUW0600007");
+        assert_eq!(
+            read_tag(path, ItemKey::TrackTitle).as_deref(),
+            Some("Around the World")
+        );
+        assert_eq!(
+            read_tag(path, ItemKey::Genre).as_deref(),
+            Some("Electronic")
+        );
+        assert_eq!(
+            read_tag(path, ItemKey::Isrc).as_deref(),
+            Some("GBDUW0600007")
+        );
+    }
+}
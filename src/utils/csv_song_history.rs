@@ -21,6 +21,12 @@ pub struct SongHistoryRecord {
     #[serde(default)]
     pub genre: Option<String>,
     pub recognition_date: String,
+
+    // The following fields have been added in version 0.7.5
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -0,0 +1,349 @@
+//! Renaming and moving recognized audio files according to a template such
+//! as "{artist}/{album}/{title}.{ext}", with a journal of the moves allowing
+//! to undo them.
+
+use gettextrs::gettext;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::core::batch_recognition::BatchRecord;
+
+#[derive(Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub from: String,
+    pub to: String,
+}
+
+/// Make a template value safe to use as a single path component.
+fn sanitize(value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|character| match character {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            character if character.is_control() => '_',
+            character => character,
+        })
+        .collect();
+
+    // Leading dots would make hidden files (or "..")
+    let sanitized = sanitized.trim().trim_start_matches('.').trim_end();
+
+    match sanitized.is_empty() {
+        true => gettext("Unknown"),
+        false => sanitized.to_string(),
+    }
+}
+
+/// Compute the path of a recognized file from the template, relative to the
+/// destination directory.
+pub fn render_template(template: &str, record: &BatchRecord) -> PathBuf {
+    let extension = Path::new(&record.file)
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let fields = [
+        ("{artist}", &record.artist_name),
+        ("{title}", &record.song_name),
+        ("{album}", &record.album_name),
+        ("{year}", &record.release_year),
+        ("{genre}", &record.genre),
+        ("{isrc}", &record.isrc),
+    ];
+
+    let mut path = PathBuf::new();
+
+    // Placeholders are substituted separately within every component of the
+    // template, so that values can't introduce directory separators
+    for component in template
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        let mut rendered = component.replace("{ext}", &sanitize(&extension));
+        for (placeholder, value) in &fields {
+            if rendered.contains(placeholder) {
+                rendered = rendered.replace(placeholder, &sanitize(value.as_deref().unwrap_or("")));
+            }
+        }
+        path.push(rendered);
+    }
+
+    path
+}
+
+/// Append " (2)", " (3)"... to the file name until it designates neither an
+/// existing file nor a file already planned in this run.
+pub fn resolve_collision(target: PathBuf, planned_targets: &HashSet<PathBuf>) -> PathBuf {
+    if !target.exists() && !planned_targets.contains(&target) {
+        return target;
+    }
+
+    let stem = target
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|index| target.with_file_name(format!("{} ({}){}", stem, index, extension)))
+        .find(|candidate| !candidate.exists() && !planned_targets.contains(candidate))
+        .unwrap()
+}
+
+fn destination_exists_error(to: &Path) -> Box<dyn Error> {
+    Box::new(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!(
+            "{} {}",
+            gettext("The destination already exists:"),
+            to.display()
+        ),
+    ))
+}
+
+/// Copy a file to a destination which must not exist yet.
+fn copy_to_new_file(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    let mut source = std::fs::File::open(from)?;
+    let mut destination = match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)
+    {
+        Ok(destination) => destination,
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(destination_exists_error(to));
+        }
+        Err(error) => return Err(Box::new(error)),
+    };
+
+    let copied = std::io::copy(&mut source, &mut destination)
+        .and_then(|_| destination.set_permissions(source.metadata()?.permissions()));
+    if let Err(error) = copied {
+        // Don't leave a partial copy behind
+        drop(destination);
+        std::fs::remove_file(to).ok();
+        return Err(Box::new(error));
+    }
+    Ok(())
+}
+
+/// Move a file, creating the parent directories if needed, and falling back
+/// to copying when the destination is on another filesystem. An existing
+/// destination is never overwritten: the destination is created by linking
+/// or with "create_new", both of which fail when it already exists.
+pub fn move_file(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::hard_link(from, to) {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(destination_exists_error(to));
+        }
+        Err(_) => copy_to_new_file(from, to)?,
+    }
+    std::fs::remove_file(from)?;
+    Ok(())
+}
+
+pub fn append_to_journal(journal_path: &Path, entry: &JournalEntry) -> Result<(), Box<dyn Error>> {
+    let mut journal = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal_path)?;
+    writeln!(journal, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// The outcome of the undoing of a journal.
+#[derive(Default)]
+pub struct UndoReport {
+    /// The moves which were undone (or would be, in a dry run)
+    pub undone: Vec<JournalEntry>,
+    /// The moves which could not be undone, with the reason why. These stay
+    /// in the journal, except when the moved file no longer exists.
+    pub skipped: Vec<(JournalEntry, String)>,
+}
+
+fn read_journal(journal_path: &Path) -> Result<Vec<JournalEntry>, Box<dyn Error>> {
+    let mut entries: Vec<JournalEntry> = vec![];
+    for line in BufReader::new(std::fs::File::open(journal_path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
+
+/// Replace the contents of a journal, removing it when no entry remains.
+fn rewrite_journal(journal_path: &Path, entries: &[&JournalEntry]) -> Result<(), Box<dyn Error>> {
+    if entries.is_empty() {
+        std::fs::remove_file(journal_path)?;
+        return Ok(());
+    }
+
+    let mut temporary_path = journal_path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let temporary_path = PathBuf::from(temporary_path);
+
+    {
+        let mut journal = std::fs::File::create(&temporary_path)?;
+        for entry in entries {
+            writeln!(journal, "{}", serde_json::to_string(entry)?)?;
+        }
+    }
+
+    std::fs::rename(temporary_path, journal_path)?;
+    Ok(())
+}
+
+/// Move back the files listed in a journal, most recent moves first. Moves
+/// which can't be undone are skipped rather than aborting the whole undo,
+/// and the journal is rewritten with the entries that remain, so that the
+/// undo can be resumed.
+pub fn undo_journal(journal_path: &Path, dry_run: bool) -> Result<UndoReport, Box<dyn Error>> {
+    let mut entries = read_journal(journal_path)?;
+    entries.reverse();
+
+    // For every entry: whether it was undone, and whether it stays in the
+    // journal
+    let mut outcomes: Vec<(Result<(), String>, bool)> = vec![];
+
+    for entry in &entries {
+        let (from, to) = (Path::new(&entry.from), Path::new(&entry.to));
+
+        outcomes.push(if to.symlink_metadata().is_err() {
+            // Nothing left to move back, forget about this entry
+            (Err(gettext("The moved file no longer exists")), false)
+        } else if dry_run && from.symlink_metadata().is_ok() {
+            let reason = format!(
+                "{} {}",
+                gettext("The destination already exists:"),
+                from.display()
+            );
+            (Err(reason), true)
+        } else if dry_run {
+            (Ok(()), false)
+        } else {
+            match move_file(to, from) {
+                Ok(()) => (Ok(()), false),
+                Err(error) => (Err(error.to_string()), true),
+            }
+        });
+    }
+
+    if !dry_run {
+        let remaining: Vec<&JournalEntry> = entries
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, (_, keep))| *keep)
+            .map(|(entry, _)| entry)
+            .rev()
+            .collect();
+        rewrite_journal(journal_path, &remaining)?;
+    }
+
+    let mut report = UndoReport::default();
+
+    for (entry, (result, _)) in entries.into_iter().zip(outcomes) {
+        match result {
+            Ok(()) => report.undone.push(entry),
+            Err(reason) => report.skipped.push((entry, reason)),
+        }
+    }
+
+    Ok(report)
+}
+
+#[test]
+fn test_undo_journal_skips_and_resumes() {
+    let directory = tempfile::tempdir().unwrap();
+    let journal_path = directory.path().join("journal.jsonl");
+
+    let path = |name: &str| directory.path().join(name).to_string_lossy().into_owned();
+
+    // "a" was moved to "sorted/a" and "b" to "sorted/b", then "sorted/b" was
+    // deleted, and a new file was created at "c" after "c" was moved
+    for (from, to) in [("a", "sorted/a"), ("b", "sorted/b"), ("c", "sorted/c")] {
+        std::fs::write(path(from), from).unwrap();
+        move_file(Path::new(&path(from)), Path::new(&path(to))).unwrap();
+        append_to_journal(
+            &journal_path,
+            &JournalEntry {
+                from: path(from),
+                to: path(to),
+            },
+        )
+        .unwrap();
+    }
+    std::fs::remove_file(path("sorted/b")).unwrap();
+    std::fs::write(path("c"), "new c").unwrap();
+
+    let report = undo_journal(&journal_path, false).unwrap();
+
+    assert_eq!(report.undone.len(), 1);
+    assert_eq!(report.undone[0].from, path("a"));
+    assert_eq!(report.skipped.len(), 2);
+    assert_eq!(std::fs::read_to_string(path("a")).unwrap(), "a");
+    assert_eq!(std::fs::read_to_string(path("c")).unwrap(), "new c");
+    assert_eq!(std::fs::read_to_string(path("sorted/c")).unwrap(), "c");
+
+    // Only the entry which can still be undone stays in the journal
+    let remaining = read_journal(&journal_path).unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].from, path("c"));
+
+    std::fs::remove_file(path("c")).unwrap();
+
+    let report = undo_journal(&journal_path, false).unwrap();
+    assert_eq!(report.undone.len(), 1);
+    assert!(report.skipped.is_empty());
+    assert_eq!(std::fs::read_to_string(path("c")).unwrap(), "c");
+    assert!(!journal_path.exists());
+}
+
+#[test]
+fn test_render_template() {
+    let record = BatchRecord {
+        file: "/music/incoming/track01.MP3".to_string(),
+        artist_name: Some("AC/DC".to_string()),
+        song_name: Some("..Who: Made Who?".to_string()),
+        album_name: None,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        render_template("{artist}/{album}/{title}.{ext}", &record),
+        PathBuf::from("AC_DC")
+            .join(gettext("Unknown"))
+            .join("Who_ Made Who_.MP3")
+    );
+}
+
+#[test]
+fn test_move_file_never_overwrites() {
+    let directory = tempfile::tempdir().unwrap();
+    let from = directory.path().join("song.mp3");
+    let to = directory.path().join("Artist").join("Song.mp3");
+    std::fs::write(&from, b"song").unwrap();
+
+    move_file(&from, &to).unwrap();
+    assert!(!from.exists());
+    assert_eq!(std::fs::read(&to).unwrap(), b"song");
+
+    std::fs::write(&from, b"other song").unwrap();
+    assert!(move_file(&from, &to).is_err());
+    assert_eq!(std::fs::read(&from).unwrap(), b"other song");
+    assert_eq!(std::fs::read(&to).unwrap(), b"song");
+
+    // The fallback used across filesystems
+    assert!(copy_to_new_file(&from, &to).is_err());
+    assert_eq!(std::fs::read(&to).unwrap(), b"song");
+}
@@ -6,13 +6,18 @@ use std::fs::create_dir_all;
 use std::os::unix::fs::symlink;
 #[cfg(windows)]
 use std::os::windows::fs::symlink_dir;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
 const QUALIFIER: &str = "";
 const ORGANIZATION: &str = "SongRec";
 const APPLICATION: &str = "SongRec";
 
+const AUDIO_FILE_EXTENSIONS: [&str; 14] = [
+    "mp3", "flac", "wav", "ogg", "oga", "opus", "m4a", "mp4", "aac", "aif", "aiff", "wma", "mka",
+    "webm",
+];
+
 static PROJECT_DIRS: LazyLock<ProjectDirs> =
     LazyLock::new(|| ProjectDirs::from(QUALIFIER, ORGANIZATION, APPLICATION).unwrap());
 
@@ -28,6 +33,92 @@ pub fn obtain_favorites_csv_path() -> Result<PathBuf, Box<dyn Error>> {
     Ok(csv_path)
 }
 
+pub fn obtain_history_database_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut database_path = obtain_data_directory()?;
+    database_path.push("history.sqlite");
+    Ok(database_path)
+}
+
+/// The cover arts of the recognized songs are kept along the history, named
+/// after the track key, the size and a checksum of their contents (a
+/// checksum alone would collide across thousands of covers).
+pub fn obtain_cover_art_path(
+    track_key: &str,
+    cover_image: &[u8],
+) -> Result<PathBuf, Box<dyn Error>> {
+    let mut cover_art_path = obtain_data_directory()?;
+    cover_art_path.push("cover_arts");
+    create_dir_all(&cover_art_path)?;
+
+    // Track keys of the local database contain file paths
+    let track_key: String = track_key
+        .chars()
+        .map(|character| match character.is_ascii_alphanumeric() {
+            true => character,
+            false => '_',
+        })
+        .take(64)
+        .collect();
+
+    cover_art_path.push(format!(
+        "{}-{}-{:08x}.jpg",
+        track_key,
+        cover_image.len(),
+        crc32fast::hash(cover_image)
+    ));
+    Ok(cover_art_path)
+}
+
+/// The audio recorded for the recognition requests, when the capture archive
+/// is enabled.
+pub fn obtain_capture_archive_directory() -> Result<PathBuf, Box<dyn Error>> {
+    let mut archive_directory = obtain_data_directory()?;
+    archive_directory.push("captures");
+    create_dir_all(&archive_directory)?;
+    Ok(archive_directory)
+}
+
+/// The listens waiting to be submitted to the scrobbling server.
+pub fn obtain_scrobbling_queue_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut queue_path = obtain_data_directory()?;
+    queue_path.push("scrobbling_queue.jsonl");
+    Ok(queue_path)
+}
+
+/// The Unix socket of the daemon mode, in the runtime directory when there
+/// is one.
+#[cfg(unix)]
+pub fn obtain_daemon_socket_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut socket_path = match PROJECT_DIRS.runtime_dir() {
+        Some(runtime_dir) => {
+            create_dir_all(runtime_dir)?;
+            runtime_dir.to_path_buf()
+        }
+        None => obtain_cache_directory()?,
+    };
+    socket_path.push("daemon.sock");
+    Ok(socket_path)
+}
+
+pub fn obtain_local_database_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut database_path = obtain_data_directory()?;
+    database_path.push("local_database.bin");
+    Ok(database_path)
+}
+
+/// A new journal file for the moves made by the organize mode, named after
+/// the current date and time.
+pub fn obtain_organize_journal_path() -> Result<PathBuf, Box<dyn Error>> {
+    let mut journal_path = obtain_data_directory()?;
+    journal_path.push("organize_journals");
+    create_dir_all(&journal_path)?;
+    journal_path.push(format!(
+        "{}.jsonl",
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
+    Ok(journal_path)
+}
+
 pub fn obtain_preferences_file_path() -> Result<PathBuf, Box<dyn Error>> {
     let mut preferences_file_path = obtain_preferences_directory()?;
     preferences_file_path.push("preferences.toml");
@@ -90,3 +181,10 @@ fn get_old_data_dir_path() -> Result<PathBuf, Box<dyn Error>> {
 
     Ok(get_app_root(UserData, &app_info)?)
 }
+
+/// Whether a file looks like an audio file, judging from its extension.
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| AUDIO_FILE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
@@ -0,0 +1,879 @@
+//! The recognition history and the favorites are stored in a SQLite database,
+//! in which new recognitions are appended as rows rather than rewriting the
+//! whole list each time. Each row also keeps the details which don't fit in
+//! the CSV format: the ISO-8601 timestamp of the recognition (in UTC, so that
+//! timestamps sort chronologically), the audio device which was listened, the
+//! raw Shazam response and the location of the cover art.
+//!
+//! The schema is versioned through SQLite's "user_version" pragma. The first
+//! migration imports the CSV files used by the previous versions of SongRec,
+//! which are otherwise left untouched.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use log::{info, warn};
+use rusqlite::{
+    Connection, OptionalExtension, ToSql, Transaction, TransactionBehavior, params,
+    params_from_iter,
+};
+use serde::Serialize;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::utils::csv_song_history::{Song, SongHistoryRecord};
+use crate::utils::filesystem_operations::{
+    obtain_cover_art_path, obtain_favorites_csv_path, obtain_recognition_history_csv_path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SongList {
+    #[default]
+    History,
+    Favorites,
+}
+
+impl SongList {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SongList::History => "history",
+            SongList::Favorites => "favorites",
+        }
+    }
+}
+
+/// The information stored alongside a `SongHistoryRecord`, when available.
+#[derive(Debug, Default, Clone)]
+pub struct RecognitionDetails {
+    pub artist_name: Option<String>,
+    pub device_name: Option<String>,
+    pub shazam_json: Option<String>,
+    pub cover_path: Option<String>,
+    pub capture_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum HistorySortKey {
+    #[default]
+    Date,
+    Artist,
+    Song,
+    Genre,
+}
+
+/// Filters for `HistoryDatabase::query`. Dates are ISO-8601 timestamps in UTC
+/// (see `parse_date_bound`).
+#[derive(Debug, Default)]
+pub struct HistoryQuery {
+    pub list: SongList,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Matched case-insensitively against a part of the artist name
+    pub artist: Option<String>,
+    pub genre: Option<String>,
+    pub is_favorite: Option<bool>,
+    pub sort_key: HistorySortKey,
+    pub descending: bool,
+    pub limit: Option<u64>,
+    pub offset: u64,
+    /// Only keep the most recent recognition of each song
+    pub deduplicate: bool,
+}
+
+/// A song history entry, with all the details stored in the database.
+#[derive(Debug, Serialize)]
+pub struct HistoryRow {
+    pub song_name: String,
+    pub artist_name: Option<String>,
+    pub album: Option<String>,
+    pub track_key: Option<String>,
+    pub release_year: Option<String>,
+    pub genre: Option<String>,
+    pub isrc: Option<String>,
+    pub label: Option<String>,
+    pub recognized_at: String,
+    pub device_name: Option<String>,
+    pub cover_path: Option<String>,
+    /// The archived audio the song was recognized from
+    pub capture_path: Option<String>,
+    pub is_favorite: bool,
+    /// The number of recognitions of the song, when deduplicating
+    pub times_recognized: i64,
+    pub shazam_json: Option<serde_json::Value>,
+}
+
+impl HistoryRow {
+    pub fn to_song_history_record(&self) -> SongHistoryRecord {
+        SongHistoryRecord {
+            song_name: self.song_name.clone(),
+            album: self.album.clone(),
+            track_key: self.track_key.clone(),
+            release_year: self.release_year.clone(),
+            genre: self.genre.clone(),
+            recognition_date: format_recognition_date(&self.recognized_at),
+            isrc: self.isrc.clone(),
+            label: self.label.clone(),
+        }
+    }
+}
+
+/// Whether a history entry (aliased "s") is also in the favorites, following
+/// the definition of `Song`.
+const IS_FAVORITE_EXPRESSION: &str = "EXISTS (SELECT 1 FROM songs f WHERE f.list = 'favorites'
+    AND f.song_name = s.song_name AND f.album IS s.album AND f.track_key IS s.track_key
+    AND f.release_year IS s.release_year AND f.genre IS s.genre)";
+
+/// Each migration brings the schema from the version equal to its index to
+/// the next one.
+type Migration = fn(&Transaction, &LegacyCsvFiles) -> Result<(), Box<dyn Error>>;
+
+const MIGRATIONS: [Migration; 2] = [create_tables_and_import_csv_files, add_capture_path];
+
+struct LegacyCsvFiles {
+    history: Option<PathBuf>,
+    favorites: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct HistoryDatabase {
+    connection: Connection,
+}
+
+/// Dates are displayed and exported to CSV in the "%c" format, which is
+/// also the format of the dates of the older CSV files.
+pub fn parse_recognition_date(recognition_date: &str) -> Option<DateTime<Local>> {
+    NaiveDateTime::parse_from_str(recognition_date, "%c")
+        .ok()
+        .and_then(|date| Local.from_local_datetime(&date).earliest())
+}
+
+/// Save a cover art for reference from the database, returning its path.
+pub fn store_cover_art(track_key: &str, cover_image: &[u8]) -> Result<String, Box<dyn Error>> {
+    let cover_art_path = obtain_cover_art_path(track_key, cover_image)?;
+    if !cover_art_path.exists() {
+        std::fs::write(&cover_art_path, cover_image)?;
+    }
+    Ok(cover_art_path.to_string_lossy().into_owned())
+}
+
+/// Make the wildcards of a user-provided string match literally within a
+/// "LIKE ... ESCAPE '\'" pattern.
+fn escape_like_pattern(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn to_timestamp<Tz: TimeZone>(date: DateTime<Tz>) -> String {
+    date.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Parse a date given on the command line, either as "YYYY-MM-DD" (meaning
+/// the start of the day, or its end when `end_of_day` is set) or as a full
+/// RFC 3339 date.
+pub fn parse_date_bound(value: &str, end_of_day: bool) -> Result<String, Box<dyn Error>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(to_timestamp(date));
+    }
+    let mut date = NaiveDate::parse_from_str(value, "%Y-%m-%d")?;
+    if end_of_day {
+        date = date.succ_opt().ok_or("Invalid date")?;
+    }
+    let date = Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .ok_or("Invalid date")?;
+    Ok(to_timestamp(date))
+}
+
+fn format_recognition_date(recognized_at: &str) -> String {
+    match DateTime::parse_from_rfc3339(recognized_at) {
+        Ok(date) => date.with_timezone(&Local).format("%c").to_string(),
+        Err(_) => recognized_at.to_string(),
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.is_empty())
+}
+
+fn insert_row(
+    transaction: &Transaction,
+    list: SongList,
+    record: &SongHistoryRecord,
+    details: &RecognitionDetails,
+) -> Result<(), Box<dyn Error>> {
+    let recognized_at = parse_recognition_date(&record.recognition_date).unwrap_or_else(Local::now);
+
+    // Song names are stored as "Artist - Title" in the CSV format
+    let artist_name = details.artist_name.clone().or_else(|| {
+        record
+            .song_name
+            .split_once(" - ")
+            .map(|(artist_name, _)| artist_name.to_string())
+    });
+
+    transaction.execute(
+        "INSERT INTO songs (list, song_name, artist_name, album, track_key, release_year,
+            genre, isrc, label, recognized_at, device_name, shazam_json, cover_path)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            list.as_str(),
+            record.song_name,
+            artist_name,
+            non_empty(&record.album),
+            non_empty(&record.track_key),
+            non_empty(&record.release_year),
+            non_empty(&record.genre),
+            non_empty(&record.isrc),
+            non_empty(&record.label),
+            to_timestamp(recognized_at),
+            details.device_name,
+            details.shazam_json,
+            details.cover_path,
+        ],
+    )?;
+    Ok(())
+}
+
+fn create_tables_and_import_csv_files(
+    transaction: &Transaction,
+    legacy_csv_files: &LegacyCsvFiles,
+) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch(
+        "CREATE TABLE songs (
+            id INTEGER PRIMARY KEY,
+            list TEXT NOT NULL, -- \"history\" or \"favorites\"
+            song_name TEXT NOT NULL,
+            artist_name TEXT,
+            album TEXT,
+            track_key TEXT,
+            release_year TEXT,
+            genre TEXT,
+            isrc TEXT,
+            label TEXT,
+            recognized_at TEXT NOT NULL, -- ISO-8601
+            device_name TEXT,
+            shazam_json TEXT,
+            cover_path TEXT
+        );
+        CREATE INDEX songs_list_recognized_at ON songs (list, recognized_at);
+        CREATE INDEX songs_track_key ON songs (track_key);
+        CREATE INDEX songs_artist_name ON songs (artist_name);",
+    )?;
+
+    for (list, csv_path) in [
+        (SongList::History, &legacy_csv_files.history),
+        (SongList::Favorites, &legacy_csv_files.favorites),
+    ] {
+        let Some(csv_path) = csv_path.as_ref().filter(|path| path.exists()) else {
+            continue;
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(csv_path)?;
+
+        let mut number_imported = 0;
+        for result in reader.deserialize() {
+            // A malformed row must not prevent the rest of the history from
+            // being migrated
+            let record: SongHistoryRecord = match result {
+                Ok(record) => record,
+                Err(error) => {
+                    warn!("Skipping a row of {}: {}", csv_path.display(), error);
+                    continue;
+                }
+            };
+            // Don't make up a date, which would misplace the entry
+            if parse_recognition_date(&record.recognition_date).is_none() {
+                warn!(
+                    "Skipping a row of {} with an invalid date: {}",
+                    csv_path.display(),
+                    record.recognition_date
+                );
+                continue;
+            }
+            insert_row(transaction, list, &record, &RecognitionDetails::default())?;
+            number_imported += 1;
+        }
+        info!(
+            "Imported {} entries from {}",
+            number_imported,
+            csv_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn add_capture_path(
+    transaction: &Transaction,
+    _legacy_csv_files: &LegacyCsvFiles,
+) -> Result<(), Box<dyn Error>> {
+    transaction.execute_batch("ALTER TABLE songs ADD COLUMN capture_path TEXT;")?;
+    Ok(())
+}
+
+impl HistoryDatabase {
+    /// Open (or create) the database, applying the pending migrations.
+    pub fn open(database_path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::open_with_csv_files(
+            database_path,
+            LegacyCsvFiles {
+                history: obtain_recognition_history_csv_path().ok(),
+                favorites: obtain_favorites_csv_path().ok(),
+            },
+        )
+    }
+
+    /// Open a database without importing the CSV files of the current user.
+    #[cfg(test)]
+    pub fn open_without_csv_files(database_path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::open_with_csv_files(
+            database_path,
+            LegacyCsvFiles {
+                history: None,
+                favorites: None,
+            },
+        )
+    }
+
+    fn open_with_csv_files(
+        database_path: &Path,
+        legacy_csv_files: LegacyCsvFiles,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut connection = Connection::open(database_path)?;
+
+        // The GUI and the CLI may access the database at the same time
+        connection.busy_timeout(std::time::Duration::from_secs(5))?;
+
+        // Take the write lock first, so that two processes can't apply the
+        // same migration
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: i64 = transaction.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if (version as usize) < MIGRATIONS.len() {
+            for migration in &MIGRATIONS[version as usize..] {
+                migration(&transaction, &legacy_csv_files)?;
+            }
+            transaction.pragma_update(None, "user_version", MIGRATIONS.len() as i64)?;
+        }
+        transaction.commit()?;
+
+        Ok(HistoryDatabase { connection })
+    }
+
+    pub fn add(
+        &mut self,
+        list: SongList,
+        record: &SongHistoryRecord,
+        details: &RecognitionDetails,
+    ) -> Result<(), Box<dyn Error>> {
+        self.add_all(list, [(record, details)])
+    }
+
+    /// Add several entries at once, in a single transaction.
+    pub fn add_all<'a>(
+        &mut self,
+        list: SongList,
+        entries: impl IntoIterator<Item = (&'a SongHistoryRecord, &'a RecognitionDetails)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let transaction = self.connection.transaction()?;
+        for (record, details) in entries {
+            insert_row(&transaction, list, record, details)?;
+
+            // Not part of "insert_row", which is also used by the first migration
+            if let Some(ref capture_path) = details.capture_path {
+                transaction.execute(
+                    "UPDATE songs SET capture_path = ?1 WHERE id = last_insert_rowid()",
+                    params![capture_path],
+                )?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Whether the list already contains a recognition of the same track
+    /// (by track key, or by name when there is none) at the same time.
+    pub fn contains_entry(
+        &self,
+        list: SongList,
+        record: &SongHistoryRecord,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(recognized_at) = parse_recognition_date(&record.recognition_date) else {
+            return Ok(false);
+        };
+
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT 1 FROM songs WHERE list = ?1 AND recognized_at = ?2
+                    AND (track_key = ?3 OR (track_key IS NULL AND song_name = ?4))",
+                params![
+                    list.as_str(),
+                    to_timestamp(recognized_at),
+                    non_empty(&record.track_key),
+                    record.song_name,
+                ],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    /// The archived audio a history entry was recognized from, if any.
+    pub fn capture_path(
+        &self,
+        record: &SongHistoryRecord,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(recognized_at) = parse_recognition_date(&record.recognition_date) else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT capture_path FROM songs WHERE list = 'history' AND recognized_at = ?1
+                    AND (track_key = ?2 OR (track_key IS NULL AND song_name = ?3))
+                    AND capture_path IS NOT NULL",
+                params![
+                    to_timestamp(recognized_at),
+                    non_empty(&record.track_key),
+                    record.song_name,
+                ],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// The name of the song recognized from an archived capture, if any.
+    pub fn song_for_capture(&self, capture_path: &str) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT song_name FROM songs WHERE list = 'history' AND capture_path = ?1",
+                params![capture_path],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Whether the song of a recognition is among the favorites.
+    pub fn is_favorite(&self, record: &SongHistoryRecord) -> Result<bool, Box<dyn Error>> {
+        Ok(self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM songs WHERE list = 'favorites'
+                AND song_name = ?1 AND album IS ?2 AND track_key IS ?3
+                AND release_year IS ?4 AND genre IS ?5)",
+            params![
+                record.song_name,
+                non_empty(&record.album),
+                non_empty(&record.track_key),
+                non_empty(&record.release_year),
+                non_empty(&record.genre),
+            ],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Remove all the entries of the list matching the song, whatever their
+    /// recognition date.
+    pub fn remove_song(&mut self, list: SongList, song: &Song) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "DELETE FROM songs WHERE list = ?1 AND song_name = ?2 AND album IS ?3
+                AND track_key IS ?4 AND release_year IS ?5 AND genre IS ?6",
+            params![
+                list.as_str(),
+                song.song_name,
+                song.album,
+                song.track_key,
+                song.release_year,
+                song.genre,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn wipe(&mut self, list: SongList) -> Result<(), Box<dyn Error>> {
+        self.connection
+            .execute("DELETE FROM songs WHERE list = ?1", params![list.as_str()])?;
+        Ok(())
+    }
+
+    /// The entries of the list, in chronological order.
+    pub fn load(&self, list: SongList) -> Result<Vec<SongHistoryRecord>, Box<dyn Error>> {
+        let mut statement = self.connection.prepare(
+            "SELECT song_name, album, track_key, release_year, genre, recognized_at, isrc, label
+            FROM songs WHERE list = ?1 ORDER BY recognized_at, id",
+        )?;
+        let rows = statement.query_map(params![list.as_str()], |row| {
+            Ok(SongHistoryRecord {
+                song_name: row.get(0)?,
+                album: row.get(1)?,
+                track_key: row.get(2)?,
+                release_year: row.get(3)?,
+                genre: row.get(4)?,
+                recognition_date: format_recognition_date(&row.get::<_, String>(5)?),
+                isrc: row.get(6)?,
+                label: row.get(7)?,
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    pub fn query(&self, query: &HistoryQuery) -> Result<Vec<HistoryRow>, Box<dyn Error>> {
+        let mut conditions = vec!["s.list = ?".to_string()];
+        let mut values: Vec<Box<dyn ToSql>> = vec![Box::new(query.list.as_str())];
+
+        if let Some(ref since) = query.since {
+            conditions.push("s.recognized_at >= ?".to_string());
+            values.push(Box::new(since.clone()));
+        }
+        if let Some(ref until) = query.until {
+            conditions.push("s.recognized_at < ?".to_string());
+            values.push(Box::new(until.clone()));
+        }
+        if let Some(ref artist) = query.artist {
+            conditions.push("s.artist_name LIKE '%' || ? || '%' ESCAPE '\\'".to_string());
+            values.push(Box::new(escape_like_pattern(artist)));
+        }
+        if let Some(ref genre) = query.genre {
+            conditions.push("s.genre = ? COLLATE NOCASE".to_string());
+            values.push(Box::new(genre.clone()));
+        }
+        match query.is_favorite {
+            Some(true) => conditions.push(IS_FAVORITE_EXPRESSION.to_string()),
+            Some(false) => conditions.push(format!("NOT {}", IS_FAVORITE_EXPRESSION)),
+            None => {}
+        }
+
+        // With a "GROUP BY", SQLite takes the other columns from the row
+        // holding the "MAX()" value
+        let grouping = match query.deduplicate {
+            true => "GROUP BY COALESCE(s.track_key, s.song_name)",
+            false => "GROUP BY s.id",
+        };
+        let sort_column = match query.sort_key {
+            HistorySortKey::Date => "s.recognized_at",
+            HistorySortKey::Artist => "s.artist_name COLLATE NOCASE",
+            HistorySortKey::Song => "s.song_name COLLATE NOCASE",
+            HistorySortKey::Genre => "s.genre COLLATE NOCASE",
+        };
+        let direction = match query.descending {
+            true => "DESC",
+            false => "ASC",
+        };
+
+        let sql = format!(
+            "SELECT s.song_name, s.artist_name, s.album, s.track_key, s.release_year, s.genre,
+                s.isrc, s.label, MAX(s.recognized_at), s.device_name, s.cover_path,
+                {}, COUNT(*), s.shazam_json, s.capture_path
+            FROM songs s WHERE {} {}
+            ORDER BY {} {}, s.id {} LIMIT ? OFFSET ?",
+            IS_FAVORITE_EXPRESSION,
+            conditions.join(" AND "),
+            grouping,
+            sort_column,
+            direction,
+            direction
+        );
+        // A negative limit means no limit in SQLite
+        values.push(Box::new(query.limit.map_or(-1, |limit| limit as i64)));
+        values.push(Box::new(query.offset as i64));
+
+        let mut statement = self.connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values.iter()), |row| {
+            Ok(HistoryRow {
+                song_name: row.get(0)?,
+                artist_name: row.get(1)?,
+                album: row.get(2)?,
+                track_key: row.get(3)?,
+                release_year: row.get(4)?,
+                genre: row.get(5)?,
+                isrc: row.get(6)?,
+                label: row.get(7)?,
+                recognized_at: row.get(8)?,
+                device_name: row.get(9)?,
+                cover_path: row.get(10)?,
+                is_favorite: row.get(11)?,
+                times_recognized: row.get(12)?,
+                shazam_json: row
+                    .get::<_, Option<String>>(13)?
+                    .and_then(|json| serde_json::from_str(&json).ok()),
+                capture_path: row.get(14)?,
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Copy the most recent history entry matching a track key or a song
+    /// name ("Artist - Title") to the favorites. Returns the song name, or
+    /// `None` if there is no such entry.
+    pub fn add_favorite(&mut self, song: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let transaction = self.connection.transaction()?;
+
+        let id: Option<(i64, String, bool)> = transaction
+            .query_row(
+                &format!(
+                    "SELECT s.id, s.song_name, {} FROM songs s
+                    WHERE s.list = 'history' AND (s.track_key = ?1 OR s.song_name = ?1)
+                    ORDER BY s.recognized_at DESC LIMIT 1",
+                    IS_FAVORITE_EXPRESSION
+                ),
+                params![song],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+
+        let Some((id, song_name, is_favorite)) = id else {
+            return Ok(None);
+        };
+
+        if !is_favorite {
+            transaction.execute(
+                "INSERT INTO songs (list, song_name, artist_name, album, track_key, release_year,
+                    genre, isrc, label, recognized_at, device_name, shazam_json, cover_path,
+                    capture_path)
+                SELECT 'favorites', song_name, artist_name, album, track_key, release_year,
+                    genre, isrc, label, recognized_at, device_name, shazam_json, cover_path,
+                    capture_path
+                FROM songs WHERE id = ?1",
+                params![id],
+            )?;
+        }
+        transaction.commit()?;
+
+        Ok(Some(song_name))
+    }
+
+    /// Remove the favorites matching a track key or a song name, returning
+    /// how many were removed.
+    pub fn remove_favorite(&mut self, song: &str) -> Result<usize, Box<dyn Error>> {
+        Ok(self.connection.execute(
+            "DELETE FROM songs WHERE list = 'favorites' AND (track_key = ?1 OR song_name = ?1)",
+            params![song],
+        )?)
+    }
+
+    /// Write the list in the CSV format of the previous versions, e.g. for
+    /// opening it in a spreadsheet program.
+    pub fn export_csv(&self, list: SongList, csv_path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut writer = csv::Writer::from_path(csv_path)?;
+        for record in self.load(list)? {
+            writer.serialize(record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_history_database_migration() {
+    use crate::utils::csv_song_history::HasSong;
+
+    let directory = tempfile::tempdir().unwrap();
+    let history_csv_path = directory.path().join("song_history.csv");
+    std::fs::write(
+        &history_csv_path,
+        "song_name,album,recognition_date\n\
+        Artist - Old Song,,Sat Aug 17 22:44:43 2024\n",
+    )
+    .unwrap();
+
+    let mut database = HistoryDatabase::open_with_csv_files(
+        &directory.path().join("history.sqlite"),
+        LegacyCsvFiles {
+            history: Some(history_csv_path),
+            favorites: None,
+        },
+    )
+    .unwrap();
+
+    let record = SongHistoryRecord {
+        song_name: "Artist - New Song".to_string(),
+        album: Some("".to_string()),
+        track_key: Some("12345".to_string()),
+        release_year: None,
+        genre: None,
+        recognition_date: "Sun Aug 18 09:00:00 2024".to_string(),
+        isrc: None,
+        label: None,
+    };
+    database
+        .add(SongList::History, &record, &RecognitionDetails::default())
+        .unwrap();
+
+    let records = database.load(SongList::History).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].song_name, "Artist - Old Song");
+    assert_eq!(records[0].recognition_date, "Sat Aug 17 22:44:43 2024");
+    assert_eq!(records[1].clone().get_song(), record.clone().get_song());
+
+    let captured_record = SongHistoryRecord {
+        recognition_date: "Sun Aug 18 09:05:00 2024".to_string(),
+        ..record.clone()
+    };
+    database
+        .add(
+            SongList::History,
+            &captured_record,
+            &RecognitionDetails {
+                capture_path: Some("capture.wav".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(database.capture_path(&record).unwrap(), None);
+    assert_eq!(
+        database.capture_path(&captured_record).unwrap().as_deref(),
+        Some("capture.wav")
+    );
+    assert_eq!(
+        database.song_for_capture("capture.wav").unwrap().as_deref(),
+        Some("Artist - New Song")
+    );
+
+    database
+        .remove_song(SongList::History, &record.get_song())
+        .unwrap();
+    assert_eq!(database.load(SongList::History).unwrap().len(), 1);
+    assert!(database.load(SongList::Favorites).unwrap().is_empty());
+}
+
+#[test]
+fn test_history_database_migration_skips_corrupt_rows() {
+    let directory = tempfile::tempdir().unwrap();
+    let history_csv_path = directory.path().join("song_history.csv");
+    std::fs::write(
+        &history_csv_path,
+        "song_name,album,recognition_date\n\
+        Artist - First Song,,Sat Aug 17 22:44:43 2024\n\
+        corrupt row\n\
+        Artist - Undated Song,,yesterday\n\
+        Artist - Second Song,,Sat Aug 17 22:50:00 2024\n",
+    )
+    .unwrap();
+    let favorites_csv_path = directory.path().join("favorites.csv");
+    std::fs::write(
+        &favorites_csv_path,
+        b"song_name,album,recognition_date\n\xff\xfe,\n",
+    )
+    .unwrap();
+
+    let database = HistoryDatabase::open_with_csv_files(
+        &directory.path().join("history.sqlite"),
+        LegacyCsvFiles {
+            history: Some(history_csv_path),
+            favorites: Some(favorites_csv_path),
+        },
+    )
+    .unwrap();
+
+    let records = database.load(SongList::History).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].song_name, "Artist - First Song");
+    assert_eq!(records[1].song_name, "Artist - Second Song");
+    assert!(database.load(SongList::Favorites).unwrap().is_empty());
+}
+
+#[test]
+fn test_history_database_query() {
+    let directory = tempfile::tempdir().unwrap();
+    let mut database = HistoryDatabase::open_with_csv_files(
+        &directory.path().join("history.sqlite"),
+        LegacyCsvFiles {
+            history: None,
+            favorites: None,
+        },
+    )
+    .unwrap();
+
+    for (song_name, track_key, genre, recognition_date) in [
+        (
+            "Daft Punk - One More Time",
+            "1",
+            "House",
+            "Mon Sep  2 10:00:00 2024",
+        ),
+        (
+            "Air - La femme d'argent",
+            "2",
+            "Electronic",
+            "Tue Sep  3 10:00:00 2024",
+        ),
+        (
+            "Daft Punk - One More Time",
+            "1",
+            "House",
+            "Wed Sep  4 10:00:00 2024",
+        ),
+    ] {
+        let record = SongHistoryRecord {
+            song_name: song_name.to_string(),
+            album: None,
+            track_key: Some(track_key.to_string()),
+            release_year: None,
+            genre: Some(genre.to_string()),
+            recognition_date: recognition_date.to_string(),
+            isrc: None,
+            label: None,
+        };
+        database
+            .add(SongList::History, &record, &RecognitionDetails::default())
+            .unwrap();
+    }
+
+    let mut query = HistoryQuery {
+        list: SongList::History,
+        since: Some(parse_date_bound("2024-09-03", false).unwrap()),
+        until: None,
+        artist: Some("daft".to_string()),
+        genre: None,
+        is_favorite: None,
+        sort_key: HistorySortKey::Date,
+        descending: true,
+        limit: None,
+        offset: 0,
+        deduplicate: false,
+    };
+    let rows = database.query(&query).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        rows[0].to_song_history_record().recognition_date,
+        "Wed Sep  4 10:00:00 2024"
+    );
+
+    for wildcard in ["%", "_"] {
+        query.artist = Some(wildcard.to_string());
+        assert!(database.query(&query).unwrap().is_empty());
+    }
+    query.artist = Some("daft".to_string());
+
+    query.since = None;
+    query.artist = None;
+    query.deduplicate = true;
+    let rows = database.query(&query).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].times_recognized, 2);
+
+    assert_eq!(
+        database.add_favorite("2").unwrap().as_deref(),
+        Some("Air - La femme d'argent")
+    );
+    assert!(database.add_favorite("unknown").unwrap().is_none());
+    query.is_favorite = Some(true);
+    assert_eq!(
+        database.query(&query).unwrap()[0].track_key.as_deref(),
+        Some("2")
+    );
+    let favorite = database.query(&query).unwrap()[0].to_song_history_record();
+    assert!(database.is_favorite(&favorite).unwrap());
+    assert_eq!(
+        database.remove_favorite("Air - La femme d'argent").unwrap(),
+        1
+    );
+    assert!(database.query(&query).unwrap().is_empty());
+    assert!(!database.is_favorite(&favorite).unwrap());
+}
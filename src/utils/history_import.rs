@@ -0,0 +1,246 @@
+//! Importing recognition histories from other sources into the history
+//! database: the CSV export of the official Shazam app ("Shazam Library",
+//! with the TagTime, Title, Artist, URL and TrackKey columns) and the CSV
+//! files of older SongRec installs (see "src/utils/csv_song_history.rs").
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use gettextrs::gettext;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+
+use crate::utils::csv_song_history::SongHistoryRecord;
+use crate::utils::history_database::{
+    HistoryDatabase, RecognitionDetails, SongList, parse_recognition_date,
+};
+
+pub struct ImportedEntry {
+    pub record: SongHistoryRecord,
+    pub details: RecognitionDetails,
+}
+
+#[derive(Default)]
+pub struct ImportReport {
+    /// The song names and recognition dates of the entries
+    pub added: Vec<String>,
+    pub skipped_duplicates: Vec<String>,
+    /// The entries whose recognition date could not be parsed, which could
+    /// neither be deduplicated nor dated
+    pub skipped_invalid_dates: Vec<String>,
+    /// The lines which could not be read
+    pub errors: Vec<String>,
+}
+
+/// Parse the dates found in Shazam exports, which are either full ISO-8601
+/// dates or local dates and times.
+fn parse_tag_time(tag_time: &str) -> Option<DateTime<Local>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(tag_time) {
+        return Some(date.with_timezone(&Local));
+    }
+    let date = NaiveDateTime::parse_from_str(tag_time, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(tag_time, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(tag_time, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .ok()?;
+    Local.from_local_datetime(&date).earliest()
+}
+
+/// Read a CSV file in either of the supported formats, returning the entries
+/// and the errors met on the way.
+pub fn read_history_file(path: &Path) -> Result<(Vec<ImportedEntry>, Vec<String>), Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+    let mut records = reader.records();
+
+    // The Shazam export may start with a title line ("Shazam Library")
+    // before the actual header
+    let mut header: Option<csv::StringRecord> = None;
+    for record in records.by_ref().take(2) {
+        let record = record?;
+        if record
+            .iter()
+            .any(|field| field == "TagTime" || field == "song_name")
+        {
+            header = Some(record);
+            break;
+        }
+    }
+    let header = header.ok_or_else(|| gettext("Unrecognized history file format"))?;
+    let column = |name: &str| header.iter().position(|field| field == name);
+
+    let mut entries = vec![];
+    let mut errors = vec![];
+
+    if let Some(tag_time_column) = column("TagTime") {
+        let (title_column, artist_column, track_key_column) =
+            (column("Title"), column("Artist"), column("TrackKey"));
+
+        for (index, record) in records.enumerate() {
+            let record = match record {
+                Ok(record) => record,
+                Err(error) => {
+                    errors.push(error.to_string());
+                    continue;
+                }
+            };
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|column| record.get(column))
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+            };
+
+            let (Some(title), Some(date)) = (
+                field(title_column),
+                field(Some(tag_time_column)).and_then(|tag_time| parse_tag_time(&tag_time)),
+            ) else {
+                errors.push(format!("{} {}", gettext("Invalid line:"), index + 1));
+                continue;
+            };
+            let artist_name = field(artist_column);
+
+            entries.push(ImportedEntry {
+                record: SongHistoryRecord {
+                    song_name: match artist_name {
+                        Some(ref artist_name) => format!("{} - {}", artist_name, title),
+                        None => title,
+                    },
+                    album: None,
+                    track_key: field(track_key_column),
+                    release_year: None,
+                    genre: None,
+                    recognition_date: date.format("%c").to_string(),
+                    isrc: None,
+                    label: None,
+                },
+                details: RecognitionDetails {
+                    artist_name,
+                    ..Default::default()
+                },
+            });
+        }
+    } else {
+        for record in records {
+            match record.and_then(|record| record.deserialize::<SongHistoryRecord>(Some(&header))) {
+                Ok(record) => entries.push(ImportedEntry {
+                    record,
+                    details: RecognitionDetails::default(),
+                }),
+                Err(error) => errors.push(error.to_string()),
+            }
+        }
+    }
+
+    Ok((entries, errors))
+}
+
+/// Add the entries of a history file which are not already in the list, in
+/// a single transaction.
+pub fn import_history_file(
+    database: &mut HistoryDatabase,
+    list: SongList,
+    path: &Path,
+    dry_run: bool,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let (entries, errors) = read_history_file(path)?;
+    let mut report = ImportReport {
+        errors,
+        ..Default::default()
+    };
+
+    // Duplicates within the file itself
+    let mut seen: HashSet<(Option<String>, String, String)> = HashSet::new();
+    let mut new_entries = vec![];
+
+    for entry in entries {
+        let description = format!(
+            "{} ({})",
+            entry.record.song_name, entry.record.recognition_date
+        );
+
+        if parse_recognition_date(&entry.record.recognition_date).is_none() {
+            report.skipped_invalid_dates.push(description);
+            continue;
+        }
+
+        let key = (
+            entry.record.track_key.clone(),
+            entry.record.song_name.clone(),
+            entry.record.recognition_date.clone(),
+        );
+        if database.contains_entry(list, &entry.record)? || !seen.insert(key) {
+            report.skipped_duplicates.push(description);
+            continue;
+        }
+        report.added.push(description);
+        new_entries.push(entry);
+    }
+
+    if !dry_run {
+        database.add_all(
+            list,
+            new_entries
+                .iter()
+                .map(|entry| (&entry.record, &entry.details)),
+        )?;
+    }
+
+    Ok(report)
+}
+
+#[test]
+fn test_read_shazam_export() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("shazamlibrary.csv");
+    std::fs::write(
+        &path,
+        "Shazam Library\n\
+        Index,TagTime,Title,Artist,URL,TrackKey\n\
+        1,2023-05-12,Windowlicker,Aphex Twin,https://www.shazam.com/track/123,123\n\
+        2,,Missing date,Nobody,,456\n",
+    )
+    .unwrap();
+
+    let (entries, errors) = read_history_file(&path).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(entries[0].record.song_name, "Aphex Twin - Windowlicker");
+    assert_eq!(entries[0].record.track_key.as_deref(), Some("123"));
+    assert_eq!(
+        entries[0].record.recognition_date,
+        "Fri May 12 00:00:00 2023"
+    );
+}
+
+#[test]
+fn test_import_is_idempotent() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("song_history.csv");
+    std::fs::write(
+        &path,
+        "song_name,album,recognition_date\n\
+        Artist - Song,,Sat Aug 17 22:44:43 2024\n\
+        Artist - Song,,Sat Aug 17 22:44:43 2024\n\
+        Artist - Undated Song,,yesterday\n",
+    )
+    .unwrap();
+
+    let mut database =
+        HistoryDatabase::open_without_csv_files(&directory.path().join("history.sqlite")).unwrap();
+
+    let report = import_history_file(&mut database, SongList::History, &path, false).unwrap();
+    assert_eq!(report.added.len(), 1);
+    assert_eq!(report.skipped_duplicates.len(), 1);
+    assert_eq!(report.skipped_invalid_dates.len(), 1);
+
+    let report = import_history_file(&mut database, SongList::History, &path, false).unwrap();
+    assert!(report.added.is_empty());
+    assert_eq!(report.skipped_duplicates.len(), 2);
+    assert_eq!(report.skipped_invalid_dates.len(), 1);
+
+    assert_eq!(database.load(SongList::History).unwrap().len(), 1);
+}
@@ -0,0 +1,335 @@
+//! Summaries of the recognition history: the most recognized artists, genres
+//! and tracks, when the recognitions happened, and which songs were heard
+//! for the first time recently. Used by the "statistics" command and by the
+//! Statistics page of the GUI.
+
+use chrono::{DateTime, Datelike, Duration, Local, Timelike};
+use gettextrs::gettext;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::utils::history_database::HistoryRow;
+
+/// The number of weeks displayed by the text rendering of the per-day
+/// activity.
+const HEATMAP_WEEKS: i64 = 26;
+
+#[derive(Serialize)]
+pub struct RankedEntry {
+    pub name: String,
+    pub count: u32,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TrackStatistics {
+    pub song_name: String,
+    pub track_key: Option<String>,
+    pub count: u32,
+    /// ISO-8601 timestamps
+    pub first_heard: String,
+    pub last_heard: String,
+}
+
+#[derive(Serialize)]
+pub struct ListeningStatistics {
+    pub number_recognitions: u32,
+    pub number_songs: u32,
+    pub top_artists: Vec<RankedEntry>,
+    pub top_genres: Vec<RankedEntry>,
+    pub top_tracks: Vec<TrackStatistics>,
+    /// The number of recognitions per day ("YYYY-MM-DD", local time)
+    pub activity_per_day: BTreeMap<String, u32>,
+    /// The number of recognitions per day of the week (from Monday) and
+    /// per hour (local time)
+    pub activity_per_hour: [[u32; 24]; 7],
+    /// The songs heard for the first time during the current month, most
+    /// recent first
+    pub new_this_month: Vec<TrackStatistics>,
+}
+
+fn rank(counts: HashMap<String, u32>, top_count: usize) -> Vec<RankedEntry> {
+    let mut entries: Vec<RankedEntry> = counts
+        .into_iter()
+        .map(|(name, count)| RankedEntry { name, count })
+        .collect();
+    entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    entries.truncate(top_count);
+    entries
+}
+
+/// Compute the statistics of the given history entries (which don't need to
+/// be sorted), keeping `top_count` entries in the rankings.
+pub fn compute_statistics(
+    rows: &[HistoryRow],
+    now: DateTime<Local>,
+    top_count: usize,
+) -> ListeningStatistics {
+    let mut artist_counts: HashMap<String, u32> = HashMap::new();
+    let mut genre_counts: HashMap<String, u32> = HashMap::new();
+    let mut tracks: HashMap<String, TrackStatistics> = HashMap::new();
+    let mut activity_per_day: BTreeMap<String, u32> = BTreeMap::new();
+    let mut activity_per_hour = [[0; 24]; 7];
+
+    for row in rows {
+        if let Some(ref artist_name) = row.artist_name {
+            *artist_counts.entry(artist_name.clone()).or_default() += 1;
+        }
+        if let Some(ref genre) = row.genre {
+            *genre_counts.entry(genre.clone()).or_default() += 1;
+        }
+
+        let track = tracks
+            .entry(row.track_key.clone().unwrap_or(row.song_name.clone()))
+            .or_insert_with(|| TrackStatistics {
+                song_name: row.song_name.clone(),
+                track_key: row.track_key.clone(),
+                count: 0,
+                first_heard: row.recognized_at.clone(),
+                last_heard: row.recognized_at.clone(),
+            });
+        track.count += 1;
+        // UTC ISO-8601 timestamps compare chronologically as strings
+        if row.recognized_at < track.first_heard {
+            track.first_heard = row.recognized_at.clone();
+        }
+        if row.recognized_at > track.last_heard {
+            track.last_heard = row.recognized_at.clone();
+        }
+
+        if let Ok(date) = DateTime::parse_from_rfc3339(&row.recognized_at) {
+            let date = date.with_timezone(&Local);
+            *activity_per_day
+                .entry(date.format("%Y-%m-%d").to_string())
+                .or_default() += 1;
+            activity_per_hour[date.weekday().num_days_from_monday() as usize]
+                [date.hour() as usize] += 1;
+        }
+    }
+
+    let is_this_month = |timestamp: &str| {
+        DateTime::parse_from_rfc3339(timestamp).is_ok_and(|date| {
+            let date = date.with_timezone(&Local);
+            date.year() == now.year() && date.month() == now.month()
+        })
+    };
+    let mut new_this_month: Vec<TrackStatistics> = tracks
+        .values()
+        .filter(|track| is_this_month(&track.first_heard))
+        .cloned()
+        .collect();
+    new_this_month.sort_by(|a, b| b.first_heard.cmp(&a.first_heard));
+
+    let number_songs = tracks.len() as u32;
+    let mut top_tracks: Vec<TrackStatistics> = tracks.into_values().collect();
+    top_tracks.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.first_heard.cmp(&b.first_heard))
+    });
+    top_tracks.truncate(top_count);
+
+    ListeningStatistics {
+        number_recognitions: rows.len() as u32,
+        number_songs,
+        top_artists: rank(artist_counts, top_count),
+        top_genres: rank(genre_counts, top_count),
+        top_tracks,
+        activity_per_day,
+        activity_per_hour,
+        new_this_month,
+    }
+}
+
+/// Format an ISO-8601 timestamp as a local date.
+pub fn format_day(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(date) => date.with_timezone(&Local).format("%Y-%m-%d").to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+fn shade(value: u32, maximum: u32) -> char {
+    const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+    match value {
+        0 => SHADES[0],
+        _ => SHADES[1 + ((value * 4 - 1) / maximum.max(1)).min(3) as usize],
+    }
+}
+
+fn weekday_names() -> [String; 7] {
+    [
+        gettext("Mon"),
+        gettext("Tue"),
+        gettext("Wed"),
+        gettext("Thu"),
+        gettext("Fri"),
+        gettext("Sat"),
+        gettext("Sun"),
+    ]
+}
+
+/// A text heatmap of the recognitions per day of the week and per hour.
+pub fn render_hour_heatmap(statistics: &ListeningStatistics) -> String {
+    let maximum = statistics
+        .activity_per_hour
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0);
+
+    let mut output = format!("{:5}0     6     12    18\n", "");
+    for (weekday_name, hours) in weekday_names().iter().zip(statistics.activity_per_hour) {
+        output += &format!("{:5}", weekday_name);
+        output.extend(hours.iter().map(|count| shade(*count, maximum)));
+        output += "\n";
+    }
+    output
+}
+
+/// A text heatmap of the recognitions per day over the last weeks, with one
+/// column per week.
+pub fn render_day_heatmap(statistics: &ListeningStatistics, now: DateTime<Local>) -> String {
+    let today = now.date_naive();
+    let first_day = today
+        - Duration::days(today.weekday().num_days_from_monday() as i64)
+        - Duration::weeks(HEATMAP_WEEKS - 1);
+
+    let count_of = |week: i64, weekday: i64| {
+        let day = first_day + Duration::days(week * 7 + weekday);
+        match day > today {
+            true => None,
+            false => Some(
+                statistics
+                    .activity_per_day
+                    .get(&day.format("%Y-%m-%d").to_string())
+                    .copied()
+                    .unwrap_or(0),
+            ),
+        }
+    };
+
+    let maximum = (0..HEATMAP_WEEKS)
+        .flat_map(|week| (0..7).filter_map(move |weekday| count_of(week, weekday)))
+        .max()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for (weekday, weekday_name) in weekday_names().iter().enumerate() {
+        output += &format!("{:5}", weekday_name);
+        for week in 0..HEATMAP_WEEKS {
+            output.push(match count_of(week, weekday as i64) {
+                Some(count) => shade(count, maximum),
+                None => ' ',
+            });
+        }
+        output += "\n";
+    }
+    output
+}
+
+/// The human-readable report printed by the "statistics" command.
+pub fn format_report(statistics: &ListeningStatistics, now: DateTime<Local>) -> String {
+    let mut output = format!(
+        "{} {}\n{} {}\n",
+        gettext("Recognitions:"),
+        statistics.number_recognitions,
+        gettext("Distinct songs:"),
+        statistics.number_songs
+    );
+
+    for (title, entries) in [
+        (gettext("Top artists"), &statistics.top_artists),
+        (gettext("Top genres"), &statistics.top_genres),
+    ] {
+        output += &format!("\n{}\n", title);
+        for (index, entry) in entries.iter().enumerate() {
+            output += &format!("{:>4}. {} ({})\n", index + 1, entry.name, entry.count);
+        }
+    }
+
+    output += &format!("\n{}\n", gettext("Top tracks"));
+    for (index, track) in statistics.top_tracks.iter().enumerate() {
+        output += &format!(
+            "{:>4}. {} ({}, {} {})\n",
+            index + 1,
+            track.song_name,
+            track.count,
+            gettext("first heard"),
+            format_day(&track.first_heard)
+        );
+    }
+
+    output += &format!("\n{}\n", gettext("New this month"));
+    for track in &statistics.new_this_month {
+        output += &format!(
+            "      {} ({})\n",
+            track.song_name,
+            format_day(&track.first_heard)
+        );
+    }
+
+    output += &format!(
+        "\n{}\n{}",
+        gettext("Activity per hour"),
+        render_hour_heatmap(statistics)
+    );
+    output += &format!(
+        "\n{}\n{}",
+        gettext("Activity per day"),
+        render_day_heatmap(statistics, now)
+    );
+
+    output
+}
+
+#[test]
+fn test_compute_statistics() {
+    use chrono::TimeZone;
+
+    let row = |song_name: &str, track_key: &str, recognized_at: DateTime<Local>| HistoryRow {
+        song_name: song_name.to_string(),
+        artist_name: song_name
+            .split_once(" - ")
+            .map(|(artist, _)| artist.to_string()),
+        album: None,
+        track_key: Some(track_key.to_string()),
+        release_year: None,
+        genre: Some("Electronic".to_string()),
+        isrc: None,
+        label: None,
+        recognized_at: recognized_at.with_timezone(&chrono::Utc).to_rfc3339(),
+        device_name: None,
+        cover_path: None,
+        capture_path: None,
+        is_favorite: false,
+        times_recognized: 1,
+        shazam_json: None,
+    };
+    let now = Local.with_ymd_and_hms(2024, 9, 18, 12, 0, 0).unwrap();
+
+    let rows = [
+        row("Air - Playground Love", "1", now - Duration::days(60)),
+        row("Air - Playground Love", "1", now - Duration::days(1)),
+        row("Moby - Porcelain", "2", now - Duration::days(2)),
+    ];
+    let statistics = compute_statistics(&rows, now, 10);
+
+    assert_eq!(statistics.number_recognitions, 3);
+    assert_eq!(statistics.number_songs, 2);
+    assert_eq!(statistics.top_artists[0].name, "Air");
+    assert_eq!(statistics.top_artists[0].count, 2);
+    assert_eq!(statistics.top_genres[0].count, 3);
+    assert_eq!(statistics.top_tracks[0].first_heard, rows[0].recognized_at);
+    assert_eq!(statistics.new_this_month.len(), 1);
+    assert_eq!(statistics.new_this_month[0].song_name, "Moby - Porcelain");
+
+    // Monday 16 to Tuesday 17 September, 12:00
+    assert_eq!(statistics.activity_per_hour[0][12], 1);
+    assert_eq!(statistics.activity_per_hour[1][12], 1);
+    let day_heatmap = render_day_heatmap(&statistics, now);
+    let lines: Vec<&str> = day_heatmap.lines().collect();
+    assert!(lines[0].ends_with('█'));
+    // The coming Sunday is left blank
+    assert!(lines[6].ends_with(' '));
+}
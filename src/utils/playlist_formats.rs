@@ -0,0 +1,293 @@
+//! Writers exporting the song history or the favorites as playlists, which
+//! can be opened in audio players or imported into other services.
+
+use chrono::{DateTime, Local};
+use serde_json::json;
+use std::error::Error;
+use std::path::Path;
+
+use crate::utils::history_database::HistoryRow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    CSV,
+    M3U8,
+    XSPF,
+    JSPF,
+    /// The JSON format of the ListenBrainz "submit-listens" API, also
+    /// accepted by its import tools
+    ListenBrainz,
+}
+
+impl PlaylistFormat {
+    /// Guess the format from the extension of the file to write.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+            "csv" => Some(PlaylistFormat::CSV),
+            "m3u8" | "m3u" => Some(PlaylistFormat::M3U8),
+            "xspf" => Some(PlaylistFormat::XSPF),
+            "jspf" => Some(PlaylistFormat::JSPF),
+            "json" => Some(PlaylistFormat::ListenBrainz),
+            _ => None,
+        }
+    }
+}
+
+/// Split the "Artist - Title" song name of an entry.
+fn artist_and_title(row: &HistoryRow) -> (Option<&str>, &str) {
+    match row.artist_name.as_deref() {
+        Some(artist_name) => (
+            Some(artist_name),
+            row.song_name
+                .strip_prefix(artist_name)
+                .and_then(|title| title.strip_prefix(" - "))
+                .unwrap_or(&row.song_name),
+        ),
+        None => (None, &row.song_name),
+    }
+}
+
+fn file_uri(path: &str) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri += &format!("%{:02X}", byte),
+        }
+    }
+    uri
+}
+
+/// Where the track can be found: the file matched by the local backend, or
+/// its Shazam page.
+fn track_location(row: &HistoryRow) -> Option<String> {
+    if let Some(path) = row
+        .track_key
+        .as_deref()
+        .and_then(|track_key| track_key.strip_prefix("local:"))
+    {
+        return Some(file_uri(path));
+    }
+    row.shazam_json
+        .as_ref()
+        .and_then(|shazam_json| shazam_json["track"]["url"].as_str())
+        .map(|url| url.to_string())
+        .or_else(|| {
+            row.track_key
+                .as_ref()
+                .map(|track_key| format!("https://www.shazam.com/track/{}", track_key))
+        })
+}
+
+fn listened_at(row: &HistoryRow) -> Option<i64> {
+    DateTime::parse_from_rfc3339(&row.recognized_at)
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+fn xml_escape(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_csv(rows: &[HistoryRow]) -> Result<String, Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        writer.serialize(row.to_song_history_record())?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// An extended M3U playlist. Entries without a known location are left out,
+/// as M3U has no way to represent them.
+fn write_m3u8(rows: &[HistoryRow], title: &str) -> String {
+    let mut output = format!("#EXTM3U\n#PLAYLIST:{}\n", title);
+
+    for row in rows {
+        let Some(location) = track_location(row) else {
+            continue;
+        };
+        output += &format!(
+            "#EXTINF:-1,{}\n{}\n",
+            row.song_name.replace('\n', " "),
+            location
+        );
+    }
+
+    output
+}
+
+fn write_xspf(rows: &[HistoryRow], title: &str) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output += "<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n";
+    output += &format!("  <title>{}</title>\n", xml_escape(title));
+    output += "  <creator>SongRec</creator>\n";
+    output += &format!("  <date>{}</date>\n", Local::now().to_rfc3339());
+    output += "  <trackList>\n";
+
+    for row in rows {
+        let (artist_name, track_title) = artist_and_title(row);
+
+        output += "    <track>\n";
+        if let Some(location) = track_location(row) {
+            output += &format!("      <location>{}</location>\n", xml_escape(&location));
+        }
+        output += &format!("      <title>{}</title>\n", xml_escape(track_title));
+        if let Some(artist_name) = artist_name {
+            output += &format!("      <creator>{}</creator>\n", xml_escape(artist_name));
+        }
+        if let Some(ref album) = row.album {
+            output += &format!("      <album>{}</album>\n", xml_escape(album));
+        }
+        output += &format!(
+            "      <annotation>{}</annotation>\n",
+            xml_escape(&row.recognized_at)
+        );
+        output += "    </track>\n";
+    }
+
+    output += "  </trackList>\n</playlist>\n";
+    output
+}
+
+fn write_jspf(rows: &[HistoryRow], title: &str) -> Result<String, Box<dyn Error>> {
+    let tracks: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|row| {
+            let (artist_name, track_title) = artist_and_title(row);
+            let mut track = json!({
+                "title": track_title,
+                "annotation": row.recognized_at,
+            });
+            if let Some(artist_name) = artist_name {
+                track["creator"] = json!(artist_name);
+            }
+            if let Some(ref album) = row.album {
+                track["album"] = json!(album);
+            }
+            if let Some(location) = track_location(row) {
+                track["location"] = json!([location]);
+            }
+            track
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&json!({
+        "playlist": {
+            "title": title,
+            "creator": "SongRec",
+            "date": Local::now().to_rfc3339(),
+            "track": tracks,
+        }
+    }))?)
+}
+
+/// The "track_metadata" object of a ListenBrainz listen.
+pub fn listenbrainz_track_metadata(
+    artist_name: &str,
+    track_name: &str,
+    release_name: Option<&str>,
+    isrc: Option<&str>,
+    origin_url: Option<&str>,
+) -> serde_json::Value {
+    let mut track_metadata = json!({
+        "artist_name": artist_name,
+        "track_name": track_name,
+        "additional_info": {
+            "submission_client": "SongRec",
+            "submission_client_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    if let Some(release_name) = release_name.filter(|value| !value.is_empty()) {
+        track_metadata["release_name"] = json!(release_name);
+    }
+    if let Some(isrc) = isrc.filter(|value| !value.is_empty()) {
+        track_metadata["additional_info"]["isrc"] = json!(isrc);
+    }
+    if let Some(origin_url) = origin_url {
+        track_metadata["additional_info"]["origin_url"] = json!(origin_url);
+    }
+    track_metadata
+}
+
+fn write_listenbrainz(rows: &[HistoryRow]) -> Result<String, Box<dyn Error>> {
+    let listens: Vec<serde_json::Value> = rows
+        .iter()
+        .filter_map(|row| {
+            let (artist_name, track_title) = artist_and_title(row);
+            Some(json!({
+                "listened_at": listened_at(row)?,
+                "track_metadata": listenbrainz_track_metadata(
+                    artist_name.unwrap_or_default(),
+                    track_title,
+                    row.album.as_deref(),
+                    row.isrc.as_deref(),
+                    track_location(row).as_deref(),
+                ),
+            }))
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&json!({
+        "listen_type": "import",
+        "payload": listens,
+    }))?)
+}
+
+pub fn write_playlist(
+    format: PlaylistFormat,
+    rows: &[HistoryRow],
+    title: &str,
+) -> Result<String, Box<dyn Error>> {
+    match format {
+        PlaylistFormat::CSV => write_csv(rows),
+        PlaylistFormat::M3U8 => Ok(write_m3u8(rows, title)),
+        PlaylistFormat::XSPF => Ok(write_xspf(rows, title)),
+        PlaylistFormat::JSPF => write_jspf(rows, title),
+        PlaylistFormat::ListenBrainz => write_listenbrainz(rows),
+    }
+}
+
+#[test]
+fn test_playlist_formats() {
+    let row = HistoryRow {
+        song_name: "Simon & Garfunkel - The Boxer".to_string(),
+        artist_name: Some("Simon & Garfunkel".to_string()),
+        album: None,
+        track_key: Some("local:/music/The Boxer.flac".to_string()),
+        release_year: None,
+        genre: None,
+        isrc: None,
+        label: None,
+        recognized_at: "2024-09-01T10:00:00Z".to_string(),
+        device_name: None,
+        cover_path: None,
+        capture_path: None,
+        is_favorite: false,
+        times_recognized: 1,
+        shazam_json: None,
+    };
+
+    assert_eq!(
+        write_m3u8(std::slice::from_ref(&row), "History"),
+        "#EXTM3U\n#PLAYLIST:History\n\
+        #EXTINF:-1,Simon & Garfunkel - The Boxer\nfile:///music/The%20Boxer.flac\n"
+    );
+    assert!(
+        write_xspf(std::slice::from_ref(&row), "History")
+            .contains("<title>The Boxer</title>\n      <creator>Simon &amp; Garfunkel</creator>")
+    );
+
+    let listens: serde_json::Value =
+        serde_json::from_str(&write_listenbrainz(&[row]).unwrap()).unwrap();
+    assert_eq!(listens["payload"][0]["listened_at"], 1725184800);
+    assert_eq!(
+        listens["payload"][0]["track_metadata"]["track_name"],
+        "The Boxer"
+    );
+}
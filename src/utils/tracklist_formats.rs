@@ -0,0 +1,236 @@
+//! Writers for the tracklists produced by the scan mode, in formats
+//! understood by audio players, editors and archival tooling.
+
+use std::path::Path;
+
+use crate::core::file_scanner::ScanSegment;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracklistFormat {
+    CueSheet,
+    FFMetadata,
+    WebVTT,
+    AudacityLabels,
+}
+
+fn segment_title(segment: &ScanSegment) -> String {
+    match segment.artist_name.is_empty() {
+        true => segment.song_name.clone(),
+        false => format!("{} - {}", segment.artist_name, segment.song_name),
+    }
+}
+
+/// Format a position as "MM:SS:FF", with 75 frames per second.
+fn cue_timestamp(secs: f32) -> String {
+    let frames = (secs * 75.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        frames / 75 / 60,
+        frames / 75 % 60,
+        frames % 75
+    )
+}
+
+/// Format a position as "HH:MM:SS.mmm".
+fn webvtt_timestamp(secs: f32) -> String {
+    let millis = (secs * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// The CUE sheet syntax has no escaping mechanism for double quotes, nor
+/// for line breaks.
+fn cue_string(string: &str) -> String {
+    format!(
+        "\"{}\"",
+        string.replace('"', "'").replace(['\r', '\n'], " ")
+    )
+}
+
+pub fn write_cue_sheet(segments: &[ScanSegment], input_file: &str) -> String {
+    let path = Path::new(input_file);
+
+    let file_type = match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .as_deref()
+    {
+        Some("mp3") => "MP3",
+        Some("aif") | Some("aiff") => "AIFF",
+        _ => "WAVE",
+    };
+
+    let mut output = String::new();
+
+    if let Some(title) = path.file_stem().and_then(|stem| stem.to_str()) {
+        output += &format!("TITLE {}\n", cue_string(title));
+    }
+
+    output += &format!(
+        "FILE {} {}\n",
+        cue_string(
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(input_file)
+        ),
+        file_type
+    );
+
+    for (index, segment) in segments.iter().enumerate() {
+        output += &format!("  TRACK {:02} AUDIO\n", index + 1);
+        output += &format!("    TITLE {}\n", cue_string(&segment.song_name));
+        if !segment.artist_name.is_empty() {
+            output += &format!("    PERFORMER {}\n", cue_string(&segment.artist_name));
+        }
+        output += &format!("    INDEX 01 {}\n", cue_timestamp(segment.start_secs));
+    }
+
+    output
+}
+
+/// Chapters in FFmpeg's metadata format, which can be applied with
+/// "ffmpeg -i input -i chapters.txt -map_metadata 1 -codec copy output".
+pub fn write_ffmetadata(segments: &[ScanSegment]) -> String {
+    fn escape(string: &str) -> String {
+        let mut escaped = String::new();
+        for character in string.chars() {
+            if matches!(character, '=' | ';' | '#' | '\\' | '\n') {
+                escaped.push('\\');
+            }
+            escaped.push(character);
+        }
+        escaped
+    }
+
+    let mut output = String::from(";FFMETADATA1\n");
+
+    for segment in segments {
+        output += "\n[CHAPTER]\nTIMEBASE=1/1000\n";
+        output += &format!("START={}\n", (segment.start_secs * 1000.0).round() as u64);
+        output += &format!("END={}\n", (segment.end_secs * 1000.0).round() as u64);
+        output += &format!("title={}\n", escape(&segment_title(segment)));
+    }
+
+    output
+}
+
+pub fn write_webvtt(segments: &[ScanSegment]) -> String {
+    let mut output = String::from("WEBVTT\n");
+
+    for (index, segment) in segments.iter().enumerate() {
+        output += &format!(
+            "\n{}\n{} --> {}\n{}\n",
+            index + 1,
+            webvtt_timestamp(segment.start_secs),
+            webvtt_timestamp(segment.end_secs),
+            // A line break would end the cue, and "-->" is escaped along
+            // with the other markup characters
+            segment_title(segment)
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace(['\r', '\n'], " ")
+        );
+    }
+
+    output
+}
+
+/// A label track which can be imported through "File > Import > Labels..."
+/// in Audacity.
+pub fn write_audacity_labels(segments: &[ScanSegment]) -> String {
+    let mut output = String::new();
+
+    for segment in segments {
+        output += &format!(
+            "{:.6}\t{:.6}\t{}\n",
+            segment.start_secs,
+            segment.end_secs,
+            segment_title(segment).replace(['\t', '\r', '\n'], " ")
+        );
+    }
+
+    output
+}
+
+#[test]
+fn test_tracklist_timestamps() {
+    assert_eq!(cue_timestamp(0.0), "00:00:00");
+    assert_eq!(cue_timestamp(6001.4), "100:01:30");
+    assert_eq!(webvtt_timestamp(3723.25), "01:02:03.250");
+}
+
+#[cfg(test)]
+fn example_segments() -> Vec<ScanSegment> {
+    vec![
+        ScanSegment {
+            start_secs: 0.0,
+            end_secs: 185.5,
+            artist_name: "Artist".to_string(),
+            song_name: "First \"Song\"".to_string(),
+            album_name: None,
+            track_key: "1".to_string(),
+        },
+        ScanSegment {
+            start_secs: 185.5,
+            end_secs: 372.25,
+            artist_name: String::new(),
+            song_name: "A --> B\nLive".to_string(),
+            album_name: None,
+            track_key: "2".to_string(),
+        },
+    ]
+}
+
+#[test]
+fn test_write_cue_sheet() {
+    assert_eq!(
+        write_cue_sheet(&example_segments(), "/music/Mix Tape.mp3"),
+        "TITLE \"Mix Tape\"\n\
+        FILE \"Mix Tape.mp3\" MP3\n  \
+          TRACK 01 AUDIO\n    \
+            TITLE \"First 'Song'\"\n    \
+            PERFORMER \"Artist\"\n    \
+            INDEX 01 00:00:00\n  \
+          TRACK 02 AUDIO\n    \
+            TITLE \"A --> B Live\"\n    \
+            INDEX 01 03:05:38\n"
+    );
+}
+
+#[test]
+fn test_write_ffmetadata() {
+    assert_eq!(
+        write_ffmetadata(&example_segments()),
+        ";FFMETADATA1\n\
+        \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=185500\n\
+        title=Artist - First \"Song\"\n\
+        \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=185500\nEND=372250\n\
+        title=A --> B\\\nLive\n"
+    );
+}
+
+#[test]
+fn test_write_webvtt() {
+    assert_eq!(
+        write_webvtt(&example_segments()),
+        "WEBVTT\n\
+        \n1\n00:00:00.000 --> 00:03:05.500\nArtist - First \"Song\"\n\
+        \n2\n00:03:05.500 --> 00:06:12.250\nA --&gt; B Live\n"
+    );
+}
+
+#[test]
+fn test_write_audacity_labels() {
+    assert_eq!(
+        write_audacity_labels(&example_segments()),
+        "0.000000\t185.500000\tArtist - First \"Song\"\n\
+        185.500000\t372.250000\tA --> B Live\n"
+    );
+}